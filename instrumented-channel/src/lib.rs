@@ -1,5 +1,6 @@
 pub mod channel_metrics;
 
+use aptos_moving_average::MovingAverage;
 use channel_metrics::ChannelMetrics;
 use delegate::delegate;
 /**
@@ -23,6 +24,7 @@ async fn main() {
 ```
  **/
 use kanal::{AsyncReceiver, AsyncSender, ReceiveError, SendError};
+use std::sync::{Arc, Mutex};
 
 pub struct InstrumentedAsyncSender<T> {
     pub(crate) sender: AsyncSender<T>,
@@ -38,7 +40,7 @@ impl<T> InstrumentedAsyncSender<T> {
             pub fn len(&self) -> usize;
             pub fn is_empty(&self) -> bool;
             pub fn is_full(&self) -> bool;
-            pub fn capacity(&self);
+            pub fn capacity(&self) -> usize;
             pub fn receiver_count(&self) -> u32;
             pub fn sender_count(&self) -> u32;
             pub fn close(&self) -> bool;
@@ -85,10 +87,15 @@ impl<T> Clone for InstrumentedAsyncSender<T> {
     }
 }
 
+/// Window over which `InstrumentedAsyncReceiver::receive_rate` is averaged.
+pub const RECEIVE_RATE_WINDOW_MILLIS: u64 = 60_000;
+
 pub struct InstrumentedAsyncReceiver<T> {
     pub(crate) receiver: AsyncReceiver<T>,
     // Metrics
     pub(crate) channel_metrics: ChannelMetrics,
+    // Shared between clones, since they receive from the same channel
+    pub(crate) receive_rate: Arc<Mutex<MovingAverage>>,
 }
 
 impl<T> InstrumentedAsyncReceiver<T> {
@@ -99,7 +106,7 @@ impl<T> InstrumentedAsyncReceiver<T> {
             pub fn len(&self) -> usize;
             pub fn is_empty(&self) -> bool;
             pub fn is_full(&self) -> bool;
-            pub fn capacity(&self);
+            pub fn capacity(&self) -> usize;
             pub fn receiver_count(&self) -> u32;
             pub fn sender_count(&self) -> u32;
             pub fn close(&self) -> bool;
@@ -112,6 +119,26 @@ impl<T> InstrumentedAsyncReceiver<T> {
         Self {
             receiver,
             channel_metrics,
+            receive_rate: Arc::new(Mutex::new(MovingAverage::new(RECEIVE_RATE_WINDOW_MILLIS))),
+        }
+    }
+
+    /// Name of the step whose output this channel carries.
+    pub fn output_of(&self) -> &str {
+        &self.channel_metrics.labels.output_of
+    }
+
+    /// Messages received per second over the last `RECEIVE_RATE_WINDOW_MILLIS`.
+    pub fn receive_rate(&self) -> f64 {
+        let mut receive_rate = self.receive_rate.lock().unwrap();
+        // Ticking drops the messages that fell out of the window, even if none came in since
+        receive_rate.tick_now(0);
+        let rate = receive_rate.avg();
+        // No time has passed yet if the channel was created within the same millisecond
+        if rate.is_finite() {
+            rate
+        } else {
+            0.0
         }
     }
 
@@ -130,6 +157,7 @@ impl<T> InstrumentedAsyncReceiver<T> {
                 .log_receive_duration(receive_duration.as_secs_f64())
                 .log_channel_size(self.receiver.len() as u64)
                 .inc_received_messages_count();
+            self.receive_rate.lock().unwrap().tick_now(1);
         }
 
         result
@@ -141,6 +169,7 @@ impl<T> Clone for InstrumentedAsyncReceiver<T> {
         Self {
             receiver: self.receiver.clone(),
             channel_metrics: self.channel_metrics.clone(),
+            receive_rate: self.receive_rate.clone(),
         }
     }
 }
@@ -190,4 +219,19 @@ mod tests {
         let metrics = gather_metrics_to_string();
         println!("{metrics}");
     }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_receiver_stats() {
+        let (sender, receiver) = instrumented_bounded_channel("stats_channel", 10);
+        assert_eq!(receiver.capacity(), 10);
+        assert_eq!(receiver.output_of(), "stats_channel");
+
+        sender.send(1).await.unwrap();
+        sender.send(2).await.unwrap();
+        assert_eq!(receiver.len(), 2);
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        receiver.recv().await.unwrap();
+        assert!(receiver.receive_rate() > 0.0);
+    }
 }
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS market_state_history;
+DROP TABLE IF EXISTS current_market_state;
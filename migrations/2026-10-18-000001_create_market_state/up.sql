@@ -0,0 +1,48 @@
+-- Kizo resource state captured from write set changes
+
+-- Latest value of each Kizo resource
+CREATE TABLE current_market_state (
+    resource_address VARCHAR(66) NOT NULL,
+    resource_type TEXT NOT NULL,
+    -- Decoded fields (NULL when the resource doesn't carry them)
+    market_id BIGINT DEFAULT NULL,
+    total_pool BIGINT DEFAULT NULL,
+    yes_pool BIGINT DEFAULT NULL,
+    no_pool BIGINT DEFAULT NULL,
+    yield_deposited BIGINT DEFAULT NULL,
+    resolved BOOLEAN DEFAULT NULL,
+    -- Raw resource JSON, NULL once the resource is deleted
+    data JSONB DEFAULT NULL,
+    is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    last_transaction_version BIGINT NOT NULL,
+    last_transaction_block_height BIGINT NOT NULL,
+    last_transaction_timestamp TIMESTAMP NOT NULL,
+    inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (resource_address, resource_type)
+);
+
+-- Every write/delete of a Kizo resource
+CREATE TABLE market_state_history (
+    transaction_version BIGINT NOT NULL,
+    write_set_change_index BIGINT NOT NULL,
+    resource_address VARCHAR(66) NOT NULL,
+    resource_type TEXT NOT NULL,
+    market_id BIGINT DEFAULT NULL,
+    total_pool BIGINT DEFAULT NULL,
+    yes_pool BIGINT DEFAULT NULL,
+    no_pool BIGINT DEFAULT NULL,
+    yield_deposited BIGINT DEFAULT NULL,
+    resolved BOOLEAN DEFAULT NULL,
+    data JSONB DEFAULT NULL,
+    is_deleted BOOLEAN NOT NULL DEFAULT FALSE,
+    transaction_block_height BIGINT NOT NULL,
+    transaction_timestamp TIMESTAMP NOT NULL,
+    inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (transaction_version, write_set_change_index)
+);
+
+CREATE INDEX idx_current_market_state_market_id ON current_market_state(market_id);
+CREATE INDEX idx_current_market_state_last_transaction_version ON current_market_state(last_transaction_version);
+
+CREATE INDEX idx_market_state_history_resource ON market_state_history(resource_address, resource_type);
+CREATE INDEX idx_market_state_history_market_id ON market_state_history(market_id);
@@ -0,0 +1,6 @@
+ALTER TABLE protocol_fees DROP COLUMN IF EXISTS extra;
+ALTER TABLE yield_deposits DROP COLUMN IF EXISTS extra;
+ALTER TABLE winnings_claims DROP COLUMN IF EXISTS extra;
+ALTER TABLE market_resolutions DROP COLUMN IF EXISTS extra;
+ALTER TABLE bets DROP COLUMN IF EXISTS extra;
+ALTER TABLE markets DROP COLUMN IF EXISTS extra;
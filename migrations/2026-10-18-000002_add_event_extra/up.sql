@@ -0,0 +1,8 @@
+-- Event fields not described by any known schema version, kept verbatim so contract
+-- upgrades that add fields don't lose data before the indexer learns about them.
+ALTER TABLE markets ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE bets ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE market_resolutions ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE winnings_claims ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE yield_deposits ADD COLUMN IF NOT EXISTS extra JSONB;
+ALTER TABLE protocol_fees ADD COLUMN IF NOT EXISTS extra JSONB;
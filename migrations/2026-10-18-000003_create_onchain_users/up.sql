@@ -0,0 +1,16 @@
+-- Addresses seen on chain as bettors, claimers or market creators
+CREATE TABLE onchain_users (
+    address VARCHAR(66) PRIMARY KEY,
+    first_seen_version BIGINT NOT NULL,
+    first_seen_timestamp TIMESTAMP NOT NULL,
+    last_seen_version BIGINT NOT NULL,
+    last_seen_timestamp TIMESTAMP NOT NULL,
+    activity_count BIGINT NOT NULL DEFAULT 0,
+    bet_count BIGINT NOT NULL DEFAULT 0,
+    claim_count BIGINT NOT NULL DEFAULT 0,
+    markets_created_count BIGINT NOT NULL DEFAULT 0,
+    inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_onchain_users_last_seen_version ON onchain_users(last_seen_version);
+CREATE INDEX idx_onchain_users_activity_count ON onchain_users(activity_count);
@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS idx_markets_creator_addr;
+ALTER TABLE markets DROP COLUMN IF EXISTS creator_addr;
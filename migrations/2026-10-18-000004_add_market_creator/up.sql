@@ -0,0 +1,5 @@
+-- Sender of the transaction that created the market. Market creation events don't name the
+-- creator, and rewinds need it to undo onchain_users.markets_created_count.
+ALTER TABLE markets ADD COLUMN IF NOT EXISTS creator_addr VARCHAR(66);
+
+CREATE INDEX idx_markets_creator_addr ON markets(creator_addr);
@@ -0,0 +1,7 @@
+DROP VIEW IF EXISTS user_asset_stats;
+DROP VIEW IF EXISTS asset_stats;
+DROP VIEW IF EXISTS bets_with_asset;
+DROP VIEW IF EXISTS markets_with_asset;
+DROP TABLE IF EXISTS asset_metadata;
+DROP INDEX IF EXISTS idx_markets_asset_type;
+ALTER TABLE markets DROP COLUMN IF EXISTS asset_type;
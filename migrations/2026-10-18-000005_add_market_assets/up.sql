@@ -0,0 +1,106 @@
+-- Asset a market is denominated in: a coin type such as 0x1::aptos_coin::AptosCoin, or the
+-- address of a fungible asset metadata object. Markets indexed before this were all in APT.
+ALTER TABLE markets ADD COLUMN IF NOT EXISTS asset_type TEXT NOT NULL DEFAULT '0x1::aptos_coin::AptosCoin';
+
+CREATE INDEX idx_markets_asset_type ON markets(asset_type);
+
+-- Symbol and decimals from 0x1::coin::CoinInfo and 0x1::fungible_asset::Metadata write-sets
+CREATE TABLE asset_metadata (
+    asset_type TEXT PRIMARY KEY,
+    standard VARCHAR(10) NOT NULL,
+    name TEXT NOT NULL,
+    symbol TEXT NOT NULL,
+    decimals INTEGER NOT NULL,
+    last_transaction_version BIGINT NOT NULL,
+    inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- APT was created at genesis, long before the first Kizo transaction
+INSERT INTO asset_metadata (asset_type, standard, name, symbol, decimals, last_transaction_version)
+VALUES
+    ('0x1::aptos_coin::AptosCoin', 'coin', 'Aptos Coin', 'APT', 8, 0),
+    ('0x000000000000000000000000000000000000000000000000000000000000000a', 'fa', 'Aptos Coin', 'APT', 8, 0)
+ON CONFLICT (asset_type) DO NOTHING;
+
+-- Markets and bets with decimal-adjusted amounts. Amounts stay NULL until the asset's metadata
+-- has been indexed.
+CREATE VIEW markets_with_asset AS
+SELECT
+    m.*,
+    a.symbol AS asset_symbol,
+    a.decimals AS asset_decimals,
+    m.total_yield_earned / POWER(10::NUMERIC, a.decimals) AS total_yield_earned_decimal
+FROM markets m
+LEFT JOIN asset_metadata a ON a.asset_type = m.asset_type;
+
+CREATE VIEW bets_with_asset AS
+SELECT
+    b.*,
+    m.asset_type,
+    a.symbol AS asset_symbol,
+    a.decimals AS asset_decimals,
+    b.amount / POWER(10::NUMERIC, a.decimals) AS amount_decimal
+FROM bets b
+JOIN markets m ON m.market_id = b.market_id
+LEFT JOIN asset_metadata a ON a.asset_type = m.asset_type;
+
+-- Amounts in different assets can't be added up, so every aggregate is per asset. A market is
+-- open until it has a resolution.
+CREATE VIEW asset_stats AS
+WITH market_totals AS (
+    SELECT
+        m.market_id,
+        m.asset_type,
+        r.market_id IS NULL AND NOT COALESCE(m.resolved, false) AS is_open,
+        COALESCE(SUM(b.amount), 0) AS volume,
+        COUNT(b.bet_id) AS bet_count
+    FROM markets m
+    LEFT JOIN market_resolutions r ON r.market_id = m.market_id
+    LEFT JOIN bets b ON b.market_id = m.market_id
+    GROUP BY m.market_id, m.asset_type, r.market_id, m.resolved
+)
+SELECT
+    t.asset_type,
+    a.symbol AS asset_symbol,
+    COUNT(*) AS market_count,
+    COUNT(*) FILTER (WHERE t.is_open) AS active_market_count,
+    (
+        SELECT COUNT(DISTINCT b.user_addr)
+        FROM bets b JOIN markets m ON m.market_id = b.market_id
+        WHERE m.asset_type = t.asset_type
+    ) AS bettor_count,
+    SUM(t.volume) AS volume,
+    COALESCE(SUM(t.volume) FILTER (WHERE t.is_open), 0) AS tvl,
+    SUM(t.volume) / POWER(10::NUMERIC, a.decimals) AS volume_decimal,
+    COALESCE(SUM(t.volume) FILTER (WHERE t.is_open), 0) / POWER(10::NUMERIC, a.decimals)
+        AS tvl_decimal
+FROM market_totals t
+LEFT JOIN asset_metadata a ON a.asset_type = t.asset_type
+GROUP BY t.asset_type, a.symbol, a.decimals;
+
+-- Per-asset leaderboard
+CREATE VIEW user_asset_stats AS
+WITH bet_totals AS (
+    SELECT b.user_addr, m.asset_type, COUNT(*) AS bet_count, SUM(b.amount) AS volume
+    FROM bets b
+    JOIN markets m ON m.market_id = b.market_id
+    GROUP BY b.user_addr, m.asset_type
+),
+claim_totals AS (
+    SELECT w.user_addr, m.asset_type, SUM(w.winning_amount) AS winnings
+    FROM winnings_claims w
+    JOIN bets b ON b.bet_id = w.bet_id
+    JOIN markets m ON m.market_id = b.market_id
+    GROUP BY w.user_addr, m.asset_type
+)
+SELECT
+    t.user_addr,
+    t.asset_type,
+    t.bet_count,
+    t.volume,
+    COALESCE(c.winnings, 0) AS winnings,
+    t.volume / POWER(10::NUMERIC, a.decimals) AS volume_decimal,
+    COALESCE(c.winnings, 0) / POWER(10::NUMERIC, a.decimals) AS winnings_decimal
+FROM bet_totals t
+LEFT JOIN claim_totals c ON c.user_addr = t.user_addr AND c.asset_type = t.asset_type
+LEFT JOIN asset_metadata a ON a.asset_type = t.asset_type;
@@ -0,0 +1,13 @@
+DROP TABLE IF EXISTS protocol_snapshots;
+
+DROP INDEX IF EXISTS idx_protocol_fees_transaction_timestamp;
+DROP INDEX IF EXISTS idx_yield_deposits_transaction_timestamp;
+DROP INDEX IF EXISTS idx_bets_transaction_timestamp;
+DROP INDEX IF EXISTS idx_markets_transaction_timestamp;
+
+ALTER TABLE protocol_fees DROP COLUMN IF EXISTS transaction_timestamp;
+ALTER TABLE yield_deposits DROP COLUMN IF EXISTS transaction_timestamp;
+ALTER TABLE winnings_claims DROP COLUMN IF EXISTS transaction_timestamp;
+ALTER TABLE market_resolutions DROP COLUMN IF EXISTS transaction_timestamp;
+ALTER TABLE bets DROP COLUMN IF EXISTS transaction_timestamp;
+ALTER TABLE markets DROP COLUMN IF EXISTS transaction_timestamp;
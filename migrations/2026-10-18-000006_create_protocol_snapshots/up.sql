@@ -0,0 +1,56 @@
+-- Block time of the transaction that emitted each event. Time-series stats are bucketed on it,
+-- so backfills land in the right hour and day. NULL for rows indexed before this migration that
+-- couldn't be matched below; rewind and re-index to fill them in.
+ALTER TABLE markets ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+ALTER TABLE bets ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+ALTER TABLE market_resolutions ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+ALTER TABLE winnings_claims ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+ALTER TABLE yield_deposits ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+ALTER TABLE protocol_fees ADD COLUMN IF NOT EXISTS transaction_timestamp TIMESTAMP;
+
+-- Most Kizo transactions also write a Kizo resource, whose history already has the block time
+CREATE TEMPORARY TABLE known_timestamps AS
+SELECT DISTINCT transaction_version, transaction_timestamp FROM market_state_history;
+
+UPDATE markets t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+UPDATE bets t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+UPDATE market_resolutions t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+UPDATE winnings_claims t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+UPDATE yield_deposits t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+UPDATE protocol_fees t SET transaction_timestamp = k.transaction_timestamp
+FROM known_timestamps k WHERE k.transaction_version = t.transaction_version;
+
+DROP TABLE known_timestamps;
+
+CREATE INDEX idx_markets_transaction_timestamp ON markets(transaction_timestamp);
+CREATE INDEX idx_bets_transaction_timestamp ON bets(transaction_timestamp);
+CREATE INDEX idx_yield_deposits_transaction_timestamp ON yield_deposits(transaction_timestamp);
+CREATE INDEX idx_protocol_fees_transaction_timestamp ON protocol_fees(transaction_timestamp);
+
+-- Hourly and daily stats per asset, both protocol-wide (yield_protocol_addr = '') and per yield
+-- protocol. Amounts are raw integers in the asset; TVL and active markets are as of the end of
+-- the bucket, everything else is activity within it.
+CREATE TABLE protocol_snapshots (
+    period VARCHAR(4) NOT NULL,
+    bucket_start TIMESTAMP NOT NULL,
+    yield_protocol_addr VARCHAR(66) NOT NULL,
+    asset_type TEXT NOT NULL,
+    tvl NUMERIC NOT NULL,
+    volume NUMERIC NOT NULL,
+    bet_count BIGINT NOT NULL,
+    active_market_count BIGINT NOT NULL,
+    unique_bettor_count BIGINT NOT NULL,
+    yield_deposited NUMERIC NOT NULL,
+    fees_collected NUMERIC NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (period, bucket_start, yield_protocol_addr, asset_type),
+    CHECK (period IN ('hour', 'day'))
+);
+
+CREATE INDEX idx_protocol_snapshots_series
+    ON protocol_snapshots(period, yield_protocol_addr, asset_type, bucket_start);
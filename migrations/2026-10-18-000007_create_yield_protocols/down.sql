@@ -0,0 +1,3 @@
+DROP VIEW IF EXISTS yield_protocol_stats;
+DROP VIEW IF EXISTS market_yield;
+DROP TABLE IF EXISTS yield_protocols;
@@ -0,0 +1,57 @@
+-- Names of the yield protocols markets deposit into, synced from the `yield_protocols` section of
+-- the indexer config on startup. backend_protocol_id links to the backend's protocols.id.
+CREATE TABLE yield_protocols (
+    address VARCHAR(66) PRIMARY KEY,
+    name TEXT NOT NULL,
+    backend_protocol_id TEXT,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+-- Yield of each market: principal is everything deposited into its yield protocol, realized
+-- yield is what the market reported at resolution. effective_apy is the simple annualized return
+-- between creation and resolution, in percent like the backend's protocols."baseApy".
+CREATE VIEW market_yield AS
+WITH deposits AS (
+    SELECT market_id, SUM(amount) AS principal, COUNT(*) AS deposit_count
+    FROM yield_deposits
+    GROUP BY market_id
+)
+SELECT
+    m.market_id,
+    m.asset_type,
+    m.yield_protocol_addr,
+    y.name AS yield_protocol_name,
+    COALESCE(d.deposit_count, 0) AS deposit_count,
+    COALESCE(d.principal, 0) AS principal,
+    r.total_yield_earned AS realized_yield,
+    m.transaction_timestamp AS created_at,
+    r.transaction_timestamp AS resolved_at,
+    CASE
+        WHEN d.principal > 0 AND r.transaction_timestamp > m.transaction_timestamp
+        THEN r.total_yield_earned / d.principal * 100 * (365.25 * 86400)
+            / EXTRACT(EPOCH FROM r.transaction_timestamp - m.transaction_timestamp)
+    END AS effective_apy
+FROM markets m
+LEFT JOIN deposits d ON d.market_id = m.market_id
+LEFT JOIN market_resolutions r ON r.market_id = m.market_id
+LEFT JOIN yield_protocols y ON y.address = m.yield_protocol_addr;
+
+-- Per yield protocol and asset. effective_apy weighs each resolved market by its principal and
+-- duration, so a large long-running market counts for more than a small short one.
+CREATE VIEW yield_protocol_stats AS
+SELECT
+    yield_protocol_addr,
+    yield_protocol_name,
+    asset_type,
+    COUNT(*) AS market_count,
+    COUNT(resolved_at) AS resolved_market_count,
+    SUM(principal) AS principal,
+    COALESCE(SUM(realized_yield), 0) AS realized_yield,
+    SUM(realized_yield) FILTER (WHERE effective_apy IS NOT NULL) * 100 * (365.25 * 86400)
+        / NULLIF(
+            SUM(principal * EXTRACT(EPOCH FROM resolved_at - created_at))
+                FILTER (WHERE effective_apy IS NOT NULL),
+            0
+        ) AS effective_apy
+FROM market_yield
+GROUP BY yield_protocol_addr, yield_protocol_name, asset_type;
@@ -0,0 +1,6 @@
+ALTER TABLE protocol_fees DROP CONSTRAINT IF EXISTS protocol_fees_event_key;
+ALTER TABLE yield_deposits DROP CONSTRAINT IF EXISTS yield_deposits_event_key;
+ALTER TABLE winnings_claims DROP CONSTRAINT IF EXISTS winnings_claims_event_key;
+ALTER TABLE protocol_fees DROP COLUMN IF EXISTS event_index;
+ALTER TABLE yield_deposits DROP COLUMN IF EXISTS event_index;
+ALTER TABLE winnings_claims DROP COLUMN IF EXISTS event_index;
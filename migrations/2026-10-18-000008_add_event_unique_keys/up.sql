@@ -0,0 +1,15 @@
+-- Claims, deposits and fees have generated ids, so without a key on the event itself every
+-- re-write of a batch (a retry after a DB outage, a replay) stored the events again. Key them on
+-- the event's index in its transaction. Rows indexed before have no index and are left as they
+-- are: which of them are copies can't be told apart from a transaction emitting the same event
+-- twice.
+ALTER TABLE winnings_claims ADD COLUMN IF NOT EXISTS event_index BIGINT;
+ALTER TABLE yield_deposits ADD COLUMN IF NOT EXISTS event_index BIGINT;
+ALTER TABLE protocol_fees ADD COLUMN IF NOT EXISTS event_index BIGINT;
+
+ALTER TABLE winnings_claims ADD CONSTRAINT winnings_claims_event_key
+    UNIQUE (transaction_version, event_index);
+ALTER TABLE yield_deposits ADD CONSTRAINT yield_deposits_event_key
+    UNIQUE (transaction_version, event_index);
+ALTER TABLE protocol_fees ADD CONSTRAINT protocol_fees_event_key
+    UNIQUE (transaction_version, event_index);
@@ -0,0 +1,122 @@
+//! The subset of the Move module ABI JSON that the generator needs.
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MoveModuleAbi {
+    pub address: String,
+    pub name: String,
+    #[serde(default)]
+    pub structs: Vec<MoveStructAbi>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MoveStructAbi {
+    pub name: String,
+    /// Only present in ABIs produced by newer nodes and CLIs.
+    #[serde(default)]
+    pub is_event: Option<bool>,
+    #[serde(default)]
+    pub generic_type_params: Vec<Value>,
+    #[serde(default)]
+    pub fields: Vec<MoveStructFieldAbi>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MoveStructFieldAbi {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub typ: String,
+}
+
+impl MoveModuleAbi {
+    /// Parses every module ABI found in `json`. This accepts a bare ABI object, a
+    /// `{"bytecode": .., "abi": {..}}` module as returned by the node API, a list of those, or the
+    /// `{"Result": [..]}` wrapper printed by `aptos account list --query modules`.
+    pub fn parse_all(json: &str) -> Result<Vec<MoveModuleAbi>> {
+        let value: Value = serde_json::from_str(json).context("ABI is not valid JSON")?;
+        let value = match value {
+            Value::Object(mut object) if object.contains_key("Result") => {
+                object.remove("Result").unwrap_or_default()
+            },
+            other => other,
+        };
+        let entries = match value {
+            Value::Array(entries) => entries,
+            other => vec![other],
+        };
+
+        let modules = entries
+            .into_iter()
+            .map(|entry| {
+                let abi = match entry {
+                    Value::Object(mut object) if object.contains_key("abi") => {
+                        object.remove("abi").unwrap_or_default()
+                    },
+                    other => other,
+                };
+                serde_json::from_value::<MoveModuleAbi>(abi).context("Failed to parse module ABI")
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if modules.is_empty() {
+            bail!("No module ABI found");
+        }
+        Ok(modules)
+    }
+
+    /// The `address::module` prefix shared by every type declared in this module.
+    pub fn module_id(&self) -> String {
+        format!("{}::{}", self.address, self.name)
+    }
+}
+
+impl MoveStructAbi {
+    /// Older ABIs don't flag events, so fall back to the `*Event` naming convention.
+    pub fn is_event(&self) -> bool {
+        self.is_event
+            .unwrap_or_else(|| self.name.ends_with("Event"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"{
+        "address": "0x1",
+        "name": "market",
+        "friends": [],
+        "exposed_functions": [],
+        "structs": [
+            {
+                "name": "MarketCreatedEvent",
+                "is_native": false,
+                "abilities": ["drop", "store"],
+                "generic_type_params": [],
+                "fields": [{"name": "market_id", "type": "u64"}]
+            },
+            {
+                "name": "Config",
+                "is_native": false,
+                "abilities": ["key"],
+                "generic_type_params": [],
+                "fields": [{"name": "admin", "type": "address"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_bare_and_wrapped_abi() {
+        let bare = MoveModuleAbi::parse_all(ABI).unwrap();
+        assert_eq!(bare.len(), 1);
+        assert_eq!(bare[0].module_id(), "0x1::market");
+
+        let wrapped = format!(r#"{{"Result": [{{"bytecode": "0x00", "abi": {ABI}}}]}}"#);
+        let wrapped = MoveModuleAbi::parse_all(&wrapped).unwrap();
+        assert_eq!(wrapped[0].structs.len(), 2);
+        assert!(wrapped[0].structs[0].is_event());
+        assert!(!wrapped[0].structs[1].is_event());
+    }
+}
@@ -0,0 +1,459 @@
+//! Generates indexer code for the events declared in a Move module ABI: serde event structs,
+//! diesel insertable rows with a `from_event` conversion, `diesel::table!` definitions, and the
+//! SQL migration creating the tables.
+//!
+//! Each event gets its own table keyed by `(transaction_version, event_index)`. Move types are
+//! mapped as described in [`move_type::MoveType::column_mapping`].
+
+pub mod abi;
+pub mod move_type;
+
+use crate::{
+    abi::{MoveModuleAbi, MoveStructAbi},
+    move_type::{ColumnMapping, Conversion, MoveType},
+};
+use anyhow::{bail, Context, Result};
+use std::{collections::BTreeSet, fmt::Write};
+
+/// Columns every generated table has in addition to the event fields.
+const METADATA_COLUMNS: [&str; 4] = [
+    "transaction_version",
+    "transaction_block_height",
+    "event_index",
+    "inserted_at",
+];
+
+const RUST_KEYWORDS: [&str; 38] = [
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while",
+];
+
+#[derive(Clone, Debug)]
+pub struct GeneratedCode {
+    /// Event structs, insertable rows and event type constants.
+    pub models: String,
+    /// `diesel::table!` definitions for the generated tables.
+    pub schema: String,
+    pub up_sql: String,
+    pub down_sql: String,
+    /// Events that could not be generated, with the reason.
+    pub skipped: Vec<String>,
+}
+
+struct EventTable {
+    event_name: String,
+    event_type: String,
+    row_name: String,
+    table_name: String,
+    columns: Vec<Column>,
+}
+
+struct Column {
+    move_name: String,
+    name: String,
+    mapping: ColumnMapping,
+}
+
+/// Generates code for every event in `abi`.
+pub fn generate(abi: &MoveModuleAbi) -> Result<GeneratedCode> {
+    let mut tables = vec![];
+    let mut skipped = vec![];
+    for event in abi.structs.iter().filter(|s| s.is_event()) {
+        if !event.generic_type_params.is_empty() {
+            skipped.push(format!(
+                "{}: generic events have no single event type to match on",
+                event.name
+            ));
+            continue;
+        }
+        tables.push(event_table(abi, event)?);
+    }
+    if tables.is_empty() {
+        bail!("Module {} declares no non-generic events", abi.module_id());
+    }
+
+    let mut table_names = BTreeSet::new();
+    for table in &tables {
+        if !table_names.insert(table.table_name.as_str()) {
+            bail!("More than one event maps to table {}", table.table_name);
+        }
+    }
+
+    Ok(GeneratedCode {
+        models: models(abi, &tables),
+        schema: schema(abi, &tables),
+        up_sql: up_sql(abi, &tables),
+        down_sql: down_sql(&tables),
+        skipped,
+    })
+}
+
+fn event_table(abi: &MoveModuleAbi, event: &MoveStructAbi) -> Result<EventTable> {
+    let columns = event
+        .fields
+        .iter()
+        .map(|field| {
+            let move_type = field
+                .typ
+                .parse::<MoveType>()
+                .with_context(|| format!("Failed to parse {}.{}", event.name, field.name))?;
+            let name = if RUST_KEYWORDS.contains(&field.name.as_str()) {
+                format!("{}_", field.name)
+            } else {
+                field.name.clone()
+            };
+            if METADATA_COLUMNS.contains(&name.as_str()) {
+                bail!(
+                    "{}.{} clashes with a generated metadata column",
+                    event.name,
+                    field.name
+                );
+            }
+            Ok(Column {
+                move_name: field.name.clone(),
+                name,
+                mapping: move_type.column_mapping(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let base_name = event.name.strip_suffix("Event").unwrap_or(&event.name);
+    let base_name = if base_name.is_empty() {
+        &event.name
+    } else {
+        base_name
+    };
+    Ok(EventTable {
+        event_name: event.name.clone(),
+        event_type: format!("{}::{}", abi.module_id(), event.name),
+        row_name: format!("{}Row", event.name),
+        table_name: format!("{}_events", to_snake_case(base_name)),
+        columns,
+    })
+}
+
+/// `MarketCreated` -> `market_created`, `APYUpdated` -> `apy_updated`.
+pub fn to_snake_case(name: &str) -> String {
+    let chars = name.chars().collect::<Vec<_>>();
+    let mut result = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            let prev = chars[i - 1];
+            let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+            if prev.is_lowercase()
+                || prev.is_ascii_digit()
+                || (prev.is_uppercase() && next_is_lower)
+            {
+                result.push('_');
+            }
+        }
+        result.extend(c.to_lowercase());
+    }
+    result
+}
+
+fn header(abi: &MoveModuleAbi, comment: &str) -> String {
+    format!(
+        "{comment} @generated by move-abi-codegen from {}. Do not edit by hand.\n",
+        abi.module_id()
+    )
+}
+
+fn models(abi: &MoveModuleAbi, tables: &[EventTable]) -> String {
+    let mappings = || {
+        tables
+            .iter()
+            .flat_map(|t| t.columns.iter().map(|c| &c.mapping))
+    };
+    let mut convert_imports = BTreeSet::new();
+    for mapping in mappings() {
+        if let Some(deserialize_with) = mapping.deserialize_with {
+            convert_imports.insert(deserialize_with);
+        }
+        match mapping.conversion {
+            Conversion::StandardizeAddress => {
+                convert_imports.insert("standardize_address");
+            },
+            Conversion::HexToBytes => {
+                convert_imports.insert("hex_to_raw_bytes");
+            },
+            _ => {},
+        }
+    }
+    let uses_big_decimal = mappings().any(|m| m.row_type == "BigDecimal");
+    let uses_context = mappings().any(|m| m.conversion == Conversion::HexToBytes);
+
+    let mut out = header(abi, "//");
+    out.push('\n');
+    let table_names = tables
+        .iter()
+        .map(|t| t.table_name.as_str())
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    let _ = writeln!(out, "use crate::schema::{{{}}};", table_names.join(", "));
+    if uses_context {
+        out.push_str("use anyhow::Context;\n");
+    }
+    if !convert_imports.is_empty() {
+        let imports = convert_imports.into_iter().collect::<Vec<_>>();
+        let _ = writeln!(
+            out,
+            "use aptos_indexer_processor_sdk::utils::convert::{{{}}};",
+            imports.join(", ")
+        );
+    }
+    if uses_big_decimal {
+        out.push_str("use bigdecimal::BigDecimal;\n");
+    }
+    out.push_str("use diesel::Insertable;\n");
+    out.push_str("use field_count::FieldCount;\n");
+    out.push_str("use serde::{Deserialize, Serialize};\n\n");
+
+    for table in tables {
+        let _ = writeln!(
+            out,
+            "pub const {}_TYPE: &str = \"{}\";",
+            to_snake_case(&table.event_name).to_uppercase(),
+            table.event_type
+        );
+    }
+
+    for table in tables {
+        let _ = write!(out, "\n// ===== {} =====\n\n", table.event_name);
+
+        out.push_str("#[derive(Clone, Debug, Deserialize, Serialize)]\n");
+        let _ = writeln!(out, "pub struct {} {{", table.event_name);
+        for column in &table.columns {
+            if column.name != column.move_name {
+                let _ = writeln!(out, "    #[serde(rename = \"{}\")]", column.move_name);
+            }
+            if let Some(deserialize_with) = column.mapping.deserialize_with {
+                let _ = writeln!(
+                    out,
+                    "    #[serde(deserialize_with = \"{deserialize_with}\")]"
+                );
+            }
+            let _ = writeln!(
+                out,
+                "    pub {}: {},",
+                column.name, column.mapping.event_type
+            );
+        }
+        out.push_str("}\n\n");
+
+        out.push_str("#[derive(Clone, Debug, Deserialize, FieldCount, Insertable, Serialize)]\n");
+        let _ = writeln!(out, "#[diesel(table_name = {})]", table.table_name);
+        let _ = writeln!(out, "pub struct {} {{", table.row_name);
+        for column in &table.columns {
+            let _ = writeln!(out, "    pub {}: {},", column.name, column.mapping.row_type);
+        }
+        out.push_str("    pub transaction_version: i64,\n");
+        out.push_str("    pub transaction_block_height: i64,\n");
+        out.push_str("    pub event_index: i64,\n");
+        out.push_str("    pub inserted_at: chrono::NaiveDateTime,\n");
+        out.push_str("}\n\n");
+
+        let _ = writeln!(out, "impl {} {{", table.row_name);
+        out.push_str("    pub fn from_event(\n");
+        let _ = writeln!(out, "        event: &{},", table.event_name);
+        out.push_str("        transaction_version: i64,\n");
+        out.push_str("        transaction_block_height: i64,\n");
+        out.push_str("        event_index: i64,\n");
+        out.push_str("    ) -> anyhow::Result<Self> {\n");
+        let _ = writeln!(out, "        Ok({} {{", table.row_name);
+        for column in &table.columns {
+            let _ = writeln!(
+                out,
+                "            {}: {},",
+                column.name,
+                column
+                    .mapping
+                    .conversion
+                    .expr(&table.event_name, &column.name)
+            );
+        }
+        out.push_str("            transaction_version,\n");
+        out.push_str("            transaction_block_height,\n");
+        out.push_str("            event_index,\n");
+        out.push_str("            inserted_at: chrono::Utc::now().naive_utc(),\n");
+        out.push_str("        })\n");
+        out.push_str("    }\n");
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn schema(abi: &MoveModuleAbi, tables: &[EventTable]) -> String {
+    let mut out = header(abi, "//");
+    for table in tables {
+        out.push_str("\ndiesel::table! {\n");
+        let _ = writeln!(
+            out,
+            "    {} (transaction_version, event_index) {{",
+            table.table_name
+        );
+        for column in &table.columns {
+            if let Some(max_length) = column.mapping.max_length {
+                let _ = writeln!(out, "        #[max_length = {max_length}]");
+            }
+            let _ = writeln!(
+                out,
+                "        {} -> {},",
+                column.name, column.mapping.diesel_type
+            );
+        }
+        out.push_str("        transaction_version -> Int8,\n");
+        out.push_str("        transaction_block_height -> Int8,\n");
+        out.push_str("        event_index -> Int8,\n");
+        out.push_str("        inserted_at -> Timestamp,\n");
+        out.push_str("    }\n");
+        out.push_str("}\n");
+    }
+
+    if tables.len() > 1 {
+        out.push_str("\ndiesel::allow_tables_to_appear_in_same_query!(\n");
+        let table_names = tables
+            .iter()
+            .map(|t| t.table_name.as_str())
+            .collect::<BTreeSet<_>>();
+        for table_name in table_names {
+            let _ = writeln!(out, "    {table_name},");
+        }
+        out.push_str(");\n");
+    }
+    out
+}
+
+fn up_sql(abi: &MoveModuleAbi, tables: &[EventTable]) -> String {
+    let mut out = header(abi, "--");
+    for table in tables {
+        let _ = write!(out, "\n-- {}\n", table.event_type);
+        let _ = writeln!(out, "CREATE TABLE IF NOT EXISTS {} (", table.table_name);
+        // Quoted because Move field names like `user` are reserved words in Postgres.
+        for column in &table.columns {
+            let _ = writeln!(
+                out,
+                "    \"{}\" {} NOT NULL,",
+                column.name, column.mapping.sql_type
+            );
+        }
+        out.push_str("    transaction_version BIGINT NOT NULL,\n");
+        out.push_str("    transaction_block_height BIGINT NOT NULL,\n");
+        out.push_str("    event_index BIGINT NOT NULL,\n");
+        out.push_str("    inserted_at TIMESTAMP NOT NULL DEFAULT NOW(),\n");
+        out.push_str("    PRIMARY KEY (transaction_version, event_index)\n");
+        out.push_str(");\n");
+    }
+    out
+}
+
+fn down_sql(tables: &[EventTable]) -> String {
+    let mut out = String::new();
+    for table in tables.iter().rev() {
+        let _ = writeln!(out, "DROP TABLE IF EXISTS {};", table.table_name);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ABI: &str = r#"{
+        "address": "0xabc",
+        "name": "kizo_prediction_market",
+        "structs": [
+            {
+                "name": "BetPlacedEvent",
+                "generic_type_params": [],
+                "fields": [
+                    {"name": "bet_id", "type": "u64"},
+                    {"name": "user", "type": "address"},
+                    {"name": "position", "type": "bool"},
+                    {"name": "amount", "type": "u128"},
+                    {"name": "memo", "type": "vector<u8>"},
+                    {"name": "type", "type": "0x1::string::String"}
+                ]
+            },
+            {
+                "name": "Wrapped",
+                "is_event": true,
+                "generic_type_params": [{"constraints": []}],
+                "fields": [{"name": "value", "type": "T0"}]
+            },
+            {
+                "name": "Market",
+                "generic_type_params": [],
+                "fields": [{"name": "id", "type": "u64"}]
+            }
+        ]
+    }"#;
+
+    #[test]
+    fn test_to_snake_case() {
+        assert_eq!(to_snake_case("MarketCreated"), "market_created");
+        assert_eq!(to_snake_case("APYUpdated"), "apy_updated");
+        assert_eq!(to_snake_case("V2Bet"), "v2_bet");
+    }
+
+    #[test]
+    fn test_generate() {
+        let abi = MoveModuleAbi::parse_all(ABI).unwrap().remove(0);
+        let code = generate(&abi).unwrap();
+
+        assert_eq!(code.skipped.len(), 1);
+        assert!(code.skipped[0].starts_with("Wrapped"));
+        assert!(!code.models.contains("Market "));
+
+        assert!(code
+            .models
+            .contains("pub const BET_PLACED_EVENT_TYPE: &str = \"0xabc::kizo_prediction_market::BetPlacedEvent\";"));
+        assert!(code.models.contains(
+            "use aptos_indexer_processor_sdk::utils::convert::{deserialize_from_string, \
+             deserialize_u64_from_string_or_number, hex_to_raw_bytes, standardize_address};"
+        ));
+        assert!(code
+            .models
+            .contains("    #[serde(rename = \"type\")]\n    pub type_: String,"));
+        assert!(code
+            .models
+            .contains("            bet_id: BigDecimal::from(event.bet_id),"));
+        assert!(code
+            .models
+            .contains("            user: standardize_address(&event.user),"));
+        assert!(code.models.contains(
+            "            memo: hex_to_raw_bytes(&event.memo).context(\"Failed to decode \
+             BetPlacedEvent.memo\")?,"
+        ));
+        assert!(code.models.contains("use anyhow::Context;\n"));
+        assert!(code.models.contains("    ) -> anyhow::Result<Self> {\n"));
+
+        assert!(code
+            .schema
+            .contains("    bet_placed_events (transaction_version, event_index) {"));
+        assert!(code
+            .schema
+            .contains("        #[max_length = 66]\n        user -> Varchar,"));
+        assert!(!code.schema.contains("allow_tables_to_appear_in_same_query"));
+
+        assert!(code.up_sql.contains("    \"bet_id\" NUMERIC NOT NULL,"));
+        assert!(code.up_sql.contains("    \"amount\" NUMERIC NOT NULL,"));
+        assert!(code.up_sql.contains("    \"user\" VARCHAR(66) NOT NULL,"));
+        assert!(code.up_sql.contains("    \"memo\" BYTEA NOT NULL,"));
+        assert_eq!(code.down_sql, "DROP TABLE IF EXISTS bet_placed_events;\n");
+    }
+
+    #[test]
+    fn test_metadata_column_clash() {
+        let abi = MoveModuleAbi::parse_all(
+            r#"{"address": "0x1", "name": "m", "structs": [{"name": "BadEvent",
+                "fields": [{"name": "event_index", "type": "u64"}]}]}"#,
+        )
+        .unwrap()
+        .remove(0);
+        assert!(generate(&abi).is_err());
+    }
+}
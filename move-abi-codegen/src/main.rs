@@ -0,0 +1,70 @@
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use move_abi_codegen::{abi::MoveModuleAbi, generate};
+use std::{fs, path::PathBuf};
+
+/// Generates event structs, diesel models and a migration from a Move module ABI.
+#[derive(Parser)]
+#[clap(author, version, about)]
+struct Args {
+    /// Module ABI JSON, e.g. the output of `aptos account list --query modules`.
+    #[clap(long)]
+    abi: PathBuf,
+    /// Module to generate for when the ABI file contains several.
+    #[clap(long)]
+    module: Option<String>,
+    /// Directory that receives `models.rs`, `schema.rs` and `migrations/`.
+    #[clap(long)]
+    out_dir: PathBuf,
+    /// Migration directory name. Defaults to `<timestamp>_create_<module>_events`.
+    #[clap(long)]
+    migration_name: Option<String>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let json = fs::read_to_string(&args.abi)
+        .with_context(|| format!("Failed to read {}", args.abi.display()))?;
+    let modules = MoveModuleAbi::parse_all(&json)?;
+    let abi = match &args.module {
+        Some(name) => modules
+            .into_iter()
+            .find(|m| &m.name == name)
+            .with_context(|| format!("Module {name} not found in {}", args.abi.display()))?,
+        None if modules.len() == 1 => modules.into_iter().next().unwrap(),
+        None => bail!(
+            "{} contains {} modules, pick one with --module",
+            args.abi.display(),
+            modules.len()
+        ),
+    };
+
+    let code = generate(&abi)?;
+    for skipped in &code.skipped {
+        eprintln!("Skipped {skipped}");
+    }
+
+    let migration_name = args.migration_name.unwrap_or_else(|| {
+        format!(
+            "{}_create_{}_events",
+            chrono::Utc::now().format("%Y-%m-%d-%H%M%S"),
+            abi.name
+        )
+    });
+    let migration_dir = args.out_dir.join("migrations").join(migration_name);
+    fs::create_dir_all(&migration_dir)
+        .with_context(|| format!("Failed to create {}", migration_dir.display()))?;
+
+    for (path, contents) in [
+        (args.out_dir.join("models.rs"), &code.models),
+        (args.out_dir.join("schema.rs"), &code.schema),
+        (migration_dir.join("up.sql"), &code.up_sql),
+        (migration_dir.join("down.sql"), &code.down_sql),
+    ] {
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,308 @@
+//! Parsing of Move type strings as they appear in ABI field definitions, and their mapping onto
+//! Rust and Postgres types.
+
+use anyhow::{bail, Context, Result};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MoveType {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    Address,
+    Signer,
+    Vector(Box<MoveType>),
+    Struct {
+        address: String,
+        module: String,
+        name: String,
+        type_args: Vec<MoveType>,
+    },
+    /// A generic type parameter such as `T0`.
+    Generic(u16),
+}
+
+impl FromStr for MoveType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let move_type = match s {
+            "bool" => MoveType::Bool,
+            "u8" => MoveType::U8,
+            "u16" => MoveType::U16,
+            "u32" => MoveType::U32,
+            "u64" => MoveType::U64,
+            "u128" => MoveType::U128,
+            "u256" => MoveType::U256,
+            "address" => MoveType::Address,
+            "signer" => MoveType::Signer,
+            _ => {
+                if let Some(inner) = s.strip_prefix("vector<").and_then(|s| s.strip_suffix('>')) {
+                    MoveType::Vector(Box::new(inner.parse()?))
+                } else if let Some(index) = s.strip_prefix('T').and_then(|s| s.parse().ok()) {
+                    MoveType::Generic(index)
+                } else {
+                    parse_struct(s)?
+                }
+            },
+        };
+        Ok(move_type)
+    }
+}
+
+fn parse_struct(s: &str) -> Result<MoveType> {
+    let (path, type_args) = match s.find('<') {
+        Some(start) => {
+            let args = s[start + 1..]
+                .strip_suffix('>')
+                .with_context(|| format!("Unbalanced type arguments in {s}"))?;
+            (&s[..start], split_type_args(args)?)
+        },
+        None => (s, vec![]),
+    };
+    let parts = path.split("::").collect::<Vec<_>>();
+    let [address, module, name] = parts[..] else {
+        bail!("Unsupported Move type {s}");
+    };
+    Ok(MoveType::Struct {
+        address: address.to_string(),
+        module: module.to_string(),
+        name: name.to_string(),
+        type_args,
+    })
+}
+
+/// Splits `A, B<C, D>` on top level commas only.
+fn split_type_args(args: &str) -> Result<Vec<MoveType>> {
+    let mut result = vec![];
+    let mut depth = 0usize;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        match c {
+            '<' => depth += 1,
+            '>' => {
+                depth = depth
+                    .checked_sub(1)
+                    .with_context(|| format!("Unbalanced type arguments in {args}"))?
+            },
+            ',' if depth == 0 => {
+                result.push(args[start..i].parse()?);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    if !args[start..].trim().is_empty() {
+        result.push(args[start..].parse()?);
+    }
+    Ok(result)
+}
+
+impl fmt::Display for MoveType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoveType::Bool => write!(f, "bool"),
+            MoveType::U8 => write!(f, "u8"),
+            MoveType::U16 => write!(f, "u16"),
+            MoveType::U32 => write!(f, "u32"),
+            MoveType::U64 => write!(f, "u64"),
+            MoveType::U128 => write!(f, "u128"),
+            MoveType::U256 => write!(f, "u256"),
+            MoveType::Address => write!(f, "address"),
+            MoveType::Signer => write!(f, "signer"),
+            MoveType::Vector(inner) => write!(f, "vector<{inner}>"),
+            MoveType::Struct {
+                address,
+                module,
+                name,
+                type_args,
+            } => {
+                write!(f, "{address}::{module}::{name}")?;
+                if !type_args.is_empty() {
+                    let args = type_args
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>();
+                    write!(f, "<{}>", args.join(", "))?;
+                }
+                Ok(())
+            },
+            MoveType::Generic(index) => write!(f, "T{index}"),
+        }
+    }
+}
+
+/// How a value is converted from the event struct field into the row field.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Conversion {
+    Copy,
+    Cast(&'static str),
+    BigDecimalFromU64,
+    Clone,
+    StandardizeAddress,
+    HexToBytes,
+}
+
+impl Conversion {
+    /// The conversion of `event.{field}`; fallible ones are `?`ed with `{struct_name}.{field}` as
+    /// the error context.
+    pub fn expr(&self, struct_name: &str, field: &str) -> String {
+        match self {
+            Conversion::Copy => format!("event.{field}"),
+            Conversion::Cast(ty) => format!("event.{field} as {ty}"),
+            Conversion::BigDecimalFromU64 => format!("BigDecimal::from(event.{field})"),
+            Conversion::Clone => format!("event.{field}.clone()"),
+            Conversion::StandardizeAddress => format!("standardize_address(&event.{field})"),
+            Conversion::HexToBytes => format!(
+                "hex_to_raw_bytes(&event.{field}).context(\"Failed to decode {struct_name}.{field}\")?"
+            ),
+        }
+    }
+}
+
+/// Everything needed to emit one Move field as an event field, a row field and a column.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ColumnMapping {
+    /// Type of the field on the deserialized event struct.
+    pub event_type: &'static str,
+    /// Serde `deserialize_with` helper, if the JSON encoding needs one.
+    pub deserialize_with: Option<&'static str>,
+    /// Type of the field on the insertable row.
+    pub row_type: &'static str,
+    pub diesel_type: &'static str,
+    pub max_length: Option<u32>,
+    pub sql_type: &'static str,
+    pub conversion: Conversion,
+}
+
+impl MoveType {
+    /// Maps a Move type onto Rust and Postgres. Integers that don't fit in a signed column of the
+    /// same width are widened, u64 and larger go to NUMERIC, and anything without a natural
+    /// column type (other vectors, structs, options, generics) is stored as JSONB.
+    pub fn column_mapping(&self) -> ColumnMapping {
+        let mapping = |event_type, row_type, diesel_type, sql_type, conversion| ColumnMapping {
+            event_type,
+            deserialize_with: None,
+            row_type,
+            diesel_type,
+            max_length: None,
+            sql_type,
+            conversion,
+        };
+        match self {
+            MoveType::Bool => mapping("bool", "bool", "Bool", "BOOLEAN", Conversion::Copy),
+            MoveType::U8 => mapping("u8", "i16", "Int2", "SMALLINT", Conversion::Cast("i16")),
+            MoveType::U16 => mapping("u16", "i32", "Int4", "INTEGER", Conversion::Cast("i32")),
+            MoveType::U32 => mapping("u32", "i64", "Int8", "BIGINT", Conversion::Cast("i64")),
+            MoveType::U64 => ColumnMapping {
+                deserialize_with: Some("deserialize_u64_from_string_or_number"),
+                ..mapping(
+                    "u64",
+                    "BigDecimal",
+                    "Numeric",
+                    "NUMERIC",
+                    Conversion::BigDecimalFromU64,
+                )
+            },
+            MoveType::U128 | MoveType::U256 => ColumnMapping {
+                deserialize_with: Some("deserialize_from_string"),
+                ..mapping(
+                    "BigDecimal",
+                    "BigDecimal",
+                    "Numeric",
+                    "NUMERIC",
+                    Conversion::Clone,
+                )
+            },
+            MoveType::Address | MoveType::Signer => ColumnMapping {
+                max_length: Some(66),
+                ..mapping(
+                    "String",
+                    "String",
+                    "Varchar",
+                    "VARCHAR(66)",
+                    Conversion::StandardizeAddress,
+                )
+            },
+            MoveType::Vector(inner) if **inner == MoveType::U8 => mapping(
+                "String",
+                "Vec<u8>",
+                "Bytea",
+                "BYTEA",
+                Conversion::HexToBytes,
+            ),
+            MoveType::Struct {
+                address,
+                module,
+                name,
+                ..
+            } if is_framework_address(address) && module == "string" && name == "String" => {
+                mapping("String", "String", "Text", "TEXT", Conversion::Clone)
+            },
+            MoveType::Vector(_) | MoveType::Struct { .. } | MoveType::Generic(_) => mapping(
+                "serde_json::Value",
+                "serde_json::Value",
+                "Jsonb",
+                "JSONB",
+                Conversion::Clone,
+            ),
+        }
+    }
+}
+
+fn is_framework_address(address: &str) -> bool {
+    address
+        .strip_prefix("0x")
+        .unwrap_or(address)
+        .trim_start_matches('0')
+        == "1"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_move_types() {
+        assert_eq!("u64".parse::<MoveType>().unwrap(), MoveType::U64);
+        assert_eq!(
+            "vector<u8>".parse::<MoveType>().unwrap(),
+            MoveType::Vector(Box::new(MoveType::U8))
+        );
+        assert_eq!("T1".parse::<MoveType>().unwrap(), MoveType::Generic(1));
+
+        let nested = "0x1::table::Table<address, vector<0x1::option::Option<u64>>>";
+        let parsed = nested.parse::<MoveType>().unwrap();
+        assert_eq!(parsed.to_string(), nested);
+        match parsed {
+            MoveType::Struct {
+                module, type_args, ..
+            } => {
+                assert_eq!(module, "table");
+                assert_eq!(type_args.len(), 2);
+            },
+            other => panic!("Unexpected type {other:?}"),
+        }
+
+        assert!("0x1::string".parse::<MoveType>().is_err());
+        assert!("0x1::option::Option<u64".parse::<MoveType>().is_err());
+    }
+
+    #[test]
+    fn test_column_mapping() {
+        let sql_type = |s: &str| s.parse::<MoveType>().unwrap().column_mapping().sql_type;
+        assert_eq!(sql_type("u64"), "NUMERIC");
+        assert_eq!(sql_type("u128"), "NUMERIC");
+        assert_eq!(sql_type("address"), "VARCHAR(66)");
+        assert_eq!(sql_type("vector<u8>"), "BYTEA");
+        assert_eq!(sql_type("0x1::string::String"), "TEXT");
+        assert_eq!(sql_type("0x0001::string::String"), "TEXT");
+        assert_eq!(sql_type("vector<u64>"), "JSONB");
+        assert_eq!(sql_type("0x1::option::Option<u64>"), "JSONB");
+    }
+}
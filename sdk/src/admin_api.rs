@@ -0,0 +1,282 @@
+//! Admin API for operating a running processor, served under `/admin` next to the probes when
+//! `admin_config` is set. Every request needs an `Authorization: Bearer <auth_token>` header.
+//!
+//! - `POST /admin/pause` and `POST /admin/resume` stop and restart fetching transactions. Batches
+//!   already fetched still go through the pipeline.
+//! - `GET /admin/steps` lists the steps with their latest versions and input queue depth.
+//! - `GET /admin/graph` returns the pipeline as a DOT graph, like `/topology/dot`.
+//! - `POST /admin/rewind` with `{"to_version": <version>}` schedules a rewind, which is applied
+//!   the next time the processor starts.
+
+use crate::{builder::topology, utils::step_metrics::StepMetricsSnapshot};
+use anyhow::Result;
+use async_trait::async_trait;
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::{error, info};
+
+static PAUSED: Lazy<watch::Sender<bool>> = Lazy::new(|| watch::channel(false).0);
+static REWIND_SCHEDULER: Mutex<Option<Arc<dyn RewindScheduler + Send + Sync>>> = Mutex::new(None);
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AdminConfig {
+    pub auth_token: String,
+}
+
+/// Stores a rewind requested through the admin API, for the processor to apply when it next
+/// starts.
+#[async_trait]
+pub trait RewindScheduler {
+    async fn schedule_rewind(&self, to_version: u64) -> Result<()>;
+}
+
+/// Pauses fetching transactions. See `wait_while_paused`.
+pub fn pause() {
+    PAUSED.send_replace(true);
+}
+
+pub fn resume() {
+    PAUSED.send_replace(false);
+}
+
+pub fn is_paused() -> bool {
+    *PAUSED.borrow()
+}
+
+/// Resolves once the processor is not paused, right away if it isn't.
+pub async fn wait_while_paused() {
+    let mut receiver = PAUSED.subscribe();
+    // The sender is static, so this can't fail.
+    let _ = receiver.wait_for(|paused| !*paused).await;
+}
+
+/// Makes `scheduler` handle the rewinds requested through the admin API.
+pub fn register_rewind_scheduler(scheduler: impl RewindScheduler + Send + Sync + 'static) {
+    *REWIND_SCHEDULER.lock().unwrap() = Some(Arc::new(scheduler));
+}
+
+#[derive(Debug, Serialize)]
+pub struct PipelineState {
+    pub paused: bool,
+    pub steps: Vec<StepState>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StepState {
+    pub id: usize,
+    pub name: String,
+    pub step_type: String,
+    /// Whether the step's task is still running.
+    pub running: bool,
+    /// Items waiting in the step's input channel.
+    pub queue_depth: usize,
+    #[serde(flatten)]
+    pub metrics: StepMetricsSnapshot,
+}
+
+/// The state of the pipeline registered with `topology::register_pipeline`, or `None` if no
+/// pipeline is registered yet.
+pub fn pipeline_state() -> Option<PipelineState> {
+    let pipeline = topology::registered_pipeline()?;
+    let node_map = pipeline.node_map.lock().unwrap();
+    let mut steps = node_map
+        .values()
+        .map(|node| StepState {
+            id: node.id,
+            name: node.name.clone(),
+            step_type: node.step_type.clone(),
+            running: node
+                .join_handle
+                .as_ref()
+                .is_some_and(|handle| !handle.is_finished()),
+            queue_depth: node.input_channel.get().size,
+            metrics: StepMetricsSnapshot::get(&node.name),
+        })
+        .collect::<Vec<_>>();
+    steps.sort_by_key(|step| step.id);
+    Some(PipelineState {
+        paused: is_paused(),
+        steps,
+    })
+}
+
+/// The routes of the admin API, all requiring `config.auth_token`.
+pub fn router(config: AdminConfig) -> Router {
+    Router::new()
+        .route("/admin/pause", post(pause_handler))
+        .route("/admin/resume", post(resume_handler))
+        .route("/admin/steps", get(steps_handler))
+        .route("/admin/graph", get(graph_handler))
+        .route("/admin/rewind", post(rewind_handler))
+        .route_layer(middleware::from_fn_with_state(Arc::new(config), authorize))
+}
+
+async fn authorize(
+    State(config): State<Arc<AdminConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let expected = format!("Bearer {}", config.auth_token);
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| constant_time_eq(value.as_bytes(), expected.as_bytes()));
+    if !authorized {
+        return StatusCode::UNAUTHORIZED.into_response();
+    }
+    next.run(request).await
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn pause_handler() -> impl IntoResponse {
+    info!("Pausing the processor through the admin API");
+    pause();
+    Json(serde_json::json!({ "paused": true }))
+}
+
+async fn resume_handler() -> impl IntoResponse {
+    info!("Resuming the processor through the admin API");
+    resume();
+    Json(serde_json::json!({ "paused": false }))
+}
+
+async fn steps_handler() -> Response {
+    match pipeline_state() {
+        Some(state) => Json(state).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No pipeline is running").into_response(),
+    }
+}
+
+async fn graph_handler() -> Response {
+    match topology::registered_pipeline() {
+        Some(graph) => ([(header::CONTENT_TYPE, "text/vnd.graphviz")], graph.dot()).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No pipeline is running").into_response(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RewindRequest {
+    to_version: u64,
+}
+
+async fn rewind_handler(Json(request): Json<RewindRequest>) -> Response {
+    let scheduler = REWIND_SCHEDULER.lock().unwrap().clone();
+    let Some(scheduler) = scheduler else {
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "This processor does not support rewinds",
+        )
+            .into_response();
+    };
+    match scheduler.schedule_rewind(request.to_version).await {
+        Ok(()) => {
+            info!(
+                to_version = request.to_version,
+                "Scheduled a rewind for the next restart"
+            );
+            (
+                StatusCode::ACCEPTED,
+                Json(serde_json::json!({ "scheduled_rewind_to_version": request.to_version })),
+            )
+                .into_response()
+        },
+        Err(e) => {
+            error!(error = ?e, "Failed to schedule a rewind");
+            (StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")).into_response()
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    /// Held by the tests that pause, since `PAUSED` is global.
+    static PAUSE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+    async fn post(path: &str, authorization: Option<&str>) -> StatusCode {
+        let mut request = axum::http::Request::post(path);
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let config = AdminConfig {
+            auth_token: "secret".to_string(),
+        };
+        router(config)
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_authorization() {
+        let _lock = PAUSE_LOCK.lock().await;
+        resume();
+
+        for authorization in [
+            None,
+            Some("Bearer wrong"),
+            Some("secret"),
+            Some("Bearer secret2"),
+        ] {
+            assert_eq!(
+                post("/admin/pause", authorization).await,
+                StatusCode::UNAUTHORIZED,
+                "{authorization:?}"
+            );
+            assert!(!is_paused());
+        }
+
+        assert_eq!(
+            post("/admin/pause", Some("Bearer secret")).await,
+            StatusCode::OK
+        );
+        assert!(is_paused());
+        assert_eq!(
+            post("/admin/resume", Some("Bearer secret")).await,
+            StatusCode::OK
+        );
+        assert!(!is_paused());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_wait_while_paused() {
+        let _lock = PAUSE_LOCK.lock().await;
+        resume();
+        tokio::time::timeout(Duration::from_secs(1), wait_while_paused())
+            .await
+            .expect("Should not wait while not paused");
+
+        pause();
+        let waiter = tokio::spawn(wait_while_paused());
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+
+        resume();
+        tokio::time::timeout(Duration::from_secs(1), waiter)
+            .await
+            .expect("Should stop waiting once resumed")
+            .unwrap();
+    }
+}
@@ -1,10 +1,14 @@
-use crate::traits::{RunnableStep, RunnableStepWithInputReceiver};
+use crate::{
+    traits::{RunnableStep, RunnableStepWithInputReceiver},
+    utils::shutdown::ShutdownSignal,
+};
 use tokio::task::JoinHandle;
 
 pub fn connect_two_steps<LeftInput, LeftOutput, RightOutput, LeftStep, RightStep>(
     left_step: RunnableStepWithInputReceiver<LeftInput, LeftOutput, LeftStep>,
     right_step: RightStep,
     channel_size: usize,
+    shutdown_signal: ShutdownSignal,
 ) -> (
     JoinHandle<()>,
     RunnableStepWithInputReceiver<LeftOutput, RightOutput, RightStep>,
@@ -27,6 +31,7 @@ where
         Some(left_input_receiver.clone()),
         channel_size,
         _left_input_sender,
+        shutdown_signal,
     );
 
     let right_step_with_input_receiver =
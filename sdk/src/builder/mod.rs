@@ -1,4 +1,5 @@
 mod dag;
 mod processor_builder;
+pub mod topology;
 
-pub use processor_builder::ProcessorBuilder;
+pub use processor_builder::{GraphBuilder, ProcessorBuilder};
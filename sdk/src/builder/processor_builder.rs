@@ -1,7 +1,9 @@
 use crate::{
-    builder::dag::connect_two_steps,
-    traits::{RunnableStep, RunnableStepWithInputReceiver},
+    builder::{dag::connect_two_steps, topology::ChannelState},
+    common_steps::OrderByVersionStep,
+    traits::{RunnablePollableStep, RunnableStep, RunnableStepWithInputReceiver},
     types::transaction_context::TransactionContext,
+    utils::{shutdown::ShutdownSignal, step_metrics::StepMetricsSnapshot},
 };
 use anyhow::Result;
 use instrumented_channel::{instrumented_bounded_channel, InstrumentedAsyncReceiver};
@@ -14,17 +16,24 @@ use std::{
     collections::HashMap,
     ops::DerefMut,
     sync::{Arc, Mutex},
+    time::Duration,
 };
 use tokio::task::JoinHandle;
 
+/// How often the `OrderByVersionStep` added by `connect_to_parallel` releases ordered batches.
+pub const PARALLEL_ORDER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[derive(Clone, Default, Debug)]
 pub struct GraphBuilder {
     // These fields are shared between all the potential instances of the graph
     pub graph: Arc<Mutex<DiGraph<usize, usize>>>,
     pub node_map: Arc<Mutex<HashMap<usize, GraphNode>>>,
     pub node_counter: Arc<Mutex<usize>>,
-    // This field is specific to the current instance of the graph
+    pub shutdown_signal: ShutdownSignal,
+    // These fields are specific to the current instance of the graph
     pub current_node_index: Option<NodeIndex>,
+    /// Nodes besides `current_node_index` that feed the next step, e.g. parallel workers.
+    pub pending_parent_indices: Vec<NodeIndex>,
 }
 
 impl GraphBuilder {
@@ -33,7 +42,9 @@ impl GraphBuilder {
             graph: Arc::new(Mutex::new(DiGraph::new())),
             node_map: Arc::new(Mutex::new(HashMap::new())),
             node_counter: Arc::new(Mutex::new(0)),
+            shutdown_signal: ShutdownSignal::new(),
             current_node_index: None,
+            pending_parent_indices: Vec::new(),
         }
     }
 
@@ -59,6 +70,7 @@ impl GraphBuilder {
                 output_type: std::any::type_name::<Output>().to_string(),
                 join_handle: None,
                 end_step: false,
+                input_channel: InputChannel::of(&step.input_receiver),
             },
         );
 
@@ -86,6 +98,7 @@ impl GraphBuilder {
                 output_type: std::any::type_name::<Output>().to_string(),
                 join_handle: None,
                 end_step: false,
+                input_channel: InputChannel::of(&step.input_receiver),
             },
         );
 
@@ -131,11 +144,12 @@ impl GraphBuilder {
     }
 
     pub fn add_edge_to(&mut self, to: NodeIndex) {
+        let mut graph = self.graph.lock().unwrap();
         if let Some(current_node_index) = self.current_node_index {
-            self.graph
-                .lock()
-                .unwrap()
-                .add_edge(current_node_index, to, 1);
+            graph.add_edge(current_node_index, to, 1);
+        }
+        for parent_index in self.pending_parent_indices.drain(..) {
+            graph.add_edge(parent_index, to, 1);
         }
     }
 
@@ -149,7 +163,14 @@ impl GraphBuilder {
             let node_map = self.node_map.lock().unwrap();
             let from_node = node_map.get(&from_node_id.index()).unwrap();
 
-            format!("label=\"  {}\"", from_node.output_type)
+            let to_node = node_map.get(&edge_ref.target().index()).unwrap();
+            let channel = to_node.input_channel.get();
+
+            format!(
+                "label=\"  {}\\n  {}\"",
+                from_node.output_type,
+                channel.summary()
+            )
         };
 
         let _last_node_index = self.graph.lock().unwrap().node_count() - 1;
@@ -158,7 +179,13 @@ impl GraphBuilder {
             let node = node_map.get(&node_val).unwrap();
 
             //let input_output = format!("{} -> {}", &node.input_type, &node.output_type);
-            let label = format!("label=\"{}\\n{}\"", &node.name, &node.step_type);
+            let metrics = StepMetricsSnapshot::get(&node.name);
+            let label = format!(
+                "label=\"{}\\n{}\\n{}\"",
+                &node.name,
+                &node.step_type,
+                metrics.summary()
+            );
             let shape = if node_val == 0 {
                 " shape=invhouse".to_string()
             } else if node.end_step {
@@ -190,6 +217,28 @@ pub struct GraphNode {
     pub output_type: String,
     pub join_handle: Option<JoinHandle<()>>,
     pub end_step: bool,
+    pub input_channel: InputChannel,
+}
+
+/// Reads the state of a step's input channel.
+#[derive(Clone)]
+pub struct InputChannel(Arc<dyn Fn() -> ChannelState + Send + Sync>);
+
+impl InputChannel {
+    fn of<T: Send + 'static>(receiver: &InstrumentedAsyncReceiver<T>) -> Self {
+        let receiver = receiver.clone();
+        Self(Arc::new(move || ChannelState::of(&receiver)))
+    }
+
+    pub fn get(&self) -> ChannelState {
+        (self.0)()
+    }
+}
+
+impl std::fmt::Debug for InputChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "InputChannel({:?})", self.get())
+    }
 }
 
 pub enum CurrentStepHolder<Input, Output, Step>
@@ -240,6 +289,13 @@ where
         }
     }
 
+    /// Uses `shutdown_signal` to stop the pipeline, instead of the builder's own. Must be called
+    /// before any step is connected, since steps get the signal when they are spawned.
+    pub fn with_shutdown_signal(mut self, shutdown_signal: ShutdownSignal) -> Self {
+        self.graph.shutdown_signal = shutdown_signal;
+        self
+    }
+
     pub fn new_with_fanin_step_with_receivers(
         fanout_step_receivers_and_graphs: Vec<(
             InstrumentedAsyncReceiver<TransactionContext<Input>>,
@@ -262,7 +318,8 @@ where
         let next_step = next_step.add_input_receiver(connector_receiver);
         let mut graph = fanout_step_receivers_and_graphs.first().unwrap().1.clone();
         graph.add_step(&next_step);
-        let (next_output_receiver, join_handle) = next_step.spawn(None, channel_size, None);
+        let (next_output_receiver, join_handle) =
+            next_step.spawn(None, channel_size, None, graph.shutdown_signal.clone());
         graph.set_join_handle(graph.current_node_index.unwrap().index(), join_handle);
 
         // Send the results of the fanned out steps to the channel
@@ -276,9 +333,9 @@ where
                         Ok(input) => {
                             sender.send(input.clone()).await.unwrap();
                         },
-                        Err(e) => {
-                            panic!("Error receiving from previous step for fanout: {e:?}");
-                        },
+                        // The previous step has finished, e.g. on shutdown. Dropping the sender
+                        // lets the next step drain once all fanned in steps are done.
+                        Err(_) => break,
                     }
                 }
             });
@@ -312,8 +369,12 @@ where
         let next_step = match current_step {
             CurrentStepHolder::RunnableStepWithInputReceiver(current_step) => {
                 self.graph.add_and_connect_step(&current_step);
-                let (join_handle, next_step) =
-                    connect_two_steps(current_step, next_step, channel_size);
+                let (join_handle, next_step) = connect_two_steps(
+                    current_step,
+                    next_step,
+                    channel_size,
+                    self.graph.shutdown_signal.clone(),
+                );
                 self.graph
                     .set_join_handle(self.graph.current_node_index.unwrap().index(), join_handle);
                 CurrentStepHolder::RunnableStepWithInputReceiver(next_step)
@@ -333,6 +394,109 @@ where
         }
     }
 
+    /// Runs `num_workers` instances of a step, built by `new_step`, in parallel. Each batch goes
+    /// to whichever worker is free. Their outputs are merged and passed through an
+    /// `OrderByVersionStep` that expects `starting_version` first, so downstream steps such as
+    /// `VersionTrackerStep` still see batches in version order.
+    ///
+    /// Workers must output exactly one batch per input batch, covering the same versions, or the
+    /// ordering step waits for the missing versions forever. They must also be fine with
+    /// processing batches out of order relative to each other.
+    pub fn connect_to_parallel<NextOutput, NextStep>(
+        mut self,
+        num_workers: usize,
+        mut new_step: impl FnMut(usize) -> NextStep,
+        starting_version: u64,
+        channel_size: usize,
+    ) -> ProcessorBuilder<
+        NextOutput,
+        NextOutput,
+        RunnablePollableStep<OrderByVersionStep<NextOutput>>,
+    >
+    where
+        NextOutput: Send + Sync + 'static,
+        NextStep: RunnableStep<Output, NextOutput>,
+    {
+        assert!(num_workers > 0, "Can not run a step on zero workers");
+        let previous_output_receiver = match self
+            .current_step
+            .take()
+            .expect("Can not connect without a prior step")
+        {
+            CurrentStepHolder::RunnableStepWithInputReceiver(current_step) => {
+                self.graph.add_and_connect_step(&current_step);
+                let (output_receiver, join_handle) = current_step.spawn(
+                    None,
+                    channel_size,
+                    None,
+                    self.graph.shutdown_signal.clone(),
+                );
+                self.graph
+                    .set_join_handle(self.graph.current_node_index.unwrap().index(), join_handle);
+                output_receiver
+            },
+            CurrentStepHolder::DanglingOutputReceiver(output_receiver) => output_receiver,
+        };
+
+        let upstream_node_index = self.graph.current_node_index;
+        let mut worker_node_indices = Vec::with_capacity(num_workers);
+        let mut merged_channel = None;
+        for idx in 0..num_workers {
+            // Workers share the upstream receiver, so a busy worker doesn't get handed batches.
+            let worker = new_step(idx).add_input_receiver(previous_output_receiver.clone());
+            let (merged_sender, _) = merged_channel.get_or_insert_with(|| {
+                instrumented_bounded_channel(
+                    &format!("{}::ParallelMerge", worker.step.name()),
+                    channel_size,
+                )
+            });
+            let merged_sender = merged_sender.clone();
+
+            self.graph.current_node_index = upstream_node_index;
+            self.graph.add_and_connect_step(&worker);
+            let worker_node_index = self.graph.current_node_index.unwrap();
+            let (worker_output_receiver, worker_handle) =
+                worker.spawn(None, channel_size, None, self.graph.shutdown_signal.clone());
+            worker_node_indices.push(worker_node_index);
+
+            // Forwards until the worker is done, so the merged channel closes after the last one.
+            // The worker's node tracks this task, which only ends once the worker has too.
+            let join_handle = tokio::spawn(async move {
+                while let Ok(output) = worker_output_receiver.recv().await {
+                    if merged_sender.send(output).await.is_err() {
+                        break;
+                    }
+                }
+                drop(merged_sender);
+                // Unblocks the worker if it is still sending
+                drop(worker_output_receiver);
+                if let Err(e) = worker_handle.await {
+                    if e.is_panic() {
+                        std::panic::resume_unwind(e.into_panic());
+                    }
+                }
+            });
+            self.graph
+                .set_join_handle(worker_node_index.index(), join_handle);
+        }
+        let (_, merged_receiver) = merged_channel.unwrap();
+
+        // The ordering step is added to the graph once it is connected or ended, with an edge
+        // from every worker.
+        self.graph.current_node_index = worker_node_indices.pop();
+        self.graph.pending_parent_indices = worker_node_indices;
+        let order_step = RunnablePollableStep::new(OrderByVersionStep::new(
+            starting_version,
+            PARALLEL_ORDER_POLL_INTERVAL,
+        ));
+        ProcessorBuilder {
+            current_step: Some(CurrentStepHolder::RunnableStepWithInputReceiver(
+                order_step.add_input_receiver(merged_receiver),
+            )),
+            graph: self.graph,
+        }
+    }
+
     pub fn fanout_broadcast(mut self, num_outputs: usize) -> FanoutBuilder<Input, Output, Step>
     where
         Output: Clone + Send + 'static,
@@ -345,7 +509,8 @@ where
             CurrentStepHolder::RunnableStepWithInputReceiver(current_step) => {
                 let step_name = current_step.step.name();
                 self.graph.add_and_connect_step(&current_step);
-                let (output_receiver, join_handle) = current_step.spawn(None, num_outputs, None);
+                let (output_receiver, join_handle) =
+                    current_step.spawn(None, num_outputs, None, self.graph.shutdown_signal.clone());
                 self.graph
                     .set_join_handle(self.graph.current_node_index.unwrap().index(), join_handle);
                 (output_receiver, step_name)
@@ -385,9 +550,9 @@ where
                             output_senders[sender_count - 1].send(input).await.unwrap();
                         }
                     },
-                    Err(e) => {
-                        panic!("Error receiving from previous step for fanout: {e:?}");
-                    },
+                    // The previous step has finished, e.g. on shutdown. Dropping the senders
+                    // closes the input of every fanned out step.
+                    Err(_) => break,
                 }
             }
         });
@@ -418,8 +583,12 @@ where
             Some(current_step) => match current_step {
                 CurrentStepHolder::RunnableStepWithInputReceiver(current_step) => {
                     self.graph.add_and_connect_step(&current_step);
-                    let (output_receiver, join_handle) =
-                        current_step.spawn(None, channel_size, None);
+                    let (output_receiver, join_handle) = current_step.spawn(
+                        None,
+                        channel_size,
+                        None,
+                        self.graph.shutdown_signal.clone(),
+                    );
                     self.graph.set_join_handle(
                         self.graph.current_node_index.unwrap().index(),
                         join_handle,
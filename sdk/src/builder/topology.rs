@@ -0,0 +1,194 @@
+//! Live view of the running pipeline: its steps with their latest metrics, and the channels
+//! between them with how full they are and how fast batches go through. A step whose input
+//! channel stays full while its own output channel stays empty is the bottleneck.
+
+use crate::{builder::GraphBuilder, utils::step_metrics::StepMetricsSnapshot};
+use instrumented_channel::InstrumentedAsyncReceiver;
+use petgraph::visit::EdgeRef;
+use serde::Serialize;
+use std::sync::Mutex;
+
+static PIPELINE: Mutex<Option<GraphBuilder>> = Mutex::new(None);
+
+/// Makes the pipeline built with `graph` the one reported by the topology endpoints and the
+/// admin API.
+pub fn register_pipeline(graph: GraphBuilder) {
+    *PIPELINE.lock().unwrap() = Some(graph);
+}
+
+/// The registered pipeline, or `None` if no pipeline is registered yet.
+pub fn registered_pipeline() -> Option<GraphBuilder> {
+    PIPELINE.lock().unwrap().clone()
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ChannelState {
+    /// The step whose output the channel carries.
+    pub output_of: String,
+    /// Batches waiting in the channel.
+    pub size: usize,
+    /// `None` for unbounded channels.
+    pub capacity: Option<usize>,
+    /// Batches received per second, averaged over the last minute.
+    pub messages_per_sec: f64,
+}
+
+impl ChannelState {
+    pub fn of<T>(receiver: &InstrumentedAsyncReceiver<T>) -> Self {
+        let capacity = receiver.capacity();
+        Self {
+            output_of: receiver.output_of().to_string(),
+            size: receiver.len(),
+            capacity: (capacity != usize::MAX).then_some(capacity),
+            messages_per_sec: receiver.receive_rate(),
+        }
+    }
+
+    /// One line summary, for graph labels.
+    pub fn summary(&self) -> String {
+        let capacity = self
+            .capacity
+            .map_or_else(|| "unbounded".to_string(), |capacity| capacity.to_string());
+        format!(
+            "{}/{} queued, {:.2} msg/s",
+            self.size, capacity, self.messages_per_sec
+        )
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Topology {
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TopologyNode {
+    pub id: usize,
+    pub name: String,
+    pub step_type: String,
+    pub input_type: String,
+    pub output_type: String,
+    /// Whether the step's task is still running.
+    pub running: bool,
+    #[serde(flatten)]
+    pub metrics: StepMetricsSnapshot,
+}
+
+/// A channel from one step to the next, i.e. the input channel of `to`.
+#[derive(Debug, Serialize)]
+pub struct TopologyEdge {
+    pub from: usize,
+    pub to: usize,
+    #[serde(flatten)]
+    pub channel: ChannelState,
+}
+
+impl GraphBuilder {
+    pub fn topology(&self) -> Topology {
+        let graph = self.graph.lock().unwrap().clone();
+        let node_map = self.node_map.lock().unwrap();
+
+        let mut nodes = node_map
+            .values()
+            .map(|node| TopologyNode {
+                id: node.id,
+                name: node.name.clone(),
+                step_type: node.step_type.clone(),
+                input_type: node.input_type.clone(),
+                output_type: node.output_type.clone(),
+                running: node
+                    .join_handle
+                    .as_ref()
+                    .is_some_and(|handle| !handle.is_finished()),
+                metrics: StepMetricsSnapshot::get(&node.name),
+            })
+            .collect::<Vec<_>>();
+        nodes.sort_by_key(|node| node.id);
+
+        let edges = graph
+            .edge_references()
+            .map(|edge| {
+                let from = graph[edge.source()];
+                let to = graph[edge.target()];
+                TopologyEdge {
+                    from,
+                    to,
+                    channel: node_map[&to].input_channel.get(),
+                }
+            })
+            .collect();
+
+        Topology { nodes, edges }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        builder::ProcessorBuilder,
+        test::{steps::pass_through_step::PassThroughStep, utils::receive_with_timeout},
+        traits::{RunnableAsyncStep, RunnableStepWithInputReceiver},
+        types::transaction_context::{TransactionContext, TransactionMetadata},
+    };
+    use instrumented_channel::instrumented_bounded_channel;
+    use std::time::Duration;
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[allow(clippy::needless_return)]
+    async fn test_topology() {
+        let (input_sender, input_receiver) = instrumented_bounded_channel("input", 1);
+        let input_step = RunnableStepWithInputReceiver::new(
+            input_receiver,
+            RunnableAsyncStep::new(PassThroughStep::new_named("TopologyFirstStep".to_string())),
+        );
+        let (builder, mut output_receiver) =
+            ProcessorBuilder::new_with_runnable_input_receiver_first_step(input_step)
+                .connect_to(
+                    RunnableAsyncStep::new(PassThroughStep::new_named(
+                        "TopologySecondStep".to_string(),
+                    )),
+                    5,
+                )
+                .end_and_return_output_receiver(5);
+
+        input_sender
+            .send(TransactionContext {
+                data: vec![1, 2, 3],
+                metadata: TransactionMetadata {
+                    start_version: 0,
+                    end_version: 7,
+                    start_transaction_timestamp: None,
+                    end_transaction_timestamp: None,
+                    total_size_in_bytes: 0,
+                },
+            })
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        receive_with_timeout(&mut output_receiver, 100)
+            .await
+            .unwrap();
+
+        let topology = builder.graph.topology();
+        let names = topology
+            .nodes
+            .iter()
+            .map(|node| node.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["TopologyFirstStep", "TopologySecondStep"]);
+        assert_eq!(topology.nodes[1].metrics.latest_version(), 7);
+
+        assert_eq!(topology.edges.len(), 1);
+        let edge = &topology.edges[0];
+        assert_eq!((edge.from, edge.to), (0, 1));
+        assert_eq!(edge.channel.output_of, "TopologyFirstStep");
+        assert_eq!(edge.channel.capacity, Some(5));
+        assert_eq!(edge.channel.size, 0);
+        assert!(edge.channel.messages_per_sec > 0.0);
+
+        let dot = builder.graph.dot();
+        assert!(dot.contains("version 7"));
+        assert!(dot.contains("0/5 queued"));
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{
+    common_steps::Sizeable,
+    traits::{
+        pollable_async_step::PollableAsyncRunType, NamedStep, PollableAsyncStep, Processable,
+    },
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Longest time between two checks for merged batches to release.
+const MAX_POLL_INTERVAL_MS: u64 = 50;
+
+/// Config for CoalescingStep. A merged batch is released as soon as it reaches any of the
+/// limits.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CoalescingConfig {
+    /// Release once the merged batch holds this many items, as counted by `Mergeable::num_items`.
+    pub max_items: usize,
+    /// Release once the merged batch holds this many bytes, as measured by `Sizeable`.
+    pub max_bytes: u64,
+    /// Release once the oldest batch in the merged batch has waited this long.
+    pub max_latency_ms: u64,
+}
+
+impl Default for CoalescingConfig {
+    fn default() -> Self {
+        Self {
+            max_items: 1_000,
+            max_bytes: 10_000_000,
+            max_latency_ms: 500,
+        }
+    }
+}
+
+/// Data that `CoalescingStep` can merge consecutive batches of.
+pub trait Mergeable {
+    /// Appends `other`, which covers the versions right after `self`.
+    fn merge(&mut self, other: Self);
+
+    /// Number of items, which `CoalescingConfig::max_items` is compared against.
+    fn num_items(&self) -> usize;
+}
+
+impl<T> Mergeable for Vec<T> {
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+
+    fn num_items(&self) -> usize {
+        self.len()
+    }
+}
+
+/// A merged batch that hasn't reached any of the limits yet.
+struct PendingBatch<Input> {
+    context: TransactionContext<Input>,
+    size_in_bytes: u64,
+    first_received_at: Instant,
+}
+
+/// This step merges consecutive batches into one, so the steps after it, typically the DB
+/// writes, run once for many small batches instead of once for each. This matters when tailing
+/// the chain, where every batch from the transaction stream holds only a few transactions.
+///
+/// Each batch is merged into the pending one with `Mergeable::merge`, widening its versions and
+/// timestamps. The pending batch is released once it reaches `max_items` or `max_bytes`, or once
+/// it has waited `max_latency_ms`, whichever comes first. A batch that doesn't start right after
+/// the pending one releases it and starts a new one, so released batches always cover a
+/// contiguous range of versions. Batches are released in order.
+pub struct CoalescingStep<Input>
+where
+    Self: Sized + Send + 'static,
+    Input: Mergeable + Sizeable + Send + 'static,
+{
+    config: CoalescingConfig,
+    pending: Option<PendingBatch<Input>>,
+    /// Merged batches waiting for the next poll to release them.
+    ready: Vec<TransactionContext<Input>>,
+}
+
+impl<Input> CoalescingStep<Input>
+where
+    Self: Sized + Send + 'static,
+    Input: Mergeable + Sizeable + Send + 'static,
+{
+    pub fn new(config: CoalescingConfig) -> Self {
+        Self {
+            config,
+            pending: None,
+            ready: Vec::new(),
+        }
+    }
+
+    fn release_pending(&mut self) {
+        if let Some(pending) = self.pending.take() {
+            self.ready.push(pending.context);
+        }
+    }
+}
+
+#[async_trait]
+impl<Input> Processable for CoalescingStep<Input>
+where
+    Input: Mergeable + Sizeable + Send + Sync + 'static,
+{
+    type Input = Input;
+    type Output = Input;
+    type RunType = PollableAsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Input>,
+    ) -> Result<Option<TransactionContext<Input>>, ProcessorError> {
+        let size_in_bytes = item.data.size_in_bytes();
+        let is_next = self.pending.as_ref().is_some_and(|pending| {
+            item.metadata.start_version == pending.context.metadata.end_version + 1
+        });
+        if !is_next {
+            self.release_pending();
+        }
+
+        let pending = match self.pending.as_mut() {
+            Some(pending) => {
+                let metadata = &mut pending.context.metadata;
+                metadata.end_version = item.metadata.end_version;
+                metadata.end_transaction_timestamp = item.metadata.end_transaction_timestamp;
+                metadata.total_size_in_bytes += item.metadata.total_size_in_bytes;
+                pending.context.data.merge(item.data);
+                pending.size_in_bytes += size_in_bytes;
+                pending
+            },
+            None => self.pending.insert(PendingBatch {
+                context: item,
+                size_in_bytes,
+                first_received_at: Instant::now(),
+            }),
+        };
+
+        if pending.context.data.num_items() >= self.config.max_items
+            || pending.size_in_bytes >= self.config.max_bytes
+        {
+            self.release_pending();
+        }
+        Ok(None) // Released by `poll`, so batches go out in order
+    }
+
+    // Once polling ends, release everything that is left
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.release_pending();
+        Ok(Some(std::mem::take(&mut self.ready)))
+    }
+}
+
+#[async_trait]
+impl<Input> PollableAsyncStep for CoalescingStep<Input>
+where
+    Input: Mergeable + Sizeable + Send + Sync + 'static,
+{
+    fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.config.max_latency_ms.clamp(1, MAX_POLL_INTERVAL_MS))
+    }
+
+    async fn poll(&mut self) -> Result<Option<Vec<TransactionContext<Input>>>, ProcessorError> {
+        let max_latency = Duration::from_millis(self.config.max_latency_ms);
+        if self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.first_received_at.elapsed() >= max_latency)
+        {
+            self.release_pending();
+        }
+        Ok(Some(std::mem::take(&mut self.ready)))
+    }
+}
+
+impl<Input> NamedStep for CoalescingStep<Input>
+where
+    Input: Mergeable + Sizeable + Send + 'static,
+{
+    fn name(&self) -> String {
+        format!("CoalescingStep: {}", std::any::type_name::<Input>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::transaction_context::TransactionMetadata;
+
+    impl Sizeable for Vec<u8> {
+        fn size_in_bytes(&self) -> u64 {
+            self.len() as u64
+        }
+    }
+
+    fn make_context(
+        start_version: u64,
+        end_version: u64,
+        data: Vec<u8>,
+    ) -> TransactionContext<Vec<u8>> {
+        TransactionContext {
+            data,
+            metadata: TransactionMetadata {
+                start_version,
+                end_version,
+                start_transaction_timestamp: None,
+                end_transaction_timestamp: None,
+                total_size_in_bytes: 100,
+            },
+        }
+    }
+
+    async fn poll_versions(step: &mut CoalescingStep<Vec<u8>>) -> Vec<(u64, u64, Vec<u8>)> {
+        step.poll()
+            .await
+            .unwrap()
+            .unwrap()
+            .into_iter()
+            .map(|context| {
+                (
+                    context.metadata.start_version,
+                    context.metadata.end_version,
+                    context.data,
+                )
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_releases_on_max_items_and_bytes() {
+        let mut step = CoalescingStep::new(CoalescingConfig {
+            max_items: 3,
+            max_bytes: 4,
+            max_latency_ms: 60_000,
+        });
+
+        for (version, data) in [(0, vec![1]), (1, vec![2]), (2, vec![3]), (3, vec![4])] {
+            assert!(step
+                .process(make_context(version, version, data))
+                .await
+                .unwrap()
+                .is_none());
+        }
+        // The first three reach `max_items`, the fourth is still pending
+        assert_eq!(poll_versions(&mut step).await, [(0, 2, vec![1, 2, 3])]);
+
+        step.process(make_context(4, 5, vec![5, 6, 7]))
+            .await
+            .unwrap();
+        // Together with the pending batch that's 4 bytes
+        assert_eq!(poll_versions(&mut step).await, [(3, 5, vec![4, 5, 6, 7])]);
+        assert_eq!(poll_versions(&mut step).await, []);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_releases_on_max_latency_and_gaps() {
+        let mut step = CoalescingStep::new(CoalescingConfig {
+            max_items: 100,
+            max_bytes: 100,
+            max_latency_ms: 50,
+        });
+
+        step.process(make_context(0, 4, vec![1])).await.unwrap();
+        step.process(make_context(5, 9, vec![2])).await.unwrap();
+        // Not right after the pending batch, so it can't be merged into it
+        step.process(make_context(20, 29, vec![3])).await.unwrap();
+        assert_eq!(poll_versions(&mut step).await, [(0, 9, vec![1, 2])]);
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        let released = step.poll().await.unwrap().unwrap();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].metadata.start_version, 20);
+        assert_eq!(released[0].metadata.total_size_in_bytes, 100);
+
+        step.process(make_context(30, 30, vec![4])).await.unwrap();
+        step.process(make_context(31, 31, vec![5])).await.unwrap();
+        let released = step.cleanup().await.unwrap().unwrap();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].data, vec![4, 5]);
+        assert_eq!(released[0].metadata.total_size_in_bytes, 200);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::{
+    traits::{AsyncStep, NamedStep, PollableAsyncStep, Processable},
+    types::transaction_context::{TransactionContext, TransactionMetadata},
+    utils::errors::ProcessorError,
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tracing::{error, warn};
+
+/// Records the batches that `ErrorPolicyStep` skips, so they can be inspected and re-processed,
+/// e.g. with a replay of their version range.
+#[async_trait]
+pub trait DeadLetterStore {
+    async fn record_skipped_batch(
+        &self,
+        step_name: &str,
+        metadata: &TransactionMetadata,
+        error: &ProcessorError,
+    ) -> Result<(), ProcessorError>;
+}
+
+/// What `ErrorPolicyStep` does with a batch that failed for good.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureAction {
+    /// Return the error, which stops the step and with it the processor.
+    #[default]
+    Halt,
+    /// Record the batch in the dead-letter store and pass on an empty batch in its place, so
+    /// `VersionTrackerStep` doesn't see a gap.
+    Skip,
+}
+
+/// Config for ErrorPolicyStep. Batches that fail with a retryable error (see
+/// `ProcessorError::is_retryable`) are retried up to `max_retries` times, waiting
+/// `initial_backoff_ms` before the first retry and twice as long before each next one, up to
+/// `max_backoff_ms`. Batches that fail with a fatal error, or run out of retries, get
+/// `on_failure`.
+///
+/// The default is to halt on the first error, like steps without a policy.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorPolicyConfig {
+    pub max_retries: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub on_failure: FailureAction,
+}
+
+impl Default for ErrorPolicyConfig {
+    fn default() -> Self {
+        Self {
+            max_retries: 0,
+            initial_backoff_ms: 500,
+            max_backoff_ms: 30_000,
+            on_failure: FailureAction::Halt,
+        }
+    }
+}
+
+/// Wraps a step and applies an `ErrorPolicyConfig` to the errors its `process` returns. Polling,
+/// `init` and `cleanup` are passed through as is.
+///
+/// Retrying needs a copy of the batch, so the step's input must be `Clone`. Skipping passes on a
+/// default output in place of the batch, so its output must be `Default`.
+pub struct ErrorPolicyStep<Step, Store>
+where
+    Step: Processable,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+    pub step: Step,
+    pub config: ErrorPolicyConfig,
+    pub dead_letter_store: Store,
+}
+
+impl<Step, Store> ErrorPolicyStep<Step, Store>
+where
+    Step: Processable,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+    pub fn new(step: Step, config: ErrorPolicyConfig, dead_letter_store: Store) -> Self {
+        Self {
+            step,
+            config,
+            dead_letter_store,
+        }
+    }
+}
+
+#[async_trait]
+impl<Step, Store> Processable for ErrorPolicyStep<Step, Store>
+where
+    Step: Processable,
+    Step::Input: Clone,
+    Step::Output: Default,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+    type Input = Step::Input;
+    type Output = Step::Output;
+    type RunType = Step::RunType;
+
+    async fn init(&mut self) {
+        self.step.init().await;
+    }
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Step::Input>,
+    ) -> Result<Option<TransactionContext<Step::Output>>, ProcessorError> {
+        let metadata = item.metadata.clone();
+        let mut item = Some(item);
+        let mut retries = 0;
+        let mut backoff = Duration::from_millis(self.config.initial_backoff_ms);
+        let error = loop {
+            // Only keep a copy of the batch while it may be retried
+            let input = if retries < self.config.max_retries {
+                item.clone()
+            } else {
+                item.take()
+            };
+            let error = match self.step.process(input.unwrap()).await {
+                Ok(output) => return Ok(output),
+                Err(e) => e,
+            };
+            if !error.is_retryable() || retries >= self.config.max_retries {
+                break error;
+            }
+            retries += 1;
+            warn!(
+                step_name = self.name(),
+                start_version = metadata.start_version,
+                end_version = metadata.end_version,
+                retry = retries,
+                error = error.to_string(),
+                "Failed to process batch. Retrying."
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(Duration::from_millis(self.config.max_backoff_ms));
+        };
+
+        match self.config.on_failure {
+            FailureAction::Halt => Err(error),
+            FailureAction::Skip => {
+                error!(
+                    step_name = self.name(),
+                    start_version = metadata.start_version,
+                    end_version = metadata.end_version,
+                    error = error.to_string(),
+                    "Failed to process batch. Skipping it."
+                );
+                // If the batch can't be recorded, halting is the only way not to lose it
+                self.dead_letter_store
+                    .record_skipped_batch(&self.name(), &metadata, &error)
+                    .await?;
+                Ok(Some(TransactionContext {
+                    data: Default::default(),
+                    metadata,
+                }))
+            },
+        }
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.step.cleanup().await
+    }
+}
+
+impl<Step, Store> AsyncStep for ErrorPolicyStep<Step, Store>
+where
+    Step: AsyncStep,
+    Step::Input: Clone,
+    Step::Output: Default,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+}
+
+#[async_trait]
+impl<Step, Store> PollableAsyncStep for ErrorPolicyStep<Step, Store>
+where
+    Step: PollableAsyncStep,
+    Step::Input: Clone,
+    Step::Output: Default,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+    fn poll_interval(&self) -> Duration {
+        self.step.poll_interval()
+    }
+
+    async fn poll(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.step.poll().await
+    }
+
+    async fn should_continue_polling(&mut self) -> bool {
+        self.step.should_continue_polling().await
+    }
+}
+
+impl<Step, Store> NamedStep for ErrorPolicyStep<Step, Store>
+where
+    Step: Processable,
+    Store: DeadLetterStore + Send + Sync + 'static,
+{
+    fn name(&self) -> String {
+        self.step.name()
+    }
+
+    fn type_name(&self) -> String {
+        format!("{} (via ErrorPolicyStep)", std::any::type_name::<Step>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::AsyncRunType;
+    use std::sync::{Arc, Mutex};
+
+    /// Fails the first `failures` batches it gets with `error`.
+    struct FailingStep {
+        failures: usize,
+        error: fn() -> ProcessorError,
+    }
+
+    impl NamedStep for FailingStep {
+        fn name(&self) -> String {
+            "FailingStep".to_string()
+        }
+    }
+
+    #[async_trait]
+    impl Processable for FailingStep {
+        type Input = Vec<usize>;
+        type Output = Vec<usize>;
+        type RunType = AsyncRunType;
+
+        async fn process(
+            &mut self,
+            item: TransactionContext<Vec<usize>>,
+        ) -> Result<Option<TransactionContext<Vec<usize>>>, ProcessorError> {
+            if self.failures > 0 {
+                self.failures -= 1;
+                return Err((self.error)());
+            }
+            Ok(Some(item))
+        }
+    }
+
+    impl AsyncStep for FailingStep {}
+
+    #[derive(Clone, Default)]
+    struct TestDeadLetterStore {
+        skipped: Arc<Mutex<Vec<(u64, u64)>>>,
+    }
+
+    #[async_trait]
+    impl DeadLetterStore for TestDeadLetterStore {
+        async fn record_skipped_batch(
+            &self,
+            _step_name: &str,
+            metadata: &TransactionMetadata,
+            _error: &ProcessorError,
+        ) -> Result<(), ProcessorError> {
+            self.skipped
+                .lock()
+                .unwrap()
+                .push((metadata.start_version, metadata.end_version));
+            Ok(())
+        }
+    }
+
+    fn db_error() -> ProcessorError {
+        ProcessorError::DBStoreError {
+            message: "connection refused".to_string(),
+            query: None,
+            source: None,
+        }
+    }
+
+    fn process_error() -> ProcessorError {
+        ProcessorError::ProcessError {
+            message: "bad data".to_string(),
+        }
+    }
+
+    fn make_batch() -> TransactionContext<Vec<usize>> {
+        TransactionContext {
+            data: vec![1, 2, 3],
+            metadata: TransactionMetadata {
+                start_version: 10,
+                end_version: 12,
+                ..Default::default()
+            },
+        }
+    }
+
+    fn make_step(
+        failures: usize,
+        error: fn() -> ProcessorError,
+        max_retries: u32,
+        on_failure: FailureAction,
+    ) -> (
+        ErrorPolicyStep<FailingStep, TestDeadLetterStore>,
+        TestDeadLetterStore,
+    ) {
+        let store = TestDeadLetterStore::default();
+        let step = ErrorPolicyStep::new(
+            FailingStep { failures, error },
+            ErrorPolicyConfig {
+                max_retries,
+                initial_backoff_ms: 1,
+                max_backoff_ms: 2,
+                on_failure,
+            },
+            store.clone(),
+        );
+        (step, store)
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_retries_retryable_errors() {
+        let (mut step, store) = make_step(2, db_error, 2, FailureAction::Halt);
+        let output = step.process(make_batch()).await.unwrap().unwrap();
+        assert_eq!(output.data, vec![1, 2, 3]);
+        assert!(store.skipped.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_halts_when_out_of_retries() {
+        let (mut step, _) = make_step(3, db_error, 2, FailureAction::Halt);
+        assert!(step.process(make_batch()).await.is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_skips_fatal_errors_without_retrying() {
+        let (mut step, store) = make_step(1, process_error, 5, FailureAction::Skip);
+        let output = step.process(make_batch()).await.unwrap().unwrap();
+        assert!(output.data.is_empty(), "Skipped batch should be empty");
+        assert_eq!(output.metadata.start_version, 10);
+        assert_eq!(output.metadata.end_version, 12);
+        assert_eq!(*store.skipped.lock().unwrap(), vec![(10, 12)]);
+
+        // The failure was not retried, so the next batch goes through
+        let output = step.process(make_batch()).await.unwrap().unwrap();
+        assert_eq!(output.data, vec![1, 2, 3]);
+    }
+}
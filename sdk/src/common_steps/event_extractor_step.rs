@@ -0,0 +1,350 @@
+use crate::{
+    aptos_indexer_transaction_stream::utils::time::parse_timestamp,
+    traits::{async_step::AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::{errors::ProcessorError, struct_tag::StructTag},
+};
+use ahash::AHashMap;
+use anyhow::Result;
+use aptos_protos::transaction::v1::{transaction::TxnData, Event, Transaction};
+use async_trait::async_trait;
+use instrumented_channel::{
+    instrumented_unbounded_channel, InstrumentedAsyncReceiver, InstrumentedAsyncSender,
+};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+use tracing::warn;
+
+type EventDecoder<E> = Box<dyn Fn(&Event) -> Result<E> + Send + Sync>;
+
+/// Maps Move event types to decoders that turn matching events into `E`, usually an enum with one
+/// variant per event the processor cares about. Events of other types are skipped.
+///
+/// Types are compared as parsed `StructTag`s, so short and long addresses are interchangeable. A
+/// type registered without type arguments matches every instantiation of a generic event, e.g.
+/// `0x1::market::BetPlaced` also routes `0x1::market::BetPlaced<0x1::aptos_coin::AptosCoin>`.
+pub struct EventRegistry<E> {
+    /// Keyed by the struct without type arguments.
+    decoders: AHashMap<StructTag, Vec<(StructTag, EventDecoder<E>)>>,
+}
+
+impl<E> EventRegistry<E> {
+    pub fn new() -> Self {
+        Self {
+            decoders: AHashMap::new(),
+        }
+    }
+
+    /// Decodes the JSON data of `type_str` events into `T`, then wraps it into `E`.
+    pub fn register<T>(self, type_str: &str, wrap: fn(T) -> E) -> Self
+    where
+        E: 'static,
+        T: DeserializeOwned + 'static,
+    {
+        self.register_with(type_str, move |event| {
+            Ok(wrap(serde_json::from_str::<T>(&event.data)?))
+        })
+    }
+
+    /// Decodes `type_str` events with a custom decoder, e.g. one that tolerates several schema
+    /// versions of the event.
+    ///
+    /// # Panics
+    ///
+    /// If `type_str` is not a valid Move struct type.
+    pub fn register_with(
+        mut self,
+        type_str: &str,
+        decoder: impl Fn(&Event) -> Result<E> + Send + Sync + 'static,
+    ) -> Self {
+        let tag = type_str
+            .parse::<StructTag>()
+            .unwrap_or_else(|e| panic!("Cannot register event type: {e:#}"));
+        self.decoders
+            .entry(tag.without_type_args())
+            .or_default()
+            .push((tag, Box::new(decoder)));
+        self
+    }
+
+    fn decoder(&self, tag: &StructTag) -> Option<&EventDecoder<E>> {
+        self.decoders
+            .get(&tag.without_type_args())?
+            .iter()
+            .find(|(registered, _)| {
+                registered.type_args.is_empty() || registered.type_args == tag.type_args
+            })
+            .map(|(_, decoder)| decoder)
+    }
+
+    /// Decodes the registered events of one transaction, in emission order.
+    pub fn extract(&self, txn: &Transaction) -> (Vec<DecodedEvent<E>>, Vec<EventParseFailure>) {
+        let mut decoded = vec![];
+        let mut failures = vec![];
+        let (events, sender) = match txn.txn_data.as_ref() {
+            Some(TxnData::User(tx_inner)) => (
+                tx_inner.events.as_slice(),
+                tx_inner.request.as_ref().map(|r| r.sender.clone()),
+            ),
+            Some(TxnData::Genesis(tx_inner)) => (tx_inner.events.as_slice(), None),
+            Some(TxnData::BlockMetadata(tx_inner)) => (tx_inner.events.as_slice(), None),
+            _ => (&[][..], None),
+        };
+        if events.is_empty() {
+            return (decoded, failures);
+        }
+
+        let transaction_timestamp = txn
+            .timestamp
+            .as_ref()
+            .map(|ts| parse_timestamp(ts, txn.version as i64).naive_utc())
+            .unwrap_or_default();
+        let transaction_hash = txn
+            .info
+            .as_ref()
+            .map(|info| format!("0x{}", hex::encode(&info.hash)))
+            .unwrap_or_default();
+
+        for (event_index, event) in events.iter().enumerate() {
+            let Ok(type_tag) = event.type_str.parse::<StructTag>() else {
+                continue;
+            };
+            let Some(decoder) = self.decoder(&type_tag) else {
+                continue;
+            };
+            match decoder(event) {
+                Ok(event) => decoded.push(DecodedEvent {
+                    type_tag,
+                    transaction_version: txn.version,
+                    transaction_block_height: txn.block_height,
+                    transaction_timestamp,
+                    transaction_hash: transaction_hash.clone(),
+                    sender: sender.clone(),
+                    event_index: event_index as u64,
+                    event,
+                }),
+                Err(e) => failures.push(EventParseFailure {
+                    transaction_version: txn.version,
+                    event_index: event_index as u64,
+                    type_str: event.type_str.clone(),
+                    data: event.data.clone(),
+                    error: format!("{e:#}"),
+                }),
+            }
+        }
+        (decoded, failures)
+    }
+}
+
+impl<E> Default for EventRegistry<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A registered event decoded into `E`, with the context of the transaction that emitted it.
+#[derive(Clone, Debug)]
+pub struct DecodedEvent<E> {
+    pub transaction_version: u64,
+    pub transaction_block_height: u64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub transaction_hash: String,
+    /// Only user transactions have a sender.
+    pub sender: Option<String>,
+    /// Position of the event among all events of the transaction.
+    pub event_index: u64,
+    /// Exact type of the event, including the type arguments of generic events.
+    pub type_tag: StructTag,
+    pub event: E,
+}
+
+/// A registered event whose data couldn't be decoded.
+#[derive(Clone, Debug)]
+pub struct EventParseFailure {
+    pub transaction_version: u64,
+    pub event_index: u64,
+    pub type_str: String,
+    pub data: String,
+    pub error: String,
+}
+
+/// EventExtractorStep decodes the events registered in an `EventRegistry` out of each batch of
+/// transactions, so the following steps only deal with typed events. Decoding failures don't fail
+/// the batch; they are sent to the receiver returned by `new`. Dropping that receiver discards
+/// them after they are logged.
+pub struct EventExtractorStep<E>
+where
+    Self: Sized + Send + 'static,
+    E: Send + Sync + 'static,
+{
+    registry: Arc<EventRegistry<E>>,
+    failure_sender: InstrumentedAsyncSender<EventParseFailure>,
+}
+
+impl<E> EventExtractorStep<E>
+where
+    E: Send + Sync + 'static,
+{
+    pub fn new(
+        registry: Arc<EventRegistry<E>>,
+    ) -> (Self, InstrumentedAsyncReceiver<EventParseFailure>) {
+        let (failure_sender, failure_receiver) =
+            instrumented_unbounded_channel("EventExtractorStep failures");
+        (
+            Self {
+                registry,
+                failure_sender,
+            },
+            failure_receiver,
+        )
+    }
+}
+
+#[async_trait]
+impl<E> Processable for EventExtractorStep<E>
+where
+    E: Send + Sync + 'static,
+{
+    type Input = Vec<Transaction>;
+    type Output = Vec<DecodedEvent<E>>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<Transaction>>,
+    ) -> Result<Option<TransactionContext<Vec<DecodedEvent<E>>>>, ProcessorError> {
+        let mut data = vec![];
+        for txn in &item.data {
+            let (decoded, failures) = self.registry.extract(txn);
+            data.extend(decoded);
+            for failure in failures {
+                warn!(
+                    transaction_version = failure.transaction_version,
+                    event_index = failure.event_index,
+                    event_type = %failure.type_str,
+                    error = %failure.error,
+                    "Failed to decode event"
+                );
+                // The receiver may have been dropped if nobody cares about failures.
+                let _ = self.failure_sender.send(failure).await;
+            }
+        }
+        Ok(Some(TransactionContext {
+            data,
+            metadata: item.metadata,
+        }))
+    }
+}
+
+impl<E> AsyncStep for EventExtractorStep<E> where E: Send + Sync + 'static {}
+
+impl<E> NamedStep for EventExtractorStep<E>
+where
+    E: Send + Sync + 'static,
+{
+    fn name(&self) -> String {
+        format!("EventExtractor<{}>", std::any::type_name::<E>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        types::transaction_context::TransactionMetadata, utils::struct_tag::normalize_address,
+    };
+    use aptos_protos::transaction::v1::{TransactionInfo, UserTransaction, UserTransactionRequest};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Deposit {
+        amount: u64,
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum TestEvent {
+        Deposit(Deposit),
+    }
+
+    fn event(type_str: &str, data: &str) -> Event {
+        Event {
+            type_str: type_str.to_string(),
+            data: data.to_string(),
+            ..Event::default()
+        }
+    }
+
+    fn generate_transaction_context() -> TransactionContext<Vec<Transaction>> {
+        let txn = Transaction {
+            version: 7,
+            block_height: 3,
+            info: Some(TransactionInfo {
+                hash: vec![0xab, 0xcd],
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                request: Some(UserTransactionRequest {
+                    sender: "0x42".to_string(),
+                    ..UserTransactionRequest::default()
+                }),
+                events: vec![
+                    event("0x1::coin::Withdraw", r#"{"amount": "1"}"#),
+                    event("0x1::coin::Deposit", r#"{"amount": 5}"#),
+                    event("0x1::coin::Deposit", r#"{"amount": "not a number"}"#),
+                    event(
+                        &format!(
+                            "{}::coin::Deposit<0x1::aptos_coin::AptosCoin>",
+                            normalize_address("0x1")
+                        ),
+                        r#"{"amount": 6}"#,
+                    ),
+                ],
+            })),
+            ..Transaction::default()
+        };
+        TransactionContext {
+            data: vec![txn],
+            metadata: TransactionMetadata {
+                start_version: 7,
+                end_version: 7,
+                start_transaction_timestamp: None,
+                end_transaction_timestamp: None,
+                total_size_in_bytes: 0,
+            },
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_event_extractor_step() {
+        let registry = EventRegistry::new().register("0x1::coin::Deposit", TestEvent::Deposit);
+        let (mut step, failures) = EventExtractorStep::new(Arc::new(registry));
+
+        let result = step
+            .process(generate_transaction_context())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.data.len(), 2);
+        let decoded = &result.data[0];
+        assert_eq!(decoded.event, TestEvent::Deposit(Deposit { amount: 5 }));
+        assert_eq!(decoded.transaction_version, 7);
+        assert_eq!(decoded.transaction_block_height, 3);
+        assert_eq!(decoded.transaction_hash, "0xabcd");
+        assert_eq!(decoded.sender.as_deref(), Some("0x42"));
+        assert_eq!(decoded.event_index, 1);
+
+        // Long addresses and type arguments still route to the registered decoder.
+        let generic = &result.data[1];
+        assert_eq!(generic.event, TestEvent::Deposit(Deposit { amount: 6 }));
+        assert_eq!(
+            generic.type_tag.to_string(),
+            "0x1::coin::Deposit<0x1::aptos_coin::AptosCoin>"
+        );
+
+        let failure = failures.recv().await.unwrap();
+        assert_eq!(failure.event_index, 2);
+        assert_eq!(failure.type_str, "0x1::coin::Deposit");
+        assert!(failures.is_empty());
+    }
+}
@@ -1,14 +1,24 @@
 pub mod arcify_step;
+pub mod coalescing_step;
+pub mod error_policy_step;
+pub mod event_extractor_step;
 pub mod order_by_version_step;
 pub mod timed_buffer_step;
+pub mod transaction_filter_step;
 pub mod transaction_stream_step;
 pub mod version_tracker_step;
 pub mod write_rate_limit_step;
 
 // Re-export the steps
 pub use arcify_step::ArcifyStep;
+pub use coalescing_step::{CoalescingConfig, CoalescingStep, Mergeable};
+pub use error_policy_step::{DeadLetterStore, ErrorPolicyConfig, ErrorPolicyStep, FailureAction};
+pub use event_extractor_step::{
+    DecodedEvent, EventExtractorStep, EventParseFailure, EventRegistry,
+};
 pub use order_by_version_step::OrderByVersionStep;
 pub use timed_buffer_step::TimedBufferStep;
+pub use transaction_filter_step::{TransactionFilterMode, TransactionFilterStep};
 pub use transaction_stream_step::TransactionStreamStep;
 pub use version_tracker_step::{
     ProcessorStatusSaver, VersionTrackerStep, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
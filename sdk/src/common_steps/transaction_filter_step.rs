@@ -0,0 +1,188 @@
+use crate::{
+    aptos_indexer_transaction_stream::{BooleanTransactionFilter, Filterable},
+    traits::{async_step::AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use aptos_protos::transaction::v1::Transaction;
+use async_trait::async_trait;
+
+/// What to do with transactions that don't match the filter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TransactionFilterMode {
+    /// Remove them from the batch, like a data service that applies the filter server-side.
+    #[default]
+    Drop,
+    /// Keep them, but clear their payload, events and write set changes. Useful for downstream
+    /// steps that expect every version in the batch to be present.
+    Strip,
+}
+
+/// TransactionFilterStep applies a `BooleanTransactionFilter` locally, for data services that don't
+/// honor server-side filters. The batch metadata is passed through unchanged, so the versions
+/// tracked for checkpointing still cover the whole batch even if every transaction is dropped.
+pub struct TransactionFilterStep
+where
+    Self: Sized + Send + 'static,
+{
+    filter: BooleanTransactionFilter,
+    mode: TransactionFilterMode,
+}
+
+impl TransactionFilterStep {
+    pub fn new(filter: BooleanTransactionFilter, mode: TransactionFilterMode) -> Self {
+        Self { filter, mode }
+    }
+}
+
+#[async_trait]
+impl Processable for TransactionFilterStep {
+    type Input = Vec<Transaction>;
+    type Output = Vec<Transaction>;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Vec<Transaction>>,
+    ) -> Result<Option<TransactionContext<Vec<Transaction>>>, ProcessorError> {
+        let data = match self.mode {
+            TransactionFilterMode::Drop => item
+                .data
+                .into_iter()
+                .filter(|txn| self.filter.matches(txn))
+                .collect(),
+            TransactionFilterMode::Strip => item
+                .data
+                .into_iter()
+                .map(|mut txn| {
+                    if !self.filter.matches(&txn) {
+                        txn.txn_data = None;
+                        if let Some(info) = txn.info.as_mut() {
+                            info.changes.clear();
+                        }
+                    }
+                    txn
+                })
+                .collect(),
+        };
+        Ok(Some(TransactionContext {
+            data,
+            metadata: item.metadata,
+        }))
+    }
+}
+
+impl AsyncStep for TransactionFilterStep {}
+
+impl NamedStep for TransactionFilterStep {
+    fn name(&self) -> String {
+        "TransactionFilterStep".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        aptos_indexer_transaction_stream::{EventFilterBuilder, MoveStructTagFilterBuilder},
+        types::transaction_context::TransactionMetadata,
+    };
+    use aptos_protos::transaction::v1::{
+        move_type::Content, transaction::TxnData, Event, MoveStructTag, MoveType, TransactionInfo,
+        UserTransaction, WriteSetChange,
+    };
+
+    fn transaction(version: u64, module: &str, name: &str) -> Transaction {
+        Transaction {
+            version,
+            info: Some(TransactionInfo {
+                changes: vec![WriteSetChange::default()],
+                ..TransactionInfo::default()
+            }),
+            txn_data: Some(TxnData::User(UserTransaction {
+                events: vec![Event {
+                    r#type: Some(MoveType {
+                        content: Some(Content::Struct(MoveStructTag {
+                            address: "0x1".to_string(),
+                            module: module.to_string(),
+                            name: name.to_string(),
+                            generic_type_params: vec![],
+                        })),
+                        ..MoveType::default()
+                    }),
+                    type_str: format!("0x1::{module}::{name}"),
+                    ..Event::default()
+                }],
+                ..UserTransaction::default()
+            })),
+            ..Transaction::default()
+        }
+    }
+
+    fn generate_transaction_context() -> TransactionContext<Vec<Transaction>> {
+        TransactionContext {
+            data: vec![
+                transaction(1, "coin", "CoinDeposit"),
+                transaction(2, "market", "BetPlacedEvent"),
+                transaction(3, "coin", "CoinWithdraw"),
+            ],
+            metadata: TransactionMetadata {
+                start_version: 1,
+                end_version: 3,
+                start_transaction_timestamp: None,
+                end_transaction_timestamp: None,
+                total_size_in_bytes: 0,
+            },
+        }
+    }
+
+    fn market_events() -> BooleanTransactionFilter {
+        EventFilterBuilder::default()
+            .struct_type(
+                MoveStructTagFilterBuilder::default()
+                    .address("0x1".to_string())
+                    .module("market".to_string())
+                    .build()
+                    .unwrap(),
+            )
+            .build()
+            .unwrap()
+            .into()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_transaction_filter_step_drop() {
+        let mut step = TransactionFilterStep::new(market_events(), TransactionFilterMode::Drop);
+
+        let result = step
+            .process(generate_transaction_context())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            result.data.iter().map(|t| t.version).collect::<Vec<_>>(),
+            vec![2]
+        );
+        assert_eq!(result.metadata.start_version, 1);
+        assert_eq!(result.metadata.end_version, 3);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_transaction_filter_step_strip() {
+        let mut step = TransactionFilterStep::new(market_events(), TransactionFilterMode::Strip);
+
+        let result = step
+            .process(generate_transaction_context())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.data.len(), 3);
+        let stripped =
+            |t: &Transaction| t.txn_data.is_none() && t.info.as_ref().unwrap().changes.is_empty();
+        assert!(stripped(&result.data[0]));
+        assert!(!stripped(&result.data[1]));
+        assert!(stripped(&result.data[2]));
+    }
+}
@@ -1,7 +1,8 @@
 use crate::{
+    admin_api,
     traits::{NamedStep, PollableAsyncRunType, PollableAsyncStep, Processable},
     types::transaction_context::{TransactionContext, TransactionMetadata},
-    utils::errors::ProcessorError,
+    utils::{errors::ProcessorError, health},
 };
 use anyhow::Result;
 use aptos_indexer_transaction_stream::{
@@ -76,6 +77,7 @@ where
     async fn poll(
         &mut self,
     ) -> Result<Option<Vec<TransactionContext<Vec<Transaction>>>>, ProcessorError> {
+        admin_api::wait_while_paused().await;
         let txn_pb_response_res = self
             .transaction_stream
             .lock()
@@ -84,6 +86,7 @@ where
             .await;
         match txn_pb_response_res {
             Ok(txn_pb_response) => {
+                health::set_stream_connected(true);
                 let transactions_with_context = TransactionContext {
                     data: txn_pb_response.transactions,
                     metadata: TransactionMetadata {
@@ -102,6 +105,7 @@ where
                     error = ?e,
                     "Error fetching transactions from TransactionStream. Attempting to reconnect."
                 );
+                health::set_stream_connected(false);
 
                 // TransactionStream closes connections every 5 minutes. We should try to reconnect
                 match self
@@ -119,6 +123,7 @@ where
                                 .to_string(),
                             "Successfully reconnected to TransactionStream."
                         );
+                        health::set_stream_connected(true);
                         // Return nothing for now. The next poll will fetch the next batch of transactions.
                         Ok(None)
                     },
@@ -130,8 +135,9 @@ where
                             error = ?e,
                             " Error reconnecting transaction stream."
                         );
-                        Err(ProcessorError::PollError {
+                        Err(ProcessorError::ConnectionError {
                             message: format!("Error reconnecting to TransactionStream: {e:?}"),
+                            source: Some(e.into()),
                         })
                     },
                 }
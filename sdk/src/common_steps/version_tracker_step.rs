@@ -84,11 +84,9 @@ where
         // If there's a gap in version, return an error
         if let Some(last_success_batch) = self.last_success_batch.as_ref() {
             if last_success_batch.metadata.end_version + 1 != current_batch.metadata.start_version {
-                return Err(ProcessorError::ProcessError {
-                    message: format!(
-                        "Gap detected starting from version: {}",
-                        current_batch.metadata.start_version
-                    ),
+                return Err(ProcessorError::StreamGap {
+                    expected_version: last_success_batch.metadata.end_version + 1,
+                    actual_version: current_batch.metadata.start_version,
                 });
             }
         }
@@ -9,6 +9,8 @@ use crate::{
         },
     },
 };
+use aptos_protos::transaction::v1::Transaction;
+use prost::Message;
 use serde::{Deserialize, Serialize};
 use std::{
     marker::PhantomData,
@@ -168,6 +170,13 @@ pub trait Sizeable {
     fn size_in_bytes(&self) -> u64;
 }
 
+impl Sizeable for Vec<Transaction> {
+    /// The encoded size of the transactions, as received from the transaction stream.
+    fn size_in_bytes(&self) -> u64 {
+        self.iter().map(|txn| txn.encoded_len() as u64).sum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
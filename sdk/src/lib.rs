@@ -1,3 +1,4 @@
+pub mod admin_api;
 pub mod builder;
 pub mod common_steps; // TODO: Feature gate this?
 #[cfg(feature = "postgres_partial")]
@@ -23,11 +24,11 @@ mod tests {
         common_steps::TimedBufferStep,
         test::{steps::pass_through_step::PassThroughStep, utils::receive_with_timeout},
         traits::{
-            AsyncStep, IntoRunnableStep, NamedStep, Processable, RunnableAsyncStep,
-            RunnableStepWithInputReceiver,
+            AsyncStep, IntoRunnableStep, NamedStep, PollableAsyncRunType, PollableAsyncStep,
+            Processable, RunnableAsyncStep, RunnablePollableStep, RunnableStepWithInputReceiver,
         },
         types::transaction_context::{TransactionContext, TransactionMetadata},
-        utils::errors::ProcessorError,
+        utils::{errors::ProcessorError, shutdown::ShutdownSignal},
     };
     use anyhow::Result;
     use async_trait::async_trait;
@@ -149,6 +150,175 @@ mod tests {
         //second_handle.abort();
     }
 
+    /// Holds up the batch starting at version 0, so the batches after it finish first.
+    pub struct SlowFirstBatchStep;
+
+    impl AsyncStep for SlowFirstBatchStep {}
+
+    impl NamedStep for SlowFirstBatchStep {
+        fn name(&self) -> String {
+            "SlowFirstBatchStep".to_string()
+        }
+    }
+
+    #[async_trait]
+    impl Processable for SlowFirstBatchStep {
+        type Input = Vec<usize>;
+        type Output = Vec<usize>;
+        type RunType = ();
+
+        async fn process(
+            &mut self,
+            item: TransactionContext<Vec<usize>>,
+        ) -> Result<Option<TransactionContext<Vec<usize>>>, ProcessorError> {
+            if item.metadata.start_version == 0 {
+                tokio::time::sleep(Duration::from_millis(300)).await;
+            }
+            Ok(Some(item))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[allow(clippy::needless_return)]
+    async fn test_connect_to_parallel() {
+        let (input_sender, input_receiver) = instrumented_bounded_channel("input", 5);
+
+        let input_step = RunnableStepWithInputReceiver::new(
+            input_receiver,
+            RunnableAsyncStep::new(PassThroughStep::default()),
+        );
+
+        let (builder, mut output_receiver) =
+            ProcessorBuilder::new_with_runnable_input_receiver_first_step(input_step)
+                .connect_to_parallel(2, |_| RunnableAsyncStep::new(SlowFirstBatchStep), 0, 5)
+                .end_and_return_output_receiver(5);
+
+        for version in 0..4 {
+            let input = TransactionContext {
+                data: vec![version as usize],
+                metadata: TransactionMetadata {
+                    start_version: version,
+                    end_version: version,
+                    start_transaction_timestamp: None,
+                    end_transaction_timestamp: None,
+                    total_size_in_bytes: 0,
+                },
+            };
+            input_sender.send(input).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(600)).await;
+
+        for version in 0..4 {
+            let result = receive_with_timeout(&mut output_receiver, 100)
+                .await
+                .unwrap();
+            assert_eq!(
+                result.metadata.start_version, version,
+                "Output should be in version order"
+            );
+        }
+
+        // Input to both workers, and both workers to the ordering step
+        assert_eq!(builder.graph.graph.lock().unwrap().edge_count(), 4);
+        let running = |builder: &ProcessorBuilder<_, _, _>| {
+            builder
+                .graph
+                .topology()
+                .nodes
+                .into_iter()
+                .map(|node| node.running)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(running(&builder), vec![true; 4]);
+
+        // Closing the input stops every step, including the forwarding from the workers
+        drop(input_sender);
+        assert!(receive_with_timeout(&mut output_receiver, 1000)
+            .await
+            .is_none());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(running(&builder), vec![false; 4]);
+    }
+
+    /// Emits one version per poll, forever.
+    pub struct CountingStep {
+        pub next_version: u64,
+    }
+
+    impl NamedStep for CountingStep {
+        fn name(&self) -> String {
+            "CountingStep".to_string()
+        }
+    }
+
+    #[async_trait]
+    impl Processable for CountingStep {
+        type Input = ();
+        type Output = Vec<usize>;
+        type RunType = PollableAsyncRunType;
+
+        async fn process(
+            &mut self,
+            _item: TransactionContext<()>,
+        ) -> Result<Option<TransactionContext<Vec<usize>>>, ProcessorError> {
+            Ok(None)
+        }
+    }
+
+    #[async_trait]
+    impl PollableAsyncStep for CountingStep {
+        fn poll_interval(&self) -> Duration {
+            Duration::from_millis(10)
+        }
+
+        async fn poll(
+            &mut self,
+        ) -> Result<Option<Vec<TransactionContext<Vec<usize>>>>, ProcessorError> {
+            let version = self.next_version;
+            self.next_version += 1;
+            Ok(Some(vec![TransactionContext {
+                data: vec![version as usize],
+                metadata: TransactionMetadata {
+                    start_version: version,
+                    end_version: version,
+                    start_transaction_timestamp: None,
+                    end_transaction_timestamp: None,
+                    total_size_in_bytes: 0,
+                },
+            }]))
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[allow(clippy::needless_return)]
+    async fn test_shutdown_drains_pipeline() {
+        let shutdown_signal = ShutdownSignal::new();
+
+        // The buffer only releases its items on cleanup
+        let (_, mut output_receiver) = ProcessorBuilder::new_with_inputless_first_step(
+            RunnablePollableStep::new(CountingStep { next_version: 0 }),
+        )
+        .with_shutdown_signal(shutdown_signal.clone())
+        .connect_to(
+            TimedBufferStep::<Vec<usize>>::new(Duration::from_secs(60)).into_runnable_step(),
+            10,
+        )
+        .connect_to(RunnableAsyncStep::new(PassThroughStep::default()), 10)
+        .end_and_return_output_receiver(10);
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(output_receiver.len(), 0, "Output should be empty");
+        shutdown_signal.trigger();
+
+        // Every emitted version comes out, then the channel closes
+        let mut versions = vec![];
+        while let Some(output) = receive_with_timeout(&mut output_receiver, 1000).await {
+            versions.extend(output.data);
+        }
+        assert!(!versions.is_empty(), "Buffered versions should be flushed");
+        assert_eq!(versions, (0..versions.len()).collect::<Vec<_>>());
+    }
+
     #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
     #[allow(clippy::needless_return)]
     async fn test_fanin() {
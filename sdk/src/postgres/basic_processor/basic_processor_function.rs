@@ -1,8 +1,10 @@
 use super::basic_processor_step::BasicProcessorStep;
 use crate::{
+    admin_api,
     aptos_indexer_transaction_stream::TransactionStreamConfig,
-    builder::ProcessorBuilder,
+    builder::{topology, ProcessorBuilder},
     common_steps::{
+        CoalescingConfig, CoalescingStep, ErrorPolicyConfig, ErrorPolicyStep,
         TransactionStreamStep, VersionTrackerStep, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS,
     },
     postgres::{
@@ -11,7 +13,12 @@ use crate::{
             checkpoint::{
                 get_starting_version, PostgresChainIdChecker, PostgresProcessorStatusSaver,
             },
-            database::{new_db_pool, run_migrations, ArcDbPool},
+            database::{run_migrations, ArcDbPool},
+            db_health::{
+                new_db_pool_when_available, DbCircuitBreakerStep, DbHealthConfig, DbHealthMonitor,
+            },
+            dead_letter_store::PostgresDeadLetterStore,
+            scheduled_rewind::{apply_scheduled_rewind, PostgresRewindScheduler},
         },
         SDK_MIGRATIONS,
     },
@@ -20,20 +27,34 @@ use crate::{
         GenericConfig, ServerArgs,
     },
     traits::IntoRunnableStep,
-    utils::{chain_id_check::check_or_update_chain_id, errors::ProcessorError},
+    utils::{
+        chain_id_check::check_or_update_chain_id, errors::ProcessorError, shutdown::ShutdownSignal,
+    },
 };
 use anyhow::Result;
 use aptos_protos::transaction::v1::Transaction;
 use clap::Parser;
 use diesel_migrations::EmbeddedMigrations;
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use std::time::Duration;
+use tracing::{error, info};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct ProcessConfig {
     pub transaction_stream_config: TransactionStreamConfig,
     pub postgres_config: PostgresConfig,
+    /// What to do when `process_function` fails. Skipped batches are recorded in
+    /// `processor_metadata.dead_letter_batches`.
+    #[serde(default)]
+    pub error_policy: ErrorPolicyConfig,
+    /// When to consider the DB down. While it is, the processor pauses instead of failing.
+    #[serde(default)]
+    pub db_health: DbHealthConfig,
+    /// If set, consecutive batches from the transaction stream are merged before being passed to
+    /// `process_function`, so it runs less often on bigger batches.
+    #[serde(default)]
+    pub coalescing: Option<CoalescingConfig>,
 }
 
 /// Processes transactions with a custom handler function.
@@ -50,22 +71,58 @@ where
     setup_logging();
     setup_panic_handler();
     let config = load::<GenericConfig<ProcessConfig>>(&args.config_path)?;
+    process_with_config(
+        processor_name,
+        config,
+        embedded_migrations,
+        process_function,
+    )
+    .await
+}
+
+/// Same as [`process`], for binaries that parse their own arguments and load the config
+/// themselves. Logging and the panic handler are expected to be set up already.
+///
+/// On SIGTERM or SIGINT the processor stops fetching transactions, finishes the batches in
+/// flight, saves its checkpoint and returns, or returns anyway after `shutdown_timeout_secs`.
+pub async fn process_with_config<F, Fut>(
+    processor_name: String,
+    config: GenericConfig<ProcessConfig>,
+    embedded_migrations: EmbeddedMigrations,
+    process_function: F,
+) -> Result<()>
+where
+    F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
+    Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
+{
     let handle = tokio::runtime::Handle::current();
 
     let health_port = config.health_check_port;
+    let health_check_config = config.health_check_config;
+    let admin_config = config.admin_config.clone();
     let additional_labels = config.metrics_config.additional_labels.clone();
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let shutdown_signal = ShutdownSignal::new();
+    shutdown_signal.trigger_on_termination();
     // Start liveness and readiness probes.
     let task_handler = handle.spawn(async move {
-        register_probes_and_metrics_handler(health_port, additional_labels).await;
+        register_probes_and_metrics_handler(
+            health_port,
+            health_check_config,
+            admin_config,
+            additional_labels,
+        )
+        .await;
         anyhow::Ok(())
     });
+    let main_shutdown_signal = shutdown_signal.clone();
     let main_task_handler = handle.spawn(async move {
         run_processor(
             processor_name,
-            config.server_config.transaction_stream_config,
-            config.server_config.postgres_config,
+            config.server_config,
             embedded_migrations,
             process_function,
+            main_shutdown_signal,
         )
         .await
     });
@@ -76,27 +133,46 @@ where
         res = main_task_handler => {
             res.expect("Main task handler unexpectedly exited")
         },
+        _ = shutdown_signal.deadline(shutdown_timeout) => {
+            error!(
+                "Processor did not drain within {}s. Exiting without a final checkpoint.",
+                shutdown_timeout.as_secs()
+            );
+            Ok(())
+        },
     }
 }
 
-async fn run_processor<F, Fut>(
+/// Runs migrations and the processor pipeline without starting the probes and metrics server.
+/// Returns once the transaction stream ends, e.g. when `request_ending_version` is reached, or
+/// once the pipeline has drained after `shutdown_signal` is triggered.
+pub async fn run_processor<F, Fut>(
     processor_name: String,
-    transaction_stream_config: TransactionStreamConfig,
-    postgres_config: PostgresConfig,
+    config: ProcessConfig,
     embedded_migrations: EmbeddedMigrations,
     process_function: F,
+    shutdown_signal: ShutdownSignal,
 ) -> Result<()>
 where
     F: FnMut(Vec<Transaction>, ArcDbPool) -> Fut + Send + Sync + 'static,
     Fut: std::future::Future<Output = Result<(), ProcessorError>> + Send + 'static,
 {
-    // Create a connection pool
-    let db_pool = new_db_pool(
+    let ProcessConfig {
+        transaction_stream_config,
+        postgres_config,
+        error_policy,
+        db_health,
+        coalescing,
+    } = config;
+
+    // Create a connection pool, waiting for the DB if it is down
+    let db_pool = new_db_pool_when_available(
         &postgres_config.connection_string,
         Some(postgres_config.db_pool_size),
+        &db_health,
     )
-    .await
-    .expect("Failed to create connection pool");
+    .await;
+    let db_health_monitor = DbHealthMonitor::new(db_pool.clone(), db_health);
 
     // Run user migrations
     run_migrations(
@@ -120,6 +196,13 @@ where
     )
     .await?;
 
+    // Apply a rewind scheduled through the admin API before the last run stopped
+    apply_scheduled_rewind(processor_name.as_str(), &db_pool).await?;
+    admin_api::register_rewind_scheduler(PostgresRewindScheduler::new(
+        processor_name.as_str(),
+        db_pool.clone(),
+    ));
+
     // Merge the starting version from config and the latest processed version from the DB
     let starting_version = get_starting_version(
         processor_name.as_str(),
@@ -135,21 +218,40 @@ where
         ..transaction_stream_config
     })
     .await?;
-    let basic_processor_step = BasicProcessorStep {
-        process_function,
-        conn_pool: db_pool.clone(),
-    };
+    let basic_processor_step = ErrorPolicyStep::new(
+        DbCircuitBreakerStep::new(
+            BasicProcessorStep {
+                process_function,
+                conn_pool: db_pool.clone(),
+            },
+            db_health_monitor.clone(),
+        ),
+        error_policy,
+        PostgresDeadLetterStore::new(processor_name.as_str(), db_pool.clone()),
+    );
     let processor_status_saver =
         PostgresProcessorStatusSaver::new(processor_name.as_str(), db_pool.clone());
-    let version_tracker =
-        VersionTrackerStep::new(processor_status_saver, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS);
+    let version_tracker = DbCircuitBreakerStep::new(
+        VersionTrackerStep::new(processor_status_saver, DEFAULT_UPDATE_PROCESSOR_STATUS_SECS),
+        db_health_monitor,
+    );
 
     // Connect processor steps together
-    let (_, buffer_receiver) =
+    let builder =
         ProcessorBuilder::new_with_inputless_first_step(transaction_stream.into_runnable_step())
+            .with_shutdown_signal(shutdown_signal);
+    let (processor_builder, buffer_receiver) = match coalescing {
+        Some(coalescing) => builder
+            .connect_to(CoalescingStep::new(coalescing).into_runnable_step(), 10)
             .connect_to(basic_processor_step.into_runnable_step(), 10)
             .connect_to(version_tracker.into_runnable_step(), 10)
-            .end_and_return_output_receiver(10);
+            .end_and_return_output_receiver(10),
+        None => builder
+            .connect_to(basic_processor_step.into_runnable_step(), 10)
+            .connect_to(version_tracker.into_runnable_step(), 10)
+            .end_and_return_output_receiver(10),
+    };
+    topology::register_pipeline(processor_builder.graph);
 
     // (Optional) Parse the results
     loop {
@@ -32,11 +32,8 @@ where
         &mut self,
         transactions: TransactionContext<Vec<Transaction>>,
     ) -> Result<Option<TransactionContext<()>>, ProcessorError> {
-        (self.process_function)(transactions.data, self.conn_pool.clone())
-            .await
-            .map_err(|e| ProcessorError::ProcessError {
-                message: format!("Processing transactionsfailed: {e:?}"),
-            })?;
+        // Errors are passed on as is, so they can be classified, e.g. by an `ErrorPolicyStep`
+        (self.process_function)(transactions.data, self.conn_pool.clone()).await?;
         Ok(Some(TransactionContext {
             data: (), // Stub out data since it's not used in the next step
             metadata: transactions.metadata,
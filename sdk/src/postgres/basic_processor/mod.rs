@@ -1,4 +1,4 @@
 pub mod basic_processor_function;
 pub mod basic_processor_step;
 
-pub use basic_processor_function::process;
+pub use basic_processor_function::{process, process_with_config, run_processor, ProcessConfig};
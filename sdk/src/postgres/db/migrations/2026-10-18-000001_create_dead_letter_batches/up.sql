@@ -0,0 +1,11 @@
+-- Batches a step skipped after failing to process them, to inspect and re-process later
+CREATE TABLE IF NOT EXISTS processor_metadata.dead_letter_batches (
+  id BIGSERIAL PRIMARY KEY,
+  processor VARCHAR(100) NOT NULL,
+  step_name TEXT NOT NULL,
+  start_version BIGINT NOT NULL,
+  end_version BIGINT NOT NULL,
+  error TEXT NOT NULL,
+  inserted_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+CREATE INDEX IF NOT EXISTS dlb_processor_start_version_index ON processor_metadata.dead_letter_batches (processor, start_version);
@@ -0,0 +1,6 @@
+-- Rewinds requested through the admin API, applied when the processor next starts
+CREATE TABLE IF NOT EXISTS processor_metadata.scheduled_rewinds (
+  processor VARCHAR(100) PRIMARY KEY NOT NULL,
+  to_version BIGINT NOT NULL,
+  scheduled_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -1,6 +1,19 @@
 // @generated automatically by Diesel CLI.
 
 pub mod processor_metadata {
+    diesel::table! {
+        processor_metadata.dead_letter_batches (id) {
+            id -> Int8,
+            #[max_length = 100]
+            processor -> Varchar,
+            step_name -> Text,
+            start_version -> Int8,
+            end_version -> Int8,
+            error -> Text,
+            inserted_at -> Timestamp,
+        }
+    }
+
     diesel::table! {
         processor_metadata.ledger_infos (chain_id) {
             chain_id -> Int8,
@@ -17,5 +30,19 @@ pub mod processor_metadata {
         }
     }
 
-    diesel::allow_tables_to_appear_in_same_query!(ledger_infos, processor_status,);
+    diesel::table! {
+        processor_metadata.scheduled_rewinds (processor) {
+            #[max_length = 100]
+            processor -> Varchar,
+            to_version -> Int8,
+            scheduled_at -> Timestamp,
+        }
+    }
+
+    diesel::allow_tables_to_appear_in_same_query!(
+        dead_letter_batches,
+        ledger_infos,
+        processor_status,
+        scheduled_rewinds,
+    );
 }
@@ -0,0 +1,18 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::postgres::processor_metadata_schema::processor_metadata::dead_letter_batches;
+use diesel::Insertable;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = dead_letter_batches)]
+/// A batch a step skipped after failing to process it
+pub struct NewDeadLetterBatch {
+    pub processor: String,
+    pub step_name: String,
+    pub start_version: i64,
+    pub end_version: i64,
+    pub error: String,
+}
@@ -1,2 +1,4 @@
+pub mod dead_letter_batch;
 pub mod ledger_info;
 pub mod processor_status;
+pub mod scheduled_rewind;
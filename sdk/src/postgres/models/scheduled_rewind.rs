@@ -0,0 +1,40 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+#![allow(clippy::extra_unused_lifetimes)]
+
+use crate::postgres::{
+    processor_metadata_schema::processor_metadata::scheduled_rewinds,
+    utils::database::DbPoolConnection,
+};
+use diesel::{ExpressionMethods, Insertable, OptionalExtension, QueryDsl, Queryable};
+use diesel_async::RunQueryDsl;
+
+#[derive(Debug, Insertable)]
+#[diesel(table_name = scheduled_rewinds)]
+/// A rewind to apply when the processor next starts
+pub struct NewScheduledRewind {
+    pub processor: String,
+    pub to_version: i64,
+}
+
+#[derive(Debug, Queryable)]
+#[diesel(table_name = scheduled_rewinds)]
+pub struct ScheduledRewindQuery {
+    pub processor: String,
+    pub to_version: i64,
+    pub scheduled_at: chrono::NaiveDateTime,
+}
+
+impl ScheduledRewindQuery {
+    pub async fn get_by_processor(
+        processor_name: &str,
+        conn: &mut DbPoolConnection<'_>,
+    ) -> diesel::QueryResult<Option<Self>> {
+        scheduled_rewinds::table
+            .filter(scheduled_rewinds::processor.eq(processor_name))
+            .first::<Self>(conn)
+            .await
+            .optional()
+    }
+}
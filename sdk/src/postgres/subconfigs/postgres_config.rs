@@ -1,3 +1,4 @@
+use ahash::AHashMap;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -7,6 +8,9 @@ pub struct PostgresConfig {
     // Size of the pool for writes/reads to the DB. Limits maximum number of queries in flight
     #[serde(default = "PostgresConfig::default_db_pool_size")]
     pub db_pool_size: u32,
+    // Rows written per insert for each table, overriding the default from the column count
+    #[serde(default)]
+    pub per_table_chunk_sizes: AHashMap<String, usize>,
 }
 
 impl PostgresConfig {
@@ -10,7 +10,7 @@ use crate::{
         processor_metadata_schema::processor_metadata::{ledger_infos, processor_status},
     },
     types::transaction_context::TransactionContext,
-    utils::{chain_id_check::ChainIdChecker, errors::ProcessorError},
+    utils::{chain_id_check::ChainIdChecker, errors::ProcessorError, health},
 };
 use anyhow::{Context, Result};
 use async_trait::async_trait;
@@ -111,6 +111,7 @@ impl ProcessorStatusSaver for PostgresProcessorStatusSaver {
                 ),
         )
         .await?;
+        health::record_checkpoint(last_transaction_timestamp);
         Ok(())
     }
 }
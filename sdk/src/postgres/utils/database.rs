@@ -4,12 +4,17 @@
 //! Database-related functions
 #![allow(clippy::extra_unused_lifetimes)]
 
-use crate::utils::{convert::remove_null_bytes, errors::ProcessorError};
+use crate::utils::{
+    convert::remove_null_bytes,
+    errors::{truncate_query, ProcessorError},
+};
 use ahash::AHashMap;
-use diesel::{query_builder::QueryFragment, ConnectionResult, QueryResult};
+use diesel::{
+    query_builder::QueryFragment, result::DatabaseErrorKind, ConnectionResult, QueryResult,
+};
 use diesel_async::{
     pooled_connection::{
-        bb8::{Pool, PooledConnection},
+        bb8::{Pool, PooledConnection, RunError},
         AsyncDieselConnectionManager, ManagerConfig, PoolError,
     },
     AsyncPgConnection, RunQueryDsl,
@@ -161,13 +166,10 @@ pub async fn execute_with_better_error<U>(
 where
     U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
 {
-    let debug_string = diesel::debug_query::<Backend, _>(&query).to_string();
+    let debug_string = truncate_query(diesel::debug_query::<Backend, _>(&query).to_string());
     let conn = &mut pool.get().await.map_err(|e| {
         warn!("Error getting connection from pool: {:?}", e);
-        ProcessorError::DBStoreError {
-            message: format!("{e:#}"),
-            query: Some(debug_string.clone()),
-        }
+        classify_pool_error(e)
     })?;
     query
         .execute(conn)
@@ -175,10 +177,69 @@ where
         .inspect_err(|e| {
             warn!("Error running query: {:?}\n{:?}", e, debug_string);
         })
-        .map_err(|e| ProcessorError::DBStoreError {
-            message: format!("{e:#}"),
-            query: Some(debug_string),
-        })
+        .map_err(|e| classify_query_error(e, debug_string))
+}
+
+/// Maps an error getting a connection from the pool to a `ProcessorError`.
+pub fn classify_pool_error(error: RunError) -> ProcessorError {
+    let message = format!("{error:#}");
+    match error {
+        RunError::TimedOut => ProcessorError::Timeout {
+            message,
+            source: Some(error.into()),
+        },
+        RunError::User(_) => ProcessorError::ConnectionError {
+            message,
+            source: Some(error.into()),
+        },
+    }
+}
+
+/// Maps a failed query to the `ProcessorError` variant for its cause. `query` should already be
+/// truncated, see `truncate_query`.
+pub fn classify_query_error(error: diesel::result::Error, query: String) -> ProcessorError {
+    let message = format!("{error:#}");
+    match &error {
+        diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::UniqueViolation
+            | DatabaseErrorKind::ForeignKeyViolation
+            | DatabaseErrorKind::NotNullViolation
+            | DatabaseErrorKind::CheckViolation,
+            info,
+        ) => ProcessorError::ConstraintViolation {
+            constraint: info.constraint_name().map(str::to_string),
+            message,
+            query: Some(query),
+            source: Some(error.into()),
+        },
+        diesel::result::Error::DatabaseError(
+            DatabaseErrorKind::ClosedConnection | DatabaseErrorKind::UnableToSendCommand,
+            _,
+        )
+        | diesel::result::Error::BrokenTransactionManager => ProcessorError::ConnectionError {
+            message,
+            source: Some(error.into()),
+        },
+        // Postgres reports `statement_timeout` as a plain query cancellation
+        diesel::result::Error::DatabaseError(_, info)
+            if info.message().contains("statement timeout") =>
+        {
+            ProcessorError::Timeout {
+                message,
+                source: Some(error.into()),
+            }
+        },
+        diesel::result::Error::SerializationError(_)
+        | diesel::result::Error::DeserializationError(_) => ProcessorError::SerializationError {
+            message,
+            source: Some(error.into()),
+        },
+        _ => ProcessorError::DBStoreError {
+            message,
+            query: Some(query),
+            source: Some(error.into()),
+        },
+    }
 }
 
 pub async fn execute_with_better_error_conn<U>(
@@ -188,7 +249,7 @@ pub async fn execute_with_better_error_conn<U>(
 where
     U: QueryFragment<Backend> + diesel::query_builder::QueryId + Send,
 {
-    let debug_string = diesel::debug_query::<Backend, _>(&query).to_string();
+    let debug_string = truncate_query(diesel::debug_query::<Backend, _>(&query).to_string());
     tracing::debug!("Executing query: {:?}", debug_string);
     let res = query.execute(conn).await;
     if let Err(ref e) = res {
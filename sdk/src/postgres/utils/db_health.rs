@@ -0,0 +1,521 @@
+//! Pausing a processor while Postgres is unreachable.
+//!
+//! A [`DbHealthMonitor`] counts connection failures on a pool. Once `failure_threshold` of them
+//! happen in a row it opens its circuit: the DB is reported unavailable to the readiness probe and
+//! probed every `probe_interval_ms` until it answers again. The circuit is then half-open: batches
+//! go through again, but the first connection failure reopens it, and the first success closes
+//! it. Steps wrapped in a [`DbCircuitBreakerStep`] wait while the circuit is open instead of
+//! failing, so batches back up in the channels in front of them and the transaction stream stops
+//! being polled until the DB is back.
+
+use crate::{
+    postgres::utils::database::{
+        classify_pool_error, classify_query_error, new_db_pool, ArcDbPool,
+    },
+    traits::{AsyncStep, NamedStep, PollableAsyncStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::{errors::ProcessorError, health},
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use diesel_async::RunQueryDsl;
+use futures_util::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+const PROBE_QUERY: &str = "SELECT 1";
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DbHealthConfig {
+    /// Connection failures in a row after which the DB is considered down.
+    pub failure_threshold: u32,
+    /// How often to check whether the DB is back while it is down.
+    pub probe_interval_ms: u64,
+    /// Attempts at a batch that fails with connection failures before its error is passed on.
+    /// Time spent waiting for the DB to come back doesn't count.
+    pub max_batch_attempts: u32,
+}
+
+impl Default for DbHealthConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            probe_interval_ms: 5_000,
+            max_batch_attempts: 10,
+        }
+    }
+}
+
+impl DbHealthConfig {
+    fn probe_interval(&self) -> Duration {
+        Duration::from_millis(self.probe_interval_ms)
+    }
+}
+
+/// Checks that a connection can be taken from `pool` and used.
+pub async fn probe_db(pool: &ArcDbPool) -> Result<(), ProcessorError> {
+    let mut conn = pool.get().await.map_err(classify_pool_error)?;
+    diesel::sql_query(PROBE_QUERY)
+        .execute(&mut conn)
+        .await
+        .map_err(|e| classify_query_error(e, PROBE_QUERY.to_string()))?;
+    Ok(())
+}
+
+/// Creates a pool for `database_url` once the DB can be reached. Until then the DB is reported
+/// unavailable and retried every `probe_interval_ms`, rather than failing the processor.
+pub async fn new_db_pool_when_available(
+    database_url: &str,
+    max_pool_size: Option<u32>,
+    config: &DbHealthConfig,
+) -> ArcDbPool {
+    loop {
+        let result = match new_db_pool(database_url, max_pool_size).await {
+            Ok(pool) => probe_db(&pool).await.map(|()| pool),
+            Err(e) => Err(ProcessorError::ConnectionError {
+                message: format!("{e:#}"),
+                source: Some(e.into()),
+            }),
+        };
+        match result {
+            Ok(pool) => {
+                health::set_db_available(true);
+                return pool;
+            },
+            Err(e) => {
+                warn!(
+                    error = e.to_string(),
+                    "Database is unavailable. Retrying in {}ms.", config.probe_interval_ms
+                );
+                health::set_db_available(false);
+                tokio::time::sleep(config.probe_interval()).await;
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+    /// The DB is up.
+    Closed,
+    /// The DB is down. Nothing is written until a probe gets an answer.
+    Open,
+    /// A probe got an answer after the DB was down. The next write decides whether it is up.
+    HalfOpen,
+}
+
+/// Checks whether the DB is up.
+type Probe = Arc<dyn Fn() -> BoxFuture<'static, Result<(), ProcessorError>> + Send + Sync>;
+
+/// Tracks whether the DB behind a pool is up. Clones share the same state.
+#[derive(Clone)]
+pub struct DbHealthMonitor {
+    probe: Probe,
+    config: DbHealthConfig,
+    consecutive_failures: Arc<AtomicU32>,
+    state: Arc<watch::Sender<CircuitState>>,
+}
+
+impl DbHealthMonitor {
+    pub fn new(pool: ArcDbPool, config: DbHealthConfig) -> Self {
+        Self::with_probe(config, move || {
+            let pool = pool.clone();
+            Box::pin(async move { probe_db(&pool).await })
+        })
+    }
+
+    fn with_probe(
+        config: DbHealthConfig,
+        probe: impl Fn() -> BoxFuture<'static, Result<(), ProcessorError>> + Send + Sync + 'static,
+    ) -> Self {
+        let (state, _) = watch::channel(CircuitState::Closed);
+        Self {
+            probe: Arc::new(probe),
+            config,
+            consecutive_failures: Arc::new(AtomicU32::new(0)),
+            state: Arc::new(state),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        *self.state.borrow()
+    }
+
+    pub fn is_available(&self) -> bool {
+        self.state() != CircuitState::Open
+    }
+
+    /// Resolves once the circuit is no longer open, right away if it isn't.
+    pub async fn wait_until_available(&self) {
+        let mut receiver = self.state.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = receiver
+            .wait_for(|state| *state != CircuitState::Open)
+            .await;
+    }
+
+    pub fn record_success(&self) {
+        self.consecutive_failures.store(0, Ordering::Relaxed);
+        let was_half_open = self.state.send_if_modified(|state| {
+            let was_half_open = *state == CircuitState::HalfOpen;
+            if was_half_open {
+                *state = CircuitState::Closed;
+            }
+            was_half_open
+        });
+        if was_half_open {
+            info!("Database is available again.");
+        }
+    }
+
+    /// Counts `error` if it is a connection failure, and opens the circuit once there have been
+    /// `failure_threshold` of them in a row, or right away if it is half-open. Other errors mean
+    /// the DB is up, so they reset the count.
+    pub fn record_failure(&self, error: &ProcessorError) {
+        if !error.is_connection_failure() {
+            self.record_success();
+            return;
+        }
+        let failures = self.consecutive_failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if failures < self.config.failure_threshold && self.state() != CircuitState::HalfOpen {
+            return;
+        }
+        let previous = self.state.send_replace(CircuitState::Open);
+        if previous != CircuitState::Open {
+            warn!(
+                failures,
+                error = error.to_string(),
+                "Database is unavailable. Pausing until it is back."
+            );
+            health::set_db_available(false);
+            tokio::spawn(self.clone().probe_until_available());
+        }
+    }
+
+    async fn probe_until_available(self) {
+        loop {
+            tokio::time::sleep(self.config.probe_interval()).await;
+            match (self.probe)().await {
+                Ok(()) => break,
+                Err(e) => warn!(error = e.to_string(), "Database is still unavailable."),
+            }
+        }
+        info!("Database answered again. Resuming.");
+        health::set_db_available(true);
+        self.state.send_replace(CircuitState::HalfOpen);
+    }
+}
+
+/// Wraps a step that writes to the DB, so that it waits for the DB instead of failing while the
+/// DB is down. Batches that fail with a connection failure are retried once the DB is back, up
+/// to `max_batch_attempts` attempts; other errors are passed on. The step must be safe to
+/// retry, since a failed attempt may have written part of the batch. Polls that fail with a
+/// connection failure are skipped, since the next poll tries again anyway.
+///
+/// Retrying needs a copy of the batch, so the step's input must be `Clone`.
+pub struct DbCircuitBreakerStep<Step>
+where
+    Step: Processable,
+{
+    pub step: Step,
+    pub monitor: DbHealthMonitor,
+}
+
+impl<Step> DbCircuitBreakerStep<Step>
+where
+    Step: Processable,
+{
+    pub fn new(step: Step, monitor: DbHealthMonitor) -> Self {
+        Self { step, monitor }
+    }
+}
+
+#[async_trait]
+impl<Step> Processable for DbCircuitBreakerStep<Step>
+where
+    Step: Processable,
+    Step::Input: Clone,
+{
+    type Input = Step::Input;
+    type Output = Step::Output;
+    type RunType = Step::RunType;
+
+    async fn init(&mut self) {
+        self.step.init().await;
+    }
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<Step::Input>,
+    ) -> Result<Option<TransactionContext<Step::Output>>, ProcessorError> {
+        let max_attempts = self.monitor.config.max_batch_attempts.max(1);
+        let metadata = item.metadata.clone();
+        let mut item = Some(item);
+        let mut attempts = 0;
+        loop {
+            self.monitor.wait_until_available().await;
+            attempts += 1;
+            // Only keep a copy of the batch while it may be retried
+            let input = if attempts < max_attempts {
+                item.clone()
+            } else {
+                item.take()
+            };
+            match self.step.process(input.unwrap()).await {
+                Ok(output) => {
+                    self.monitor.record_success();
+                    return Ok(output);
+                },
+                Err(e) if e.is_connection_failure() && attempts >= max_attempts => {
+                    warn!(
+                        step_name = self.name(),
+                        start_version = metadata.start_version,
+                        end_version = metadata.end_version,
+                        error = e.to_string(),
+                        "Failed to reach the database {attempts} times. Giving up on the batch."
+                    );
+                    self.monitor.record_failure(&e);
+                    return Err(e);
+                },
+                Err(e) if e.is_connection_failure() => {
+                    warn!(
+                        step_name = self.name(),
+                        start_version = metadata.start_version,
+                        end_version = metadata.end_version,
+                        error = e.to_string(),
+                        "Failed to reach the database. Retrying the batch."
+                    );
+                    self.monitor.record_failure(&e);
+                    if self.monitor.is_available() {
+                        tokio::time::sleep(self.monitor.config.probe_interval()).await;
+                    }
+                },
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn cleanup(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.step.cleanup().await
+    }
+}
+
+impl<Step> AsyncStep for DbCircuitBreakerStep<Step>
+where
+    Step: AsyncStep,
+    Step::Input: Clone,
+{
+}
+
+#[async_trait]
+impl<Step> PollableAsyncStep for DbCircuitBreakerStep<Step>
+where
+    Step: PollableAsyncStep,
+    Step::Input: Clone,
+{
+    fn poll_interval(&self) -> Duration {
+        self.step.poll_interval()
+    }
+
+    async fn poll(
+        &mut self,
+    ) -> Result<Option<Vec<TransactionContext<Self::Output>>>, ProcessorError> {
+        self.monitor.wait_until_available().await;
+        match self.step.poll().await {
+            Ok(output) => {
+                self.monitor.record_success();
+                Ok(output)
+            },
+            Err(e) if e.is_connection_failure() => {
+                warn!(
+                    step_name = self.name(),
+                    error = e.to_string(),
+                    "Failed to reach the database. Skipping this poll."
+                );
+                self.monitor.record_failure(&e);
+                Ok(None)
+            },
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn should_continue_polling(&mut self) -> bool {
+        self.step.should_continue_polling().await
+    }
+}
+
+impl<Step> NamedStep for DbCircuitBreakerStep<Step>
+where
+    Step: Processable,
+{
+    fn name(&self) -> String {
+        self.step.name()
+    }
+
+    fn type_name(&self) -> String {
+        format!(
+            "{} (via DbCircuitBreakerStep)",
+            std::any::type_name::<Step>()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traits::AsyncRunType;
+    use std::sync::atomic::AtomicBool;
+
+    fn connection_error() -> ProcessorError {
+        ProcessorError::ConnectionError {
+            message: "connection refused".to_string(),
+            source: None,
+        }
+    }
+
+    /// A monitor whose probe succeeds once `db_up` is set.
+    fn make_monitor(db_up: Arc<AtomicBool>) -> DbHealthMonitor {
+        let config = DbHealthConfig {
+            failure_threshold: 2,
+            probe_interval_ms: 5,
+            max_batch_attempts: 3,
+        };
+        DbHealthMonitor::with_probe(config, move || {
+            let db_up = AtomicBool::load(&db_up, Ordering::Relaxed);
+            Box::pin(async move {
+                if db_up {
+                    Ok(())
+                } else {
+                    Err(connection_error())
+                }
+            })
+        })
+    }
+
+    async fn wait_for_half_open(monitor: &DbHealthMonitor) {
+        tokio::time::timeout(Duration::from_secs(1), monitor.wait_until_available())
+            .await
+            .unwrap();
+        assert_eq!(monitor.state(), CircuitState::HalfOpen);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_circuit_transitions() {
+        let _guard = health::TEST_LOCK.lock().await;
+        let db_up = Arc::new(AtomicBool::new(false));
+        let monitor = make_monitor(db_up.clone());
+        assert_eq!(monitor.state(), CircuitState::Closed);
+
+        // Other errors mean the DB is up, so they break a run of connection failures
+        monitor.record_failure(&connection_error());
+        monitor.record_failure(&ProcessorError::ProcessError {
+            message: "bad data".to_string(),
+        });
+        monitor.record_failure(&connection_error());
+        assert_eq!(monitor.state(), CircuitState::Closed);
+
+        monitor.record_failure(&connection_error());
+        assert_eq!(monitor.state(), CircuitState::Open);
+        assert!(!monitor.is_available());
+        assert!(!health::is_db_available());
+
+        // Stays open while the probe fails
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(monitor.state(), CircuitState::Open);
+
+        db_up.store(true, Ordering::Relaxed);
+        wait_for_half_open(&monitor).await;
+        assert!(health::is_db_available());
+
+        // A single failure while half-open reopens the circuit
+        monitor.record_failure(&connection_error());
+        assert_eq!(monitor.state(), CircuitState::Open);
+        wait_for_half_open(&monitor).await;
+
+        monitor.record_success();
+        assert_eq!(monitor.state(), CircuitState::Closed);
+        monitor.record_failure(&connection_error());
+        assert_eq!(monitor.state(), CircuitState::Closed);
+    }
+
+    /// Fails with a connection failure until `failures_left` runs out.
+    struct FlakyStep {
+        failures_left: u32,
+        attempts: u32,
+    }
+
+    #[async_trait]
+    impl Processable for FlakyStep {
+        type Input = u64;
+        type Output = u64;
+        type RunType = AsyncRunType;
+
+        async fn process(
+            &mut self,
+            item: TransactionContext<u64>,
+        ) -> Result<Option<TransactionContext<u64>>, ProcessorError> {
+            self.attempts += 1;
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(connection_error());
+            }
+            Ok(Some(item))
+        }
+    }
+
+    impl AsyncStep for FlakyStep {}
+
+    impl NamedStep for FlakyStep {
+        fn name(&self) -> String {
+            "FlakyStep".to_string()
+        }
+    }
+
+    fn make_context() -> TransactionContext<u64> {
+        TransactionContext {
+            data: 7,
+            metadata: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_breaker_retries_up_to_max_attempts() {
+        let _guard = health::TEST_LOCK.lock().await;
+        let db_up = Arc::new(AtomicBool::new(true));
+
+        let step = FlakyStep {
+            failures_left: 2,
+            attempts: 0,
+        };
+        let mut breaker = DbCircuitBreakerStep::new(step, make_monitor(db_up.clone()));
+        let output = breaker.process(make_context()).await.unwrap().unwrap();
+        assert_eq!(output.data, 7);
+        assert_eq!(breaker.step.attempts, 3);
+        assert_eq!(breaker.monitor.state(), CircuitState::Closed);
+
+        let step = FlakyStep {
+            failures_left: u32::MAX,
+            attempts: 0,
+        };
+        let mut breaker = DbCircuitBreakerStep::new(step, make_monitor(db_up));
+        let error = breaker.process(make_context()).await.err().unwrap();
+        assert!(error.is_connection_failure());
+        assert_eq!(breaker.step.attempts, 3);
+
+        // Let the probe close the circuit again, so other tests see the DB as available
+        wait_for_half_open(&breaker.monitor).await;
+    }
+}
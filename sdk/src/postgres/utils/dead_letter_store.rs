@@ -0,0 +1,51 @@
+use super::database::{execute_with_better_error, ArcDbPool};
+use crate::{
+    common_steps::DeadLetterStore,
+    postgres::{
+        models::dead_letter_batch::NewDeadLetterBatch,
+        processor_metadata_schema::processor_metadata::dead_letter_batches,
+    },
+    types::transaction_context::TransactionMetadata,
+    utils::errors::ProcessorError,
+};
+use async_trait::async_trait;
+
+/// A trait implementation of DeadLetterStore for Postgres. Skipped batches are recorded in
+/// `processor_metadata.dead_letter_batches`.
+pub struct PostgresDeadLetterStore {
+    pub db_pool: ArcDbPool,
+    pub processor_name: String,
+}
+
+impl PostgresDeadLetterStore {
+    pub fn new(processor_name: &str, db_pool: ArcDbPool) -> Self {
+        Self {
+            db_pool,
+            processor_name: processor_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl DeadLetterStore for PostgresDeadLetterStore {
+    async fn record_skipped_batch(
+        &self,
+        step_name: &str,
+        metadata: &TransactionMetadata,
+        error: &ProcessorError,
+    ) -> Result<(), ProcessorError> {
+        let batch = NewDeadLetterBatch {
+            processor: self.processor_name.clone(),
+            step_name: step_name.to_string(),
+            start_version: metadata.start_version as i64,
+            end_version: metadata.end_version as i64,
+            error: error.to_string(),
+        };
+        execute_with_better_error(
+            self.db_pool.clone(),
+            diesel::insert_into(dead_letter_batches::table).values(batch),
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -1,2 +1,6 @@
 pub mod checkpoint;
 pub mod database;
+pub mod db_health;
+pub mod dead_letter_store;
+pub mod scheduled_rewind;
+pub mod writer;
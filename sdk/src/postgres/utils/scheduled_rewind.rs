@@ -0,0 +1,212 @@
+use super::database::{execute_with_better_error, ArcDbPool};
+use crate::{
+    admin_api::RewindScheduler,
+    postgres::{
+        models::scheduled_rewind::{NewScheduledRewind, ScheduledRewindQuery},
+        processor_metadata_schema::processor_metadata::{processor_status, scheduled_rewinds},
+    },
+};
+use anyhow::Result;
+use async_trait::async_trait;
+use diesel::{query_dsl::methods::FilterDsl, upsert::excluded, ExpressionMethods};
+use tracing::{info, warn};
+
+/// A trait implementation of RewindScheduler for Postgres. Scheduled rewinds are stored in
+/// `processor_metadata.scheduled_rewinds`, one per processor, replacing any earlier one.
+pub struct PostgresRewindScheduler {
+    pub db_pool: ArcDbPool,
+    pub processor_name: String,
+}
+
+impl PostgresRewindScheduler {
+    pub fn new(processor_name: &str, db_pool: ArcDbPool) -> Self {
+        Self {
+            db_pool,
+            processor_name: processor_name.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl RewindScheduler for PostgresRewindScheduler {
+    async fn schedule_rewind(&self, to_version: u64) -> Result<()> {
+        let rewind = NewScheduledRewind {
+            processor: self.processor_name.clone(),
+            to_version: to_version as i64,
+        };
+        execute_with_better_error(
+            self.db_pool.clone(),
+            diesel::insert_into(scheduled_rewinds::table)
+                .values(rewind)
+                .on_conflict(scheduled_rewinds::processor)
+                .do_update()
+                .set((
+                    scheduled_rewinds::to_version.eq(excluded(scheduled_rewinds::to_version)),
+                    scheduled_rewinds::scheduled_at.eq(diesel::dsl::now),
+                )),
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+/// The version of the rewind scheduled for `processor_name`, if any.
+pub async fn get_scheduled_rewind(
+    processor_name: &str,
+    conn_pool: &ArcDbPool,
+) -> Result<Option<u64>> {
+    let mut conn = conn_pool.get().await?;
+    let rewind = ScheduledRewindQuery::get_by_processor(processor_name, &mut conn).await?;
+    Ok(rewind.map(|rewind| rewind.to_version as u64))
+}
+
+pub async fn clear_scheduled_rewind(processor_name: &str, conn_pool: &ArcDbPool) -> Result<()> {
+    execute_with_better_error(
+        conn_pool.clone(),
+        diesel::delete(
+            scheduled_rewinds::table.filter(scheduled_rewinds::processor.eq(processor_name)),
+        ),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Applies the rewind scheduled for `processor_name`, if any, by moving its checkpoint back to
+/// the scheduled version, and returns that version. Data written above the version is left as
+/// is, so processors whose writes aren't idempotent should apply the rewind themselves first,
+/// with `get_scheduled_rewind` and `clear_scheduled_rewind`.
+pub async fn apply_scheduled_rewind(
+    processor_name: &str,
+    conn_pool: &ArcDbPool,
+) -> Result<Option<u64>> {
+    let Some(to_version) = get_scheduled_rewind(processor_name, conn_pool).await? else {
+        return Ok(None);
+    };
+    // Only ever move the checkpoint back
+    let rewound = execute_with_better_error(
+        conn_pool.clone(),
+        diesel::update(
+            processor_status::table
+                .filter(processor_status::processor.eq(processor_name))
+                .filter(processor_status::last_success_version.gt(to_version as i64)),
+        )
+        .set(processor_status::last_success_version.eq(to_version as i64)),
+    )
+    .await?;
+    clear_scheduled_rewind(processor_name, conn_pool).await?;
+    if rewound > 0 {
+        info!(to_version, "Applied the scheduled rewind");
+    } else {
+        warn!(
+            to_version,
+            "Dropped the scheduled rewind, the checkpoint is not above its version"
+        );
+    }
+    Ok(Some(to_version))
+}
+
+#[cfg(all(test, feature = "testing_framework"))]
+mod tests {
+    use super::*;
+    use crate::{
+        postgres::{
+            models::processor_status::ProcessorStatusQuery,
+            utils::database::{new_db_pool, run_migrations},
+            SDK_MIGRATIONS,
+        },
+        testing_framework::database::{PostgresTestDatabase, TestDatabase},
+    };
+
+    const PROCESSOR: &str = "test_processor";
+
+    async fn setup() -> (PostgresTestDatabase, ArcDbPool) {
+        let mut database = PostgresTestDatabase::new();
+        database.setup().await.unwrap();
+        let pool = new_db_pool(&database.get_db_url(), Some(2)).await.unwrap();
+        run_migrations(database.get_db_url(), pool.clone(), SDK_MIGRATIONS).await;
+        (database, pool)
+    }
+
+    async fn set_checkpoint(pool: &ArcDbPool, version: i64) {
+        execute_with_better_error(
+            pool.clone(),
+            diesel::insert_into(processor_status::table)
+                .values((
+                    processor_status::processor.eq(PROCESSOR),
+                    processor_status::last_success_version.eq(version),
+                ))
+                .on_conflict(processor_status::processor)
+                .do_update()
+                .set(processor_status::last_success_version.eq(version)),
+        )
+        .await
+        .unwrap();
+    }
+
+    async fn checkpoint(pool: &ArcDbPool) -> i64 {
+        let mut conn = pool.get().await.unwrap();
+        ProcessorStatusQuery::get_by_processor(PROCESSOR, &mut conn)
+            .await
+            .unwrap()
+            .unwrap()
+            .last_success_version
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_schedule_replaces_earlier_rewind() {
+        let (_database, pool) = setup().await;
+        let scheduler = PostgresRewindScheduler::new(PROCESSOR, pool.clone());
+        assert_eq!(get_scheduled_rewind(PROCESSOR, &pool).await.unwrap(), None);
+
+        scheduler.schedule_rewind(100).await.unwrap();
+        scheduler.schedule_rewind(50).await.unwrap();
+        assert_eq!(
+            get_scheduled_rewind(PROCESSOR, &pool).await.unwrap(),
+            Some(50)
+        );
+        // Other processors have their own
+        assert_eq!(get_scheduled_rewind("other", &pool).await.unwrap(), None);
+
+        clear_scheduled_rewind(PROCESSOR, &pool).await.unwrap();
+        assert_eq!(get_scheduled_rewind(PROCESSOR, &pool).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_apply_scheduled_rewind() {
+        let (_database, pool) = setup().await;
+        let scheduler = PostgresRewindScheduler::new(PROCESSOR, pool.clone());
+        set_checkpoint(&pool, 200).await;
+        assert_eq!(
+            apply_scheduled_rewind(PROCESSOR, &pool).await.unwrap(),
+            None
+        );
+        assert_eq!(checkpoint(&pool).await, 200);
+
+        scheduler.schedule_rewind(100).await.unwrap();
+        assert_eq!(
+            apply_scheduled_rewind(PROCESSOR, &pool).await.unwrap(),
+            Some(100)
+        );
+        assert_eq!(checkpoint(&pool).await, 100);
+        assert_eq!(get_scheduled_rewind(PROCESSOR, &pool).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_apply_scheduled_rewind_above_checkpoint() {
+        let (_database, pool) = setup().await;
+        let scheduler = PostgresRewindScheduler::new(PROCESSOR, pool.clone());
+        set_checkpoint(&pool, 50).await;
+
+        // The checkpoint never moves forward, but the rewind is still cleared
+        scheduler.schedule_rewind(100).await.unwrap();
+        assert_eq!(
+            apply_scheduled_rewind(PROCESSOR, &pool).await.unwrap(),
+            Some(100)
+        );
+        assert_eq!(checkpoint(&pool).await, 50);
+        assert_eq!(get_scheduled_rewind(PROCESSOR, &pool).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,683 @@
+//! Declarative writes of typed rows to Postgres.
+//!
+//! A model implements [`PostgresWritable`] to declare its table, the columns of the unique
+//! constraint its rows conflict on and a [`WriteStrategy`] for those conflicts. Rows for several
+//! tables are collected in a [`RowBundle`] and written with [`write_bundle`], or with a
+//! [`PostgresWriterStep`] in a pipeline. Tables are written after the tables they depend on, and
+//! each table is written in chunks of `get_config_table_chunk_size` rows, which can be
+//! overridden per table with `per_table_chunk_sizes`.
+
+use crate::{
+    postgres::utils::database::{
+        execute_in_chunks, get_config_table_chunk_size, ArcDbPool, Backend,
+    },
+    traits::{AsyncRunType, AsyncStep, NamedStep, Processable},
+    types::transaction_context::TransactionContext,
+    utils::errors::ProcessorError,
+};
+use ahash::AHashMap;
+use anyhow::Result;
+use async_trait::async_trait;
+use diesel::{
+    debug_query,
+    query_builder::{AstPass, QueryFragment, QueryId},
+    QueryResult,
+};
+use field_count::FieldCount;
+use futures_util::future::{try_join_all, BoxFuture};
+use std::{any::TypeId, sync::Mutex};
+use tracing::info;
+
+/// The plain `INSERT` of a batch of rows, i.e. `diesel::insert_into(table).values(rows)`, boxed.
+pub type BoxedInsert = Box<dyn QueryFragment<Backend> + Send>;
+
+/// What to do with a row that conflicts with an existing one on `CONFLICT_TARGET`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WriteStrategy {
+    /// Keep the existing row.
+    InsertIgnore,
+    /// Overwrite every column of the existing row, except the conflict target and
+    /// `PRESERVED_COLUMNS`.
+    UpsertAllColumns,
+    /// Like `UpsertAllColumns`, unless the existing row has a higher `version_column`. Rewriting
+    /// the same version overwrites, so reprocessing a batch is idempotent.
+    UpsertIfNewerVersion { version_column: &'static str },
+}
+
+/// A model that `write_bundle` can write to its table.
+///
+/// ```ignore
+/// impl PostgresWritable for Market {
+///     const TABLE_NAME: &'static str = "markets";
+///     const COLUMNS: &'static [&'static str] = &["market_id", "question", "end_time"];
+///     const CONFLICT_TARGET: &'static [&'static str] = &["market_id"];
+///     const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+///
+///     fn insert(rows: Vec<Self>) -> BoxedInsert {
+///         Box::new(diesel::insert_into(schema::markets::table).values(rows))
+///     }
+/// }
+/// ```
+pub trait PostgresWritable:
+    FieldCount + serde::Serialize + for<'de> serde::Deserialize<'de> + Clone + Send + Sync + 'static
+{
+    /// The table the rows go to. Also the key for the table in `per_table_chunk_sizes`.
+    const TABLE_NAME: &'static str;
+    /// The columns `insert` writes, in any order. Upserts overwrite these. Checked against the
+    /// insert on the first write of the model.
+    const COLUMNS: &'static [&'static str];
+    /// The columns of the unique constraint conflicts are detected on. Upserts need one;
+    /// `InsertIgnore` without one ignores conflicts on any constraint.
+    const CONFLICT_TARGET: &'static [&'static str];
+    const STRATEGY: WriteStrategy;
+    /// Columns upserts keep from the existing row, e.g. when it was first inserted.
+    const PRESERVED_COLUMNS: &'static [&'static str] = &[];
+    /// Tables that must be written before this one, e.g. the ones its foreign keys reference.
+    const DEPENDS_ON: &'static [&'static str] = &[];
+
+    /// The plain insert of `rows`. The `ON CONFLICT` clause is added from `CONFLICT_TARGET` and
+    /// `STRATEGY`.
+    fn insert(rows: Vec<Self>) -> BoxedInsert;
+}
+
+/// An insert followed by the `ON CONFLICT` clause for its model's strategy.
+pub struct WriteQuery {
+    insert: BoxedInsert,
+    table_name: &'static str,
+    columns: &'static [&'static str],
+    conflict_target: &'static [&'static str],
+    preserved_columns: &'static [&'static str],
+    strategy: WriteStrategy,
+}
+
+impl WriteQuery {
+    pub fn new<T: PostgresWritable>(rows: Vec<T>) -> Self {
+        Self {
+            insert: T::insert(rows),
+            table_name: T::TABLE_NAME,
+            columns: T::COLUMNS,
+            conflict_target: T::CONFLICT_TARGET,
+            preserved_columns: T::PRESERVED_COLUMNS,
+            strategy: T::STRATEGY,
+        }
+    }
+
+    /// Checks that the columns match the ones the insert writes and make a valid `ON CONFLICT`
+    /// clause.
+    fn validate(&self) -> Result<(), ProcessorError> {
+        let error = |message: String| {
+            Err(ProcessorError::ProcessError {
+                message: format!(
+                    "Invalid PostgresWritable for {}: {message}",
+                    self.table_name
+                ),
+            })
+        };
+        let inserted = self.inserted_columns();
+        let undeclared: Vec<&str> = inserted
+            .iter()
+            .map(String::as_str)
+            .filter(|column| !self.columns.contains(column))
+            .collect();
+        if !undeclared.is_empty() {
+            return error(format!(
+                "insert writes {undeclared:?}, which are not in COLUMNS"
+            ));
+        }
+        let unwritten: Vec<&str> = self
+            .columns
+            .iter()
+            .copied()
+            .filter(|column| !inserted.iter().any(|inserted| inserted == column))
+            .collect();
+        if !unwritten.is_empty() {
+            return error(format!(
+                "COLUMNS has {unwritten:?}, which insert doesn't write"
+            ));
+        }
+        if let Some(column) = self
+            .conflict_target
+            .iter()
+            .chain(self.preserved_columns)
+            .find(|column| !self.columns.contains(column))
+        {
+            return error(format!("column {column} is not in COLUMNS"));
+        }
+        match self.strategy {
+            WriteStrategy::InsertIgnore => Ok(()),
+            WriteStrategy::UpsertAllColumns | WriteStrategy::UpsertIfNewerVersion { .. }
+                if self.conflict_target.is_empty() =>
+            {
+                error("upserts need a conflict target".to_string())
+            },
+            WriteStrategy::UpsertAllColumns | WriteStrategy::UpsertIfNewerVersion { .. }
+                if updated_columns(self.columns, self.conflict_target, self.preserved_columns)
+                    .next()
+                    .is_none() =>
+            {
+                error("upserts need a column to update".to_string())
+            },
+            WriteStrategy::UpsertIfNewerVersion { version_column }
+                if !self.columns.contains(&version_column) =>
+            {
+                error(format!("version column {version_column} is not in COLUMNS"))
+            },
+            WriteStrategy::UpsertAllColumns | WriteStrategy::UpsertIfNewerVersion { .. } => Ok(()),
+        }
+    }
+
+    /// The columns in the `INSERT INTO table (columns) VALUES ...` of the insert, unquoted.
+    fn inserted_columns(&self) -> Vec<String> {
+        let sql = debug_query::<Backend, _>(&self.insert).to_string();
+        let Some(end) = sql.find(") VALUES") else {
+            return Vec::new();
+        };
+        let start = sql[..end].rfind('(').map_or(end, |start| start + 1);
+        sql[start..end]
+            .split(", ")
+            .map(|column| column.trim_matches('"').to_string())
+            .collect()
+    }
+}
+
+/// Models that passed validation, which only needs to happen for their first write.
+static VALIDATED_MODELS: Mutex<Vec<TypeId>> = Mutex::new(Vec::new());
+
+/// Validates what `T` declares against the insert of one of `rows`, unless it already passed.
+fn validate_once<T: PostgresWritable>(rows: &[T]) -> Result<(), ProcessorError> {
+    let mut validated = VALIDATED_MODELS.lock().unwrap();
+    if validated.contains(&TypeId::of::<T>()) {
+        return Ok(());
+    }
+    if let Some(row) = rows.first() {
+        WriteQuery::new(vec![row.clone()]).validate()?;
+        validated.push(TypeId::of::<T>());
+    }
+    Ok(())
+}
+
+/// The columns an upsert overwrites.
+fn updated_columns(
+    columns: &'static [&'static str],
+    conflict_target: &'static [&'static str],
+    preserved_columns: &'static [&'static str],
+) -> impl Iterator<Item = &'static str> {
+    columns
+        .iter()
+        .copied()
+        .filter(|column| !conflict_target.contains(column) && !preserved_columns.contains(column))
+}
+
+fn push_identifiers(out: &mut AstPass<'_, '_, Backend>, identifiers: &[&str]) -> QueryResult<()> {
+    for (i, identifier) in identifiers.iter().enumerate() {
+        if i > 0 {
+            out.push_sql(", ");
+        }
+        out.push_identifier(identifier)?;
+    }
+    Ok(())
+}
+
+impl QueryFragment<Backend> for WriteQuery {
+    fn walk_ast<'b>(&'b self, mut out: AstPass<'_, 'b, Backend>) -> QueryResult<()> {
+        self.insert.walk_ast(out.reborrow())?;
+        out.push_sql(" ON CONFLICT ");
+        if !self.conflict_target.is_empty() {
+            out.push_sql("(");
+            push_identifiers(&mut out, self.conflict_target)?;
+            out.push_sql(") ");
+        }
+        if self.strategy == WriteStrategy::InsertIgnore {
+            out.push_sql("DO NOTHING");
+            return Ok(());
+        }
+
+        out.push_sql("DO UPDATE SET ");
+        let columns = updated_columns(self.columns, self.conflict_target, self.preserved_columns);
+        for (i, column) in columns.enumerate() {
+            if i > 0 {
+                out.push_sql(", ");
+            }
+            out.push_identifier(column)?;
+            out.push_sql(" = EXCLUDED.");
+            out.push_identifier(column)?;
+        }
+        if let WriteStrategy::UpsertIfNewerVersion { version_column } = self.strategy {
+            out.push_sql(" WHERE ");
+            // The table name may be qualified with its schema
+            for (i, part) in self.table_name.split('.').enumerate() {
+                if i > 0 {
+                    out.push_sql(".");
+                }
+                out.push_identifier(part)?;
+            }
+            out.push_sql(".");
+            out.push_identifier(version_column)?;
+            out.push_sql(" <= EXCLUDED.");
+            out.push_identifier(version_column)?;
+        }
+        Ok(())
+    }
+}
+
+impl QueryId for WriteQuery {
+    type QueryId = ();
+
+    const HAS_STATIC_QUERY_ID: bool = false;
+}
+
+/// The rows of one table in a `RowBundle`.
+trait TableRows: Send + Sync {
+    fn table_name(&self) -> &'static str;
+    fn depends_on(&self) -> &'static [&'static str];
+    fn num_rows(&self) -> usize;
+    fn clone_box(&self) -> Box<dyn TableRows>;
+    fn write<'a>(
+        &'a self,
+        pool: ArcDbPool,
+        per_table_chunk_sizes: &'a AHashMap<String, usize>,
+    ) -> BoxFuture<'a, Result<(), ProcessorError>>;
+}
+
+impl<T: PostgresWritable> TableRows for Vec<T> {
+    fn table_name(&self) -> &'static str {
+        T::TABLE_NAME
+    }
+
+    fn depends_on(&self) -> &'static [&'static str] {
+        T::DEPENDS_ON
+    }
+
+    fn num_rows(&self) -> usize {
+        self.len()
+    }
+
+    fn clone_box(&self) -> Box<dyn TableRows> {
+        Box::new(self.clone())
+    }
+
+    fn write<'a>(
+        &'a self,
+        pool: ArcDbPool,
+        per_table_chunk_sizes: &'a AHashMap<String, usize>,
+    ) -> BoxFuture<'a, Result<(), ProcessorError>> {
+        Box::pin(async move {
+            validate_once(self)?;
+            let chunk_size = get_config_table_chunk_size::<T>(T::TABLE_NAME, per_table_chunk_sizes);
+            execute_in_chunks(pool, WriteQuery::new::<T>, self, chunk_size).await?;
+            info!(table = T::TABLE_NAME, rows = self.len(), "Stored rows");
+            Ok(())
+        })
+    }
+}
+
+/// Rows for any number of tables, to be written together by `write_bundle`.
+#[derive(Default)]
+pub struct RowBundle {
+    tables: Vec<Box<dyn TableRows>>,
+}
+
+impl Clone for RowBundle {
+    fn clone(&self) -> Self {
+        Self {
+            tables: self.tables.iter().map(|rows| rows.clone_box()).collect(),
+        }
+    }
+}
+
+impl RowBundle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds `rows` to the bundle. Nothing is written for empty vectors.
+    pub fn push<T: PostgresWritable>(&mut self, rows: Vec<T>) {
+        if !rows.is_empty() {
+            self.tables.push(Box::new(rows));
+        }
+    }
+
+    pub fn with<T: PostgresWritable>(mut self, rows: Vec<T>) -> Self {
+        self.push(rows);
+        self
+    }
+
+    /// Total number of rows in the bundle.
+    pub fn len(&self) -> usize {
+        self.tables.iter().map(|rows| rows.num_rows()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Groups the tables into rounds, each depending only on tables of earlier rounds.
+    /// Dependencies on tables that aren't in the bundle are ignored.
+    fn write_rounds(&self) -> Result<Vec<Vec<&dyn TableRows>>, ProcessorError> {
+        let mut remaining: Vec<&dyn TableRows> =
+            self.tables.iter().map(|rows| rows.as_ref()).collect();
+        let mut rounds = Vec::new();
+        while !remaining.is_empty() {
+            let remaining_tables: Vec<&str> =
+                remaining.iter().map(|rows| rows.table_name()).collect();
+            let (round, blocked): (Vec<&dyn TableRows>, Vec<&dyn TableRows>) =
+                remaining.into_iter().partition(|rows| {
+                    !rows.depends_on().iter().any(|parent| {
+                        *parent != rows.table_name() && remaining_tables.contains(parent)
+                    })
+                });
+            if round.is_empty() {
+                return Err(ProcessorError::ProcessError {
+                    message: format!("Dependency cycle between tables {remaining_tables:?}"),
+                });
+            }
+            rounds.push(round);
+            remaining = blocked;
+        }
+        Ok(rounds)
+    }
+}
+
+/// Writes every table in `bundle`, each after the tables it depends on. Tables that don't depend
+/// on each other are written concurrently. Stops at the first table that fails, so tables that
+/// depend on it aren't written.
+pub async fn write_bundle(
+    pool: ArcDbPool,
+    bundle: &RowBundle,
+    per_table_chunk_sizes: &AHashMap<String, usize>,
+) -> Result<(), ProcessorError> {
+    for round in bundle.write_rounds()? {
+        try_join_all(
+            round
+                .into_iter()
+                .map(|rows| rows.write(pool.clone(), per_table_chunk_sizes)),
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Writes the `RowBundle` of each batch with `write_bundle`, and passes it on.
+pub struct PostgresWriterStep {
+    pool: ArcDbPool,
+    per_table_chunk_sizes: AHashMap<String, usize>,
+}
+
+impl PostgresWriterStep {
+    pub fn new(pool: ArcDbPool, per_table_chunk_sizes: AHashMap<String, usize>) -> Self {
+        Self {
+            pool,
+            per_table_chunk_sizes,
+        }
+    }
+}
+
+#[async_trait]
+impl Processable for PostgresWriterStep {
+    type Input = RowBundle;
+    type Output = RowBundle;
+    type RunType = AsyncRunType;
+
+    async fn process(
+        &mut self,
+        item: TransactionContext<RowBundle>,
+    ) -> Result<Option<TransactionContext<RowBundle>>, ProcessorError> {
+        write_bundle(self.pool.clone(), &item.data, &self.per_table_chunk_sizes).await?;
+        Ok(Some(item))
+    }
+}
+
+impl AsyncStep for PostgresWriterStep {}
+
+impl NamedStep for PostgresWriterStep {
+    fn name(&self) -> String {
+        "PostgresWriterStep".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+
+    mod schema {
+        diesel::table! {
+            assets (asset_type) {
+                asset_type -> Text,
+                name -> Text,
+                inserted_at -> Int8,
+                last_transaction_version -> Int8,
+            }
+        }
+    }
+
+    #[derive(Clone, Deserialize, FieldCount, diesel::Insertable, Serialize)]
+    #[diesel(table_name = schema::assets)]
+    struct Asset {
+        asset_type: String,
+        name: String,
+        inserted_at: i64,
+        last_transaction_version: i64,
+    }
+
+    impl PostgresWritable for Asset {
+        const COLUMNS: &'static [&'static str] = &[
+            "asset_type",
+            "name",
+            "inserted_at",
+            "last_transaction_version",
+        ];
+        const CONFLICT_TARGET: &'static [&'static str] = &["asset_type"];
+        const PRESERVED_COLUMNS: &'static [&'static str] = &["inserted_at"];
+        const STRATEGY: WriteStrategy = WriteStrategy::UpsertIfNewerVersion {
+            version_column: "last_transaction_version",
+        };
+        const TABLE_NAME: &'static str = "assets";
+
+        fn insert(rows: Vec<Self>) -> BoxedInsert {
+            Box::new(diesel::insert_into(schema::assets::table).values(rows))
+        }
+    }
+
+    fn asset() -> Asset {
+        Asset {
+            asset_type: "0x1::aptos_coin::AptosCoin".to_string(),
+            name: "Aptos Coin".to_string(),
+            inserted_at: 1,
+            last_transaction_version: 5,
+        }
+    }
+
+    /// A query for `asset()` with the given declarations.
+    fn asset_query(
+        columns: &'static [&'static str],
+        conflict_target: &'static [&'static str],
+        strategy: WriteStrategy,
+    ) -> WriteQuery {
+        WriteQuery {
+            columns,
+            conflict_target,
+            strategy,
+            ..WriteQuery::new(vec![asset()])
+        }
+    }
+
+    /// The SQL of `query`, without the binds.
+    fn sql(query: &WriteQuery) -> String {
+        let sql = debug_query::<Backend, _>(query).to_string();
+        sql.split(" -- binds").next().unwrap().to_string()
+    }
+
+    const INSERT: &str = r#"INSERT INTO "assets" ("asset_type", "name", "inserted_at", "last_transaction_version") VALUES ($1, $2, $3, $4)"#;
+
+    #[test]
+    fn test_insert_ignore_sql() {
+        let query = asset_query(Asset::COLUMNS, &["asset_type"], WriteStrategy::InsertIgnore);
+        assert_eq!(
+            sql(&query),
+            format!(r#"{INSERT} ON CONFLICT ("asset_type") DO NOTHING"#)
+        );
+
+        let query = asset_query(Asset::COLUMNS, &[], WriteStrategy::InsertIgnore);
+        assert_eq!(sql(&query), format!("{INSERT} ON CONFLICT DO NOTHING"));
+    }
+
+    #[test]
+    fn test_upsert_all_columns_sql() {
+        let query = asset_query(
+            Asset::COLUMNS,
+            Asset::CONFLICT_TARGET,
+            WriteStrategy::UpsertAllColumns,
+        );
+        assert_eq!(
+            sql(&query),
+            format!(
+                r#"{INSERT} ON CONFLICT ("asset_type") DO UPDATE SET "name" = EXCLUDED."name", "last_transaction_version" = EXCLUDED."last_transaction_version""#
+            )
+        );
+    }
+
+    #[test]
+    fn test_upsert_if_newer_version_sql() {
+        let query = WriteQuery::new(vec![asset()]);
+        assert_eq!(
+            sql(&query),
+            format!(
+                r#"{INSERT} ON CONFLICT ("asset_type") DO UPDATE SET "name" = EXCLUDED."name", "last_transaction_version" = EXCLUDED."last_transaction_version" WHERE "assets"."last_transaction_version" <= EXCLUDED."last_transaction_version""#
+            )
+        );
+
+        let query = WriteQuery {
+            table_name: "public.assets",
+            ..WriteQuery::new(vec![asset()])
+        };
+        assert!(sql(&query).ends_with(
+            r#" WHERE "public"."assets"."last_transaction_version" <= EXCLUDED."last_transaction_version""#
+        ));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(WriteQuery::new(vec![asset()]).validate().is_ok());
+
+        let invalid = [
+            // A column insert writes is missing
+            asset_query(
+                &["asset_type", "name", "last_transaction_version"],
+                &["asset_type"],
+                WriteStrategy::InsertIgnore,
+            ),
+            // Same number of columns, but one is misnamed
+            asset_query(
+                &["asset_type", "name", "inserted", "last_transaction_version"],
+                &["asset_type"],
+                WriteStrategy::InsertIgnore,
+            ),
+            asset_query(Asset::COLUMNS, &["id"], WriteStrategy::InsertIgnore),
+            asset_query(Asset::COLUMNS, &[], WriteStrategy::UpsertAllColumns),
+            asset_query(
+                Asset::COLUMNS,
+                &["asset_type"],
+                WriteStrategy::UpsertIfNewerVersion {
+                    version_column: "version",
+                },
+            ),
+        ];
+        for query in invalid {
+            assert!(query.validate().is_err(), "{}", sql(&query));
+        }
+
+        // Columns may be declared in any order
+        let query = asset_query(
+            &[
+                "name",
+                "asset_type",
+                "last_transaction_version",
+                "inserted_at",
+            ],
+            &["asset_type"],
+            WriteStrategy::UpsertAllColumns,
+        );
+        assert!(query.validate().is_ok());
+    }
+
+    /// Rows of a table that only has dependencies.
+    struct Table {
+        name: &'static str,
+        depends_on: &'static [&'static str],
+    }
+
+    impl TableRows for Table {
+        fn table_name(&self) -> &'static str {
+            self.name
+        }
+
+        fn depends_on(&self) -> &'static [&'static str] {
+            self.depends_on
+        }
+
+        fn num_rows(&self) -> usize {
+            1
+        }
+
+        fn clone_box(&self) -> Box<dyn TableRows> {
+            Box::new(Table {
+                name: self.name,
+                depends_on: self.depends_on,
+            })
+        }
+
+        fn write<'a>(
+            &'a self,
+            _pool: ArcDbPool,
+            _per_table_chunk_sizes: &'a AHashMap<String, usize>,
+        ) -> BoxFuture<'a, Result<(), ProcessorError>> {
+            unimplemented!()
+        }
+    }
+
+    fn bundle(tables: &[(&'static str, &'static [&'static str])]) -> RowBundle {
+        RowBundle {
+            tables: tables
+                .iter()
+                .map(|&(name, depends_on)| Box::new(Table { name, depends_on }) as Box<_>)
+                .collect(),
+        }
+    }
+
+    fn round_names(bundle: &RowBundle) -> Result<Vec<Vec<&'static str>>, ProcessorError> {
+        Ok(bundle
+            .write_rounds()?
+            .into_iter()
+            .map(|round| round.into_iter().map(|rows| rows.table_name()).collect())
+            .collect())
+    }
+
+    #[test]
+    fn test_write_rounds_order() {
+        let bundle = bundle(&[
+            ("claims", &["bets"]),
+            ("bets", &["markets", "users"]),
+            ("markets", &[]),
+            // Depends on itself and on a table that isn't in the bundle
+            ("users", &["users", "assets"]),
+        ]);
+        let expected = vec![vec!["markets", "users"], vec!["bets"], vec!["claims"]];
+        assert_eq!(round_names(&bundle).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_rounds_cycle() {
+        let bundle = bundle(&[
+            ("markets", &[]),
+            ("bets", &["claims"]),
+            ("claims", &["bets"]),
+        ]);
+        match round_names(&bundle) {
+            Err(ProcessorError::ProcessError { message }) => {
+                assert!(message.contains(r#"["bets", "claims"]"#), "{message}")
+            },
+            result => panic!("Expected a dependency cycle, got {result:?}"),
+        }
+    }
+}
@@ -1,21 +1,27 @@
 // Copyright © Aptos Foundation
 
 use crate::{
+    admin_api::{self, AdminConfig},
+    builder::topology,
     instrumented_channel::channel_metrics::init_channel_metrics_registry,
-    utils::step_metrics::init_step_metrics_registry,
+    utils::{
+        health::{self, HealthCheckConfig},
+        shutdown::{ShutdownSignal, DEFAULT_SHUTDOWN_TIMEOUT_SECS},
+        step_metrics::init_step_metrics_registry,
+    },
 };
 use anyhow::{Context, Result};
 #[cfg(target_os = "linux")]
 use aptos_system_utils::profiling::start_cpu_profiling;
 use autometrics::settings::AutometricsSettings;
-use axum::{http::StatusCode, response::IntoResponse, routing::get, Router};
+use axum::{http::StatusCode, response::IntoResponse, routing::get, Json, Router};
 use backtrace::Backtrace;
 use clap::Parser;
 use prometheus_client::registry::Registry;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 // TODO: remove deprecated lint when new clippy nightly is released
 #[allow(deprecated)]
-use std::{fs::File, io::Read, panic::PanicInfo, path::PathBuf, process};
+use std::{fs::File, io::Read, panic::PanicInfo, path::PathBuf, process, time::Duration};
 use tokio::runtime::Handle;
 use tracing::error;
 use tracing_subscriber::EnvFilter;
@@ -43,18 +49,34 @@ impl ServerArgs {
 
 /// Run a server and the necessary probes. For spawning these tasks, the user must
 /// provide a handle to a runtime they already have.
+///
+/// On SIGTERM or SIGINT the server is asked to shut down, and given `shutdown_timeout_secs` to do
+/// so before this returns anyway.
 pub async fn run_server_with_config<C>(config: GenericConfig<C>, handle: Handle) -> Result<()>
 where
     C: RunnableConfig,
 {
     let health_port = config.health_check_port;
+    let health_check_config = config.health_check_config;
+    let admin_config = config.admin_config.clone();
     let additional_labels = config.metrics_config.additional_labels.clone();
+    let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    let shutdown_signal = ShutdownSignal::new();
+    shutdown_signal.trigger_on_termination();
     // Start liveness and readiness probes.
     let task_handler = handle.spawn(async move {
-        register_probes_and_metrics_handler(health_port, additional_labels).await;
+        register_probes_and_metrics_handler(
+            health_port,
+            health_check_config,
+            admin_config,
+            additional_labels,
+        )
+        .await;
         anyhow::Ok(())
     });
-    let main_task_handler = handle.spawn(async move { config.run().await });
+    let main_shutdown_signal = shutdown_signal.clone();
+    let main_task_handler =
+        handle.spawn(async move { config.run_with_shutdown(main_shutdown_signal).await });
     tokio::select! {
         res = task_handler => {
             res.expect("Probes and metrics handler unexpectedly exited")
@@ -62,6 +84,10 @@ where
         res = main_task_handler => {
             res.expect("Main task handler unexpectedly exited")
         },
+        _ = shutdown_signal.deadline(shutdown_timeout) => {
+            error!("Did not shut down within {}s. Exiting anyway.", shutdown_timeout.as_secs());
+            Ok(())
+        },
     }
 }
 
@@ -70,13 +96,28 @@ pub struct GenericConfig<T> {
     // Shared configuration among all services.
     pub health_check_port: u16,
 
+    #[serde(default)]
+    pub health_check_config: HealthCheckConfig,
+
+    /// Enables the admin API on the health check port, see `admin_api`.
+    #[serde(default)]
+    pub admin_config: Option<AdminConfig>,
+
     #[serde(default)]
     pub metrics_config: MetricsConfig,
 
+    /// How long to wait for in-flight work to drain after SIGTERM or SIGINT.
+    #[serde(default = "default_shutdown_timeout_secs")]
+    pub shutdown_timeout_secs: u64,
+
     // Specific configuration for each service.
     pub server_config: T,
 }
 
+const fn default_shutdown_timeout_secs() -> u64 {
+    DEFAULT_SHUTDOWN_TIMEOUT_SECS
+}
+
 #[derive(Clone, Deserialize, Debug, Default, Serialize)]
 pub struct MetricsConfig {
     /// Additional labels to use for metrics.
@@ -92,6 +133,10 @@ where
         self.server_config.run().await
     }
 
+    async fn run_with_shutdown(&self, shutdown_signal: ShutdownSignal) -> Result<()> {
+        self.server_config.run_with_shutdown(shutdown_signal).await
+    }
+
     fn get_server_name(&self) -> String {
         self.server_config.get_server_name()
     }
@@ -101,6 +146,15 @@ where
 #[async_trait::async_trait]
 pub trait RunnableConfig: DeserializeOwned + Send + Sync + 'static {
     async fn run(&self) -> Result<()>;
+
+    /// Like `run`, but should return once the work in flight is done after `shutdown_signal` is
+    /// triggered. By default `run` is simply stopped when the signal is triggered.
+    async fn run_with_shutdown(&self, shutdown_signal: ShutdownSignal) -> Result<()> {
+        tokio::select! {
+            res = self.run() => res,
+            _ = shutdown_signal.triggered() => Ok(()),
+        }
+    }
     fn get_server_name(&self) -> String;
 }
 
@@ -167,9 +221,12 @@ pub fn setup_logging() {
         .init();
 }
 
-/// Register readiness and liveness probes and set up metrics endpoint.
+/// Register readiness and liveness probes, the admin API if configured, and set up metrics and
+/// pipeline topology endpoints.
 pub async fn register_probes_and_metrics_handler(
     port: u16,
+    health_check_config: HealthCheckConfig,
+    admin_config: Option<AdminConfig>,
     additional_labels: Vec<(String, String)>,
 ) {
     let mut registry = Registry::with_labels(
@@ -184,18 +241,46 @@ pub async fn register_probes_and_metrics_handler(
         .init();
 
     let router = Router::new()
-        .route("/readiness", get(StatusCode::OK))
-        .route("/metrics", get(metrics_handler));
+        .route(
+            "/readiness",
+            get(move || readiness_handler(health_check_config)),
+        )
+        .route(
+            "/liveness",
+            get(move || liveness_handler(health_check_config)),
+        )
+        .route("/metrics", get(metrics_handler))
+        .route("/topology", get(topology_handler))
+        .route("/topology/dot", get(topology_dot_handler));
 
     #[cfg(target_os = "linux")]
     let router = router.merge(Router::new().route("/profilez", get(profilez_handler)));
 
+    let router = match admin_config {
+        Some(admin_config) => router.merge(admin_api::router(admin_config)),
+        None => router,
+    };
+
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}",))
         .await
         .expect("Failed to bind TCP listener");
     axum::serve(listener, router).await.unwrap();
 }
 
+async fn readiness_handler(config: HealthCheckConfig) -> impl IntoResponse {
+    match health::readiness_error(&config) {
+        None => StatusCode::OK.into_response(),
+        Some(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason).into_response(),
+    }
+}
+
+async fn liveness_handler(config: HealthCheckConfig) -> impl IntoResponse {
+    match health::liveness_error(&config) {
+        None => StatusCode::OK.into_response(),
+        Some(reason) => (StatusCode::SERVICE_UNAVAILABLE, reason).into_response(),
+    }
+}
+
 async fn metrics_handler() -> impl IntoResponse {
     match autometrics::prometheus_exporter::encode_to_string() {
         Ok(prometheus_client_rust_metrics) => (
@@ -208,6 +293,20 @@ async fn metrics_handler() -> impl IntoResponse {
     }
 }
 
+async fn topology_handler() -> impl IntoResponse {
+    match topology::registered_pipeline() {
+        Some(graph) => Json(graph.topology()).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No pipeline is running").into_response(),
+    }
+}
+
+async fn topology_dot_handler() -> impl IntoResponse {
+    match topology::registered_pipeline() {
+        Some(graph) => ([("Content-Type", "text/vnd.graphviz")], graph.dot()).into_response(),
+        None => (StatusCode::SERVICE_UNAVAILABLE, "No pipeline is running").into_response(),
+    }
+}
+
 #[cfg(target_os = "linux")]
 async fn profilez_handler() -> impl IntoResponse {
     match start_cpu_profiling(10, 99, false).await {
@@ -271,6 +370,8 @@ mod tests {
 
         let config = load::<GenericConfig<TestConfig>>(&file_path).unwrap();
         assert_eq!(config.health_check_port, 12345);
+        assert_eq!(config.shutdown_timeout_secs, DEFAULT_SHUTDOWN_TIMEOUT_SECS);
+        assert_eq!(config.health_check_config.readiness_max_lag_secs, None);
         assert_eq!(config.server_config.test, 123);
         assert_eq!(config.server_config.test_name, "test");
     }
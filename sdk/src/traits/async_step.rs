@@ -3,7 +3,10 @@ use crate::{
         processable::RunnableStepType, IntoRunnableStep, NamedStep, Processable, RunnableStep,
     },
     types::transaction_context::TransactionContext,
-    utils::step_metrics::{StepMetricLabels, StepMetricsBuilder},
+    utils::{
+        shutdown::ShutdownSignal,
+        step_metrics::{StepMetricLabels, StepMetricsBuilder},
+    },
 };
 use async_trait::async_trait;
 use bigdecimal::Zero;
@@ -73,6 +76,8 @@ where
         input_receiver: Option<InstrumentedAsyncReceiver<TransactionContext<Step::Input>>>,
         output_channel_size: usize,
         _input_sender: Option<InstrumentedAsyncSender<TransactionContext<Step::Input>>>,
+        // Async steps only work on their input, so they stop once the upstream step closes it.
+        _shutdown_signal: ShutdownSignal,
     ) -> (
         InstrumentedAsyncReceiver<TransactionContext<Step::Output>>,
         JoinHandle<()>,
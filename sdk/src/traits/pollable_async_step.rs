@@ -5,6 +5,7 @@ use crate::{
     types::transaction_context::TransactionContext,
     utils::{
         errors::ProcessorError,
+        shutdown::ShutdownSignal,
         step_metrics::{StepMetricLabels, StepMetricsBuilder},
     },
 };
@@ -88,6 +89,7 @@ where
         input_receiver: Option<InstrumentedAsyncReceiver<TransactionContext<PollableStep::Input>>>,
         output_channel_size: usize,
         _input_sender: Option<InstrumentedAsyncSender<TransactionContext<PollableStep::Input>>>,
+        shutdown_signal: ShutdownSignal,
     ) -> (
         InstrumentedAsyncReceiver<TransactionContext<PollableStep::Output>>,
         JoinHandle<()>,
@@ -101,7 +103,7 @@ where
 
         let handle = tokio::spawn(async move {
             // This should only be used for the inputless first step to keep the async sender in scope so the channel stays alive.
-            let _input_sender = _input_sender.clone();
+            let mut input_sender = _input_sender;
             let step_name = step.name();
 
             step.init().await;
@@ -113,13 +115,22 @@ where
             let poll_step = Arc::clone(&arc_step);
             let poll_step_name = step_name.clone();
             let poll_output_sender = output_sender.clone();
+            let poll_shutdown_signal = shutdown_signal.clone();
+            // Returns whether polling stopped because of the shutdown signal
             let mut polling_task = tokio::spawn(async move {
                 let poll_duration = poll_step.lock().await.poll_interval();
 
-                while poll_step.lock().await.should_continue_polling().await {
+                while !poll_shutdown_signal.is_triggered()
+                    && poll_step.lock().await.should_continue_polling().await
+                {
                     // It's possible that the channel always has items, so we need to ensure we call `poll` manually if we need to
                     let polling_duration_for_logging = Instant::now();
-                    let result = match poll_step.lock().await.poll().await {
+                    // A poll can wait a long time on its source, e.g. the transaction stream
+                    let result = tokio::select! {
+                        result = async { poll_step.lock().await.poll().await } => result,
+                        _ = poll_shutdown_signal.triggered() => break,
+                    };
+                    let result = match result {
                         Ok(result) => result,
                         Err(e) => {
                             error!(
@@ -194,8 +205,12 @@ where
                         }
                     };
 
-                    tokio::time::sleep(poll_duration).await;
+                    tokio::select! {
+                        _ = tokio::time::sleep(poll_duration) => {},
+                        _ = poll_shutdown_signal.triggered() => break,
+                    }
                 }
+                poll_shutdown_signal.is_triggered()
             });
 
             // Spawn processing task
@@ -278,11 +293,25 @@ where
                 }
             });
 
-            // If either polling or processing task ends, we should stop the other one.
+            // If either polling or processing task ends, we should stop the other one. On shutdown,
+            // the processing task keeps going until its input closes, so in-flight batches drain.
             tokio::select! {
-                _ = &mut polling_task => {
-                    info!(step_name = step_name, "Polling task has ended. Stopping processing task.");
-                    processing_task.abort();
+                res = &mut polling_task => {
+                    if matches!(res, Ok(true)) {
+                        info!(
+                            step_name = step_name,
+                            "Shutting down. Draining input before cleanup."
+                        );
+                        // Closes the input of the inputless first step, which starts the drain
+                        input_sender.take();
+                        let _ = processing_task.await;
+                    } else {
+                        info!(
+                            step_name = step_name,
+                            "Polling task has ended. Stopping processing task."
+                        );
+                        processing_task.abort();
+                    }
                 },
                 _ = &mut processing_task => {
                     info!(step_name = step_name, "Processing task has ended. Stopping polling task.");
@@ -1,4 +1,7 @@
-use crate::{traits::NamedStep, types::transaction_context::TransactionContext};
+use crate::{
+    traits::NamedStep, types::transaction_context::TransactionContext,
+    utils::shutdown::ShutdownSignal,
+};
 use instrumented_channel::{InstrumentedAsyncReceiver, InstrumentedAsyncSender};
 use std::marker::PhantomData;
 use tokio::task::JoinHandle;
@@ -11,11 +14,14 @@ where
 {
     #[allow(clippy::too_long_first_doc_paragraph)]
     /// Runs the step, forever, with the given input receiver and returns the output receiver and the join handle.
+    /// The step stops once its input closes or, for steps that produce output on their own, once
+    /// `shutdown_signal` is triggered.
     fn spawn(
         self,
         input_receiver: Option<InstrumentedAsyncReceiver<TransactionContext<Input>>>,
         output_channel_size: usize,
         _input_sender: Option<InstrumentedAsyncSender<TransactionContext<Input>>>,
+        shutdown_signal: ShutdownSignal,
     ) -> (
         InstrumentedAsyncReceiver<TransactionContext<Output>>,
         JoinHandle<()>,
@@ -104,6 +110,7 @@ where
         input_receiver: Option<InstrumentedAsyncReceiver<TransactionContext<Input>>>,
         channel_size: usize,
         _input_sender: Option<InstrumentedAsyncSender<TransactionContext<Input>>>,
+        shutdown_signal: ShutdownSignal,
     ) -> (
         InstrumentedAsyncReceiver<TransactionContext<Output>>,
         JoinHandle<()>,
@@ -111,8 +118,12 @@ where
         if input_receiver.is_some() {
             panic!("Input receiver already set for {:?}", self.name());
         }
-        self.step
-            .spawn(Some(self.input_receiver), channel_size, _input_sender)
+        self.step.spawn(
+            Some(self.input_receiver),
+            channel_size,
+            _input_sender,
+            shutdown_signal,
+        )
     }
 
     fn add_input_receiver(
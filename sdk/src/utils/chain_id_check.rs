@@ -48,10 +48,9 @@ where
     match maybe_existing_chain_id {
         Some(chain_id) => {
             if chain_id != grpc_chain_id {
-                return Err(ProcessorError::ChainIdCheckError {
-                    message: format!(
-                        "Wrong chain id detected! Trying to index chain {grpc_chain_id} now but existing data is for chain {chain_id}",
-                    ),
+                return Err(ProcessorError::ChainMismatch {
+                    expected_chain_id: chain_id,
+                    actual_chain_id: grpc_chain_id,
                 });
             }
 
@@ -118,6 +118,28 @@ where
     s.parse::<T>().map_err(D::Error::custom)
 }
 
+/// Deserialize a u64 that may be encoded either as a JSON string or as a JSON number. Move
+/// events serialize u64 as strings, but payloads built off-chain or by newer contract
+/// versions sometimes use plain numbers.
+pub fn deserialize_u64_from_string_or_number<'de, D>(deserializer: D) -> Result<u64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    use serde::de::Error;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(u64),
+    }
+
+    match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s.parse::<u64>().map_err(D::Error::custom),
+        StringOrNumber::Number(n) => Ok(n),
+    }
+}
+
 /// Convert the bcs serialized vector<u8> to its original string format
 pub fn convert_bcs_hex(typ: String, value: String) -> Option<String> {
     let decoded = hex::decode(value.strip_prefix("0x").unwrap_or(&*value)).ok()?;
@@ -153,3 +175,28 @@ pub fn convert_bcs_hex_new(typ: u8, value: String) -> Option<String> {
     }
         .ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Amount {
+        #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
+        amount: u64,
+    }
+
+    #[test]
+    fn test_deserialize_u64_from_string_or_number() {
+        let from_string: Amount =
+            serde_json::from_str(r#"{"amount": "18446744073709551615"}"#).unwrap();
+        assert_eq!(from_string.amount, u64::MAX);
+
+        let from_number: Amount = serde_json::from_str(r#"{"amount": 42}"#).unwrap();
+        assert_eq!(from_number.amount, 42);
+
+        assert!(serde_json::from_str::<Amount>(r#"{"amount": "-1"}"#).is_err());
+        assert!(serde_json::from_str::<Amount>(r#"{"amount": -1}"#).is_err());
+        assert!(serde_json::from_str::<Amount>(r#"{"amount": "abc"}"#).is_err());
+    }
+}
@@ -1,5 +1,12 @@
 use thiserror::Error;
 
+/// The error a `ProcessorError` was created from, kept as its `source()`.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Queries kept in errors are cut to this many bytes. A chunked insert renders to megabytes of
+/// SQL, which is too much to log or keep around.
+pub const MAX_ERROR_QUERY_LEN: usize = 2_000;
+
 #[derive(Error, Debug)]
 pub enum ProcessorError {
     #[error("Step Init Error: {message}")]
@@ -12,7 +19,137 @@ pub enum ProcessorError {
     DBStoreError {
         message: String,
         query: Option<String>,
+        #[source]
+        source: Option<BoxError>,
     },
     #[error("Chain ID Check Error: {message}")]
     ChainIdCheckError { message: String },
+    /// The DB or the transaction stream could not be reached, or the connection dropped.
+    #[error("Connection Error: {message}")]
+    ConnectionError {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// A write broke a unique, foreign key, not null or check constraint.
+    #[error("Constraint Violation: {message}, Constraint: {constraint:?}, Query: {query:?}")]
+    ConstraintViolation {
+        message: String,
+        constraint: Option<String>,
+        query: Option<String>,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// Data could not be converted to or from its DB or wire representation.
+    #[error("Serialization Error: {message}")]
+    SerializationError {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// A query or connection attempt took too long.
+    #[error("Timeout: {message}")]
+    Timeout {
+        message: String,
+        #[source]
+        source: Option<BoxError>,
+    },
+    /// A batch did not start right after the previous one.
+    #[error("Stream Gap: expected version {expected_version}, got {actual_version}")]
+    StreamGap {
+        expected_version: u64,
+        actual_version: u64,
+    },
+    /// The transaction stream serves a different chain than the one the DB was indexed from.
+    #[error("Chain Mismatch: the DB has chain id {expected_chain_id}, the stream has chain id {actual_chain_id}")]
+    ChainMismatch {
+        expected_chain_id: u64,
+        actual_chain_id: u64,
+    },
+}
+
+impl ProcessorError {
+    /// Whether the error may go away if the same batch is tried again, e.g. the DB or the
+    /// transaction stream being briefly unavailable. Other errors are fatal for the batch.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ProcessorError::DBStoreError { .. }
+            | ProcessorError::PollError { .. }
+            | ProcessorError::ConnectionError { .. }
+            | ProcessorError::Timeout { .. } => true,
+            ProcessorError::StepInitError { .. }
+            | ProcessorError::ProcessError { .. }
+            | ProcessorError::ChainIdCheckError { .. }
+            | ProcessorError::ConstraintViolation { .. }
+            | ProcessorError::SerializationError { .. }
+            | ProcessorError::StreamGap { .. }
+            | ProcessorError::ChainMismatch { .. } => false,
+        }
+    }
+
+    /// Whether the error means the DB or stream couldn't be reached at all, as opposed to
+    /// rejecting the request.
+    pub fn is_connection_failure(&self) -> bool {
+        matches!(
+            self,
+            ProcessorError::ConnectionError { .. } | ProcessorError::Timeout { .. }
+        )
+    }
+}
+
+/// Cuts `query` to at most `MAX_ERROR_QUERY_LEN` bytes, on a char boundary, noting how much was
+/// left out.
+pub fn truncate_query(mut query: String) -> String {
+    if query.len() <= MAX_ERROR_QUERY_LEN {
+        return query;
+    }
+    let total_len = query.len();
+    let mut end = MAX_ERROR_QUERY_LEN;
+    while !query.is_char_boundary(end) {
+        end -= 1;
+    }
+    query.truncate(end);
+    query.push_str(&format!("... ({} more bytes)", total_len - end));
+    query
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error as _;
+
+    #[test]
+    fn test_truncate_query() {
+        let short = "SELECT 1".to_string();
+        assert_eq!(truncate_query(short.clone()), short);
+
+        // Multi-byte chars straddle the limit, so the cut has to move back to a char boundary
+        let long = format!("a{}", "é".repeat(MAX_ERROR_QUERY_LEN));
+        let truncated = truncate_query(long);
+        let kept = format!("a{}", "é".repeat(MAX_ERROR_QUERY_LEN / 2 - 1));
+        assert_eq!(
+            truncated,
+            format!(
+                "{kept}... ({} more bytes)",
+                2 * MAX_ERROR_QUERY_LEN + 1 - kept.len()
+            )
+        );
+    }
+
+    #[test]
+    fn test_source_is_kept() {
+        let error = ProcessorError::ConnectionError {
+            message: "connection refused".to_string(),
+            source: Some(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into()),
+        };
+        assert!(error.is_retryable());
+        assert!(error.source().is_some());
+
+        let error = ProcessorError::StreamGap {
+            expected_version: 10,
+            actual_version: 12,
+        };
+        assert!(!error.is_retryable());
+        assert!(error.source().is_none());
+    }
 }
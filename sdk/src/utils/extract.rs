@@ -336,10 +336,10 @@ fn get_clean_script_payload(payload: &ScriptPayload, version: i64) -> ScriptPayl
 }
 
 /// Get name from unwrapped move type
-/// E.g. 0x1::domain::Name will return Name
+/// E.g. 0x1::domain::Name will return Name, and 0x1::coin::Coin<0x1::aptos_coin::AptosCoin> Coin
 pub fn get_name_from_unnested_move_type(move_type: &str) -> &str {
-    let t: Vec<&str> = move_type.split("::").collect();
-    t.last().unwrap()
+    let base = move_type.split('<').next().unwrap_or(move_type);
+    base.rsplit("::").next().unwrap_or(base).trim()
 }
 
 #[cfg(test)]
@@ -498,4 +498,16 @@ mod tests {
         let d: TokenObjectDataMock = serde_json::from_str(val.as_str()).unwrap();
         assert_eq!(d.default_properties, Value::Object(serde_json::Map::new()));
     }
+
+    #[test]
+    fn test_get_name_from_unnested_move_type() {
+        assert_eq!(
+            get_name_from_unnested_move_type("0x1::domain::Name"),
+            "Name"
+        );
+        assert_eq!(
+            get_name_from_unnested_move_type("0x1::coin::Coin<0x1::aptos_coin::AptosCoin>"),
+            "Coin"
+        );
+    }
 }
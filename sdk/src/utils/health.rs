@@ -0,0 +1,169 @@
+//! Health of the processor running in this process, as reported by the `/readiness` and
+//! `/liveness` probes that `register_probes_and_metrics_handler` serves.
+
+use crate::admin_api;
+use chrono::{NaiveDateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+static DB_AVAILABLE: AtomicBool = AtomicBool::new(true);
+static STREAM_CONNECTED: AtomicBool = AtomicBool::new(true);
+/// Timestamp of the last checkpointed version, and when it was recorded.
+static CHECKPOINTED_POSITION: Mutex<Option<(NaiveDateTime, Instant)>> = Mutex::new(None);
+static LAST_PROGRESS: Lazy<Mutex<Instant>> = Lazy::new(|| Mutex::new(Instant::now()));
+
+/// Held by tests that change the state above, so they don't see each other's changes.
+#[cfg(test)]
+pub(crate) static TEST_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// When the probes report the processor as unhealthy, on top of the DB or the transaction
+/// stream being unreachable. Both checks are off by default.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct HealthCheckConfig {
+    /// Not ready while the checkpointed version is further than this behind the chain.
+    pub readiness_max_lag_secs: Option<u64>,
+    /// Not live while no step has processed or polled a batch for this long. Not checked while
+    /// the processor is paused, for a DB outage or through the admin API.
+    pub liveness_max_stall_secs: Option<u64>,
+}
+
+/// Marks whether the DB can be reached. The processor is not ready while it can't.
+pub fn set_db_available(available: bool) {
+    DB_AVAILABLE.store(available, Ordering::Relaxed);
+}
+
+pub fn is_db_available() -> bool {
+    DB_AVAILABLE.load(Ordering::Relaxed)
+}
+
+/// Marks whether the transaction stream is connected. The processor is not ready while it isn't.
+pub fn set_stream_connected(connected: bool) {
+    STREAM_CONNECTED.store(connected, Ordering::Relaxed);
+}
+
+pub fn is_stream_connected() -> bool {
+    STREAM_CONNECTED.load(Ordering::Relaxed)
+}
+
+/// Records a checkpointed batch, whose lag readiness compares against `readiness_max_lag_secs`.
+///
+/// Batches the transaction filter emptied have no timestamp. Those only move the position on by
+/// the time since the previous checkpoint: a caught-up processor on a quiet chain stays as far
+/// behind as it was, and one backfilling faster than real time never looks closer than it is.
+pub fn record_checkpoint(last_transaction_timestamp: Option<NaiveDateTime>) {
+    let mut position = CHECKPOINTED_POSITION.lock().unwrap();
+    let timestamp = match (last_transaction_timestamp, *position) {
+        (Some(timestamp), _) => timestamp,
+        (None, Some((timestamp, recorded_at))) => {
+            timestamp + chrono::Duration::from_std(recorded_at.elapsed()).unwrap_or_default()
+        },
+        (None, None) => return,
+    };
+    *position = Some((timestamp, Instant::now()));
+}
+
+/// Records that a step processed or polled a batch just now.
+pub fn record_progress() {
+    *LAST_PROGRESS.lock().unwrap() = Instant::now();
+}
+
+/// Why the processor is not ready, or `None` if it is.
+pub fn readiness_error(config: &HealthCheckConfig) -> Option<String> {
+    if !is_db_available() {
+        return Some("Database is unavailable".to_string());
+    }
+    if !is_stream_connected() {
+        return Some("Transaction stream is disconnected".to_string());
+    }
+    let max_lag_secs = config.readiness_max_lag_secs?;
+    let (timestamp, _) = (*CHECKPOINTED_POSITION.lock().unwrap())?;
+    let lag_secs = (Utc::now().naive_utc() - timestamp).num_seconds();
+    if lag_secs > max_lag_secs as i64 {
+        return Some(format!(
+            "Lagging {lag_secs}s behind, more than the allowed {max_lag_secs}s"
+        ));
+    }
+    None
+}
+
+/// Why the processor is not live, or `None` if it is.
+pub fn liveness_error(config: &HealthCheckConfig) -> Option<String> {
+    let max_stall = Duration::from_secs(config.liveness_max_stall_secs?);
+    // Waiting for the DB to come back, or for an admin to resume, is not a reason to restart
+    if !is_db_available() || admin_api::is_paused() {
+        return None;
+    }
+    let stalled_for = LAST_PROGRESS.lock().unwrap().elapsed();
+    if stalled_for > max_stall {
+        return Some(format!(
+            "No progress for {}s, more than the allowed {}s",
+            stalled_for.as_secs(),
+            max_stall.as_secs()
+        ));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_readiness_and_liveness() {
+        let _guard = TEST_LOCK.blocking_lock();
+        let config = HealthCheckConfig {
+            readiness_max_lag_secs: Some(60),
+            liveness_max_stall_secs: Some(60),
+        };
+        record_progress();
+        assert_eq!(readiness_error(&config), None);
+        assert_eq!(liveness_error(&config), None);
+
+        record_checkpoint(Some(Utc::now().naive_utc() - chrono::Duration::hours(1)));
+        assert!(readiness_error(&config).unwrap().starts_with("Lagging"));
+        // Empty batches don't tell how far behind the processor is, so the lag stays
+        record_checkpoint(None);
+        assert!(readiness_error(&config).unwrap().starts_with("Lagging"));
+        assert_eq!(readiness_error(&HealthCheckConfig::default()), None);
+
+        set_stream_connected(false);
+        assert_eq!(
+            readiness_error(&config).unwrap(),
+            "Transaction stream is disconnected"
+        );
+        set_stream_connected(true);
+
+        record_checkpoint(Some(Utc::now().naive_utc()));
+        assert_eq!(readiness_error(&config), None);
+    }
+
+    #[test]
+    fn test_empty_batches_keep_the_lag() {
+        let _guard = TEST_LOCK.blocking_lock();
+        let config = HealthCheckConfig {
+            readiness_max_lag_secs: Some(2),
+            liveness_max_stall_secs: None,
+        };
+        record_checkpoint(Some(Utc::now().naive_utc() - chrono::Duration::seconds(1)));
+
+        // Caught up on a chain with nothing to index: the lag doesn't grow while batches arrive
+        for _ in 0..3 {
+            std::thread::sleep(Duration::from_millis(500));
+            record_checkpoint(None);
+            assert_eq!(readiness_error(&config), None);
+        }
+
+        // But does once they stop
+        std::thread::sleep(Duration::from_millis(2500));
+        assert!(readiness_error(&config).unwrap().starts_with("Lagging"));
+        record_checkpoint(Some(Utc::now().naive_utc()));
+    }
+}
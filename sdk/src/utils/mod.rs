@@ -3,5 +3,8 @@ pub mod constants;
 pub mod convert;
 pub mod errors;
 pub mod extract;
+pub mod health;
 pub mod property_map;
+pub mod shutdown;
 pub mod step_metrics;
+pub mod struct_tag;
@@ -0,0 +1,113 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Graceful shutdown of a processor pipeline.
+//!
+//! A [`ShutdownSignal`] is shared by every step of a pipeline. Once it is triggered, pollable
+//! steps stop polling and the inputless first step closes its output, so the in-flight batches
+//! drain through the rest of the pipeline as each channel closes. Every step's `cleanup` runs on
+//! the way, which is where `VersionTrackerStep` saves the last checkpoint.
+
+use std::{sync::Arc, time::Duration};
+use tokio::sync::watch;
+use tracing::info;
+
+/// How long a processor may take to drain after a termination signal, if not configured.
+pub const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+#[derive(Clone, Debug)]
+pub struct ShutdownSignal {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl Default for ShutdownSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownSignal {
+    pub fn new() -> Self {
+        let (sender, _) = watch::channel(false);
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    pub fn trigger(&self) {
+        self.sender.send_replace(true);
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.sender.borrow()
+    }
+
+    /// Resolves once the signal is triggered.
+    pub async fn triggered(&self) {
+        let mut receiver = self.sender.subscribe();
+        // The sender lives as long as `self`, so this can't fail.
+        let _ = receiver.wait_for(|triggered| *triggered).await;
+    }
+
+    /// Resolves `timeout` after the signal is triggered. Used to bound how long draining takes.
+    pub async fn deadline(&self, timeout: Duration) {
+        self.triggered().await;
+        tokio::time::sleep(timeout).await;
+    }
+
+    /// Triggers the signal on SIGINT, or SIGTERM on unix.
+    pub fn trigger_on_termination(&self) {
+        let signal = self.clone();
+        tokio::spawn(async move {
+            wait_for_termination().await;
+            info!("Received termination signal. Shutting down.");
+            signal.trigger();
+        });
+    }
+}
+
+async fn wait_for_termination() {
+    #[cfg(unix)]
+    {
+        let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = sigterm.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_deadline_starts_on_trigger() {
+        let signal = ShutdownSignal::new();
+        assert!(!signal.is_triggered());
+
+        let deadline = signal.deadline(Duration::from_millis(50));
+        tokio::pin!(deadline);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), &mut deadline)
+                .await
+                .is_err(),
+            "Deadline should not pass before the signal is triggered"
+        );
+
+        signal.clone().trigger();
+        assert!(signal.is_triggered());
+        assert!(
+            tokio::time::timeout(Duration::from_millis(200), &mut deadline)
+                .await
+                .is_ok(),
+            "Deadline should pass shortly after the signal is triggered"
+        );
+    }
+}
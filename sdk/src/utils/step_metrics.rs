@@ -1,3 +1,4 @@
+use super::health;
 use derive_builder::Builder;
 use once_cell::sync::Lazy;
 use prometheus_client::{
@@ -5,6 +6,7 @@ use prometheus_client::{
     metrics::{counter::Counter, family::Family, gauge::Gauge},
     registry::Registry,
 };
+use serde::Serialize;
 use std::sync::atomic::AtomicU64;
 
 pub const METRICS_PREFIX: &str = "aptos_procsdk_step_";
@@ -114,6 +116,13 @@ pub fn init_step_metrics_registry(registry: &mut Registry) {
         "WriteRateLimitStep bytes written",
         WRITE_RATE_LIMIT_STEP_BYTES_WRITTEN.clone(),
     );
+
+    // Event parsing metrics
+    registry.register(
+        format!("{}_{}", METRICS_PREFIX, "event_parse_count"),
+        "Number of events parsed, by event type, detected schema version and outcome",
+        EVENT_PARSE_COUNT.clone(),
+    );
 }
 
 #[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
@@ -174,6 +183,66 @@ pub static WRITE_RATE_LIMIT_STEP_REMAINING_BYTES: Lazy<Family<StepMetricLabels,
 pub static WRITE_RATE_LIMIT_STEP_BYTES_WRITTEN: Lazy<Family<StepMetricLabels, Counter>> =
     Lazy::new(Family::<StepMetricLabels, Counter>::default);
 
+// Event parsing metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct EventParseMetricLabels {
+    pub event_type: String,
+    pub schema_version: String,
+    /// Either "success" or "failure".
+    pub status: String,
+}
+
+pub static EVENT_PARSE_COUNT: Lazy<Family<EventParseMetricLabels, Counter>> =
+    Lazy::new(Family::<EventParseMetricLabels, Counter>::default);
+
+/// Current values of a step's metrics, for reporting them outside of Prometheus. Metrics the step
+/// hasn't logged yet read 0.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StepMetricsSnapshot {
+    pub latest_processed_version: i64,
+    pub latest_polled_version: i64,
+    pub processing_duration_in_secs: f64,
+    pub polling_duration_in_secs: f64,
+}
+
+impl StepMetricsSnapshot {
+    pub fn get(step_name: &str) -> Self {
+        let labels = StepMetricLabels {
+            step_name: step_name.to_string(),
+        };
+        Self {
+            latest_processed_version: LATEST_PROCESSED_VERSION.get_or_create(&labels).get(),
+            latest_polled_version: LATEST_POLLED_VERSION.get_or_create(&labels).get(),
+            processing_duration_in_secs: PROCESSING_DURATION_IN_SECS.get_or_create(&labels).get(),
+            polling_duration_in_secs: POLLING_DURATION_IN_SECS.get_or_create(&labels).get(),
+        }
+    }
+
+    /// The last version the step processed, or polled for steps that only poll.
+    pub fn latest_version(&self) -> i64 {
+        self.latest_processed_version
+            .max(self.latest_polled_version)
+    }
+
+    /// How long the step took on its last batch, or its last poll for steps that only poll.
+    pub fn duration_in_secs(&self) -> f64 {
+        if self.processing_duration_in_secs > 0.0 {
+            self.processing_duration_in_secs
+        } else {
+            self.polling_duration_in_secs
+        }
+    }
+
+    /// One line summary, for graph labels.
+    pub fn summary(&self) -> String {
+        format!(
+            "version {}, took {:.3}s",
+            self.latest_version(),
+            self.duration_in_secs()
+        )
+    }
+}
+
 #[derive(Builder)]
 pub struct StepMetrics {
     pub labels: StepMetricLabels,
@@ -213,6 +282,7 @@ impl StepMetrics {
             LATEST_PROCESSED_VERSION
                 .get_or_create(&self.labels)
                 .set(version as i64);
+            health::record_progress();
         }
         if let Some(timestamp) = self.latest_transaction_timestamp {
             LATEST_PROCESSED_TRANSACTION_TIMESTAMP
@@ -245,6 +315,7 @@ impl StepMetrics {
             LATEST_POLLED_VERSION
                 .get_or_create(&self.labels)
                 .set(version as i64);
+            health::record_progress();
         }
         if let Some(timestamp) = self.latest_polled_transaction_timestamp {
             LATEST_POLLED_TRANSACTION_TIMESTAMP
@@ -0,0 +1,323 @@
+// Copyright © Aptos Foundation
+// SPDX-License-Identifier: Apache-2.0
+
+//! Parsing, formatting and matching of Move type tags, as found in event and resource type
+//! strings such as `0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>`.
+//!
+//! Addresses are normalized to the long form (`0x` + 64 hex chars), so `0x1` and `0x0...01`
+//! compare equal. Formatting follows AIP-40, the format the node API uses: special addresses
+//! (`0x0` to `0xf`) are printed short and all others long.
+
+use super::convert::standardize_address;
+use anyhow::{bail, Context, Result};
+use std::{fmt, str::FromStr};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeTag {
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    U256,
+    Address,
+    Signer,
+    Vector(Box<TypeTag>),
+    Struct(Box<StructTag>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StructTag {
+    /// Always in the long form.
+    pub address: String,
+    pub module: String,
+    pub name: String,
+    pub type_args: Vec<TypeTag>,
+}
+
+/// Normalizes an address to lowercase `0x` + 64 hex chars.
+pub fn normalize_address(address: &str) -> String {
+    standardize_address(&address.to_ascii_lowercase())
+}
+
+/// Formats an address per AIP-40: `0x0` to `0xf` short, everything else long.
+pub fn format_address(address: &str) -> String {
+    let long = normalize_address(address);
+    let hex = long.trim_start_matches("0x");
+    if hex[..63].bytes().all(|b| b == b'0') {
+        format!("0x{}", &hex[63..])
+    } else {
+        long
+    }
+}
+
+impl StructTag {
+    pub fn new(address: &str, module: &str, name: &str) -> Self {
+        Self {
+            address: normalize_address(address),
+            module: module.to_string(),
+            name: name.to_string(),
+            type_args: vec![],
+        }
+    }
+
+    /// Whether this struct is declared in `address::module`, e.g. to match any event of a module.
+    pub fn is_in_module(&self, address: &str, module: &str) -> bool {
+        self.module == module && self.address == normalize_address(address)
+    }
+
+    /// Whether this is `address::module::name`, with any type arguments.
+    pub fn is(&self, address: &str, module: &str, name: &str) -> bool {
+        self.name == name && self.is_in_module(address, module)
+    }
+
+    /// Whether both tags name the same struct, ignoring type arguments.
+    pub fn is_same_struct(&self, other: &StructTag) -> bool {
+        self.address == other.address && self.module == other.module && self.name == other.name
+    }
+
+    /// This tag without its type arguments.
+    pub fn without_type_args(&self) -> StructTag {
+        StructTag {
+            type_args: vec![],
+            ..self.clone()
+        }
+    }
+}
+
+impl FromStr for StructTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.parse::<TypeTag>()? {
+            TypeTag::Struct(tag) => Ok(*tag),
+            other => bail!("Expected a struct type, got {other}"),
+        }
+    }
+}
+
+impl FromStr for TypeTag {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut parser = Parser { input: s, pos: 0 };
+        let tag = parser
+            .type_tag()
+            .with_context(|| format!("Invalid Move type {s:?}"))?;
+        parser.skip_whitespace();
+        if parser.pos != s.len() {
+            bail!(
+                "Invalid Move type {s:?}: unexpected {:?} at {}",
+                &s[parser.pos..],
+                parser.pos
+            );
+        }
+        Ok(tag)
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input[self.pos..].chars().next()
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        self.skip_whitespace();
+        if !self.input[self.pos..].starts_with(token) {
+            bail!("expected {token:?} at {}", self.pos);
+        }
+        self.pos += token.len();
+        Ok(())
+    }
+
+    /// An address, module, struct or primitive name.
+    fn identifier(&mut self) -> Result<&'a str> {
+        self.skip_whitespace();
+        let rest = &self.input[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            bail!("expected an identifier at {}", self.pos);
+        }
+        self.pos += len;
+        Ok(&rest[..len])
+    }
+
+    fn type_tag(&mut self) -> Result<TypeTag> {
+        let identifier = self.identifier()?;
+        let tag = match identifier {
+            "bool" => TypeTag::Bool,
+            "u8" => TypeTag::U8,
+            "u16" => TypeTag::U16,
+            "u32" => TypeTag::U32,
+            "u64" => TypeTag::U64,
+            "u128" => TypeTag::U128,
+            "u256" => TypeTag::U256,
+            "address" => TypeTag::Address,
+            "signer" => TypeTag::Signer,
+            "vector" => {
+                self.expect("<")?;
+                let inner = self.type_tag()?;
+                self.expect(">")?;
+                TypeTag::Vector(Box::new(inner))
+            },
+            address => TypeTag::Struct(Box::new(self.struct_tag(address)?)),
+        };
+        Ok(tag)
+    }
+
+    fn struct_tag(&mut self, address: &str) -> Result<StructTag> {
+        let hex = address.strip_prefix("0x").unwrap_or(address);
+        if hex.is_empty() || hex.len() > 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("invalid address {address:?}");
+        }
+        self.expect("::")?;
+        let module = self.identifier()?;
+        self.expect("::")?;
+        let name = self.identifier()?;
+
+        let mut tag = StructTag::new(address, module, name);
+        if self.peek() == Some('<') {
+            self.expect("<")?;
+            loop {
+                tag.type_args.push(self.type_tag()?);
+                match self.peek() {
+                    Some(',') => self.expect(",")?,
+                    _ => break,
+                }
+            }
+            self.expect(">")?;
+        }
+        Ok(tag)
+    }
+}
+
+impl fmt::Display for TypeTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TypeTag::Bool => write!(f, "bool"),
+            TypeTag::U8 => write!(f, "u8"),
+            TypeTag::U16 => write!(f, "u16"),
+            TypeTag::U32 => write!(f, "u32"),
+            TypeTag::U64 => write!(f, "u64"),
+            TypeTag::U128 => write!(f, "u128"),
+            TypeTag::U256 => write!(f, "u256"),
+            TypeTag::Address => write!(f, "address"),
+            TypeTag::Signer => write!(f, "signer"),
+            TypeTag::Vector(inner) => write!(f, "vector<{inner}>"),
+            TypeTag::Struct(tag) => write!(f, "{tag}"),
+        }
+    }
+}
+
+impl fmt::Display for StructTag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}::{}::{}",
+            format_address(&self.address),
+            self.module,
+            self.name
+        )?;
+        if !self.type_args.is_empty() {
+            let args = self
+                .type_args
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            write!(f, "<{}>", args.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LONG_ONE: &str = "0x0000000000000000000000000000000000000000000000000000000000000001";
+
+    #[test]
+    fn test_parse_struct_tag() {
+        let tag: StructTag = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>"
+            .parse()
+            .unwrap();
+        assert_eq!(tag.address, LONG_ONE);
+        assert_eq!(tag.module, "coin");
+        assert_eq!(tag.name, "CoinStore");
+        assert_eq!(
+            tag.type_args,
+            vec![TypeTag::Struct(Box::new(StructTag::new(
+                "0x1",
+                "aptos_coin",
+                "AptosCoin"
+            )))]
+        );
+
+        let long = format!("{LONG_ONE}::coin::CoinStore<{LONG_ONE}::aptos_coin::AptosCoin>");
+        let long: StructTag = long.parse().unwrap();
+        assert_eq!(tag, long);
+    }
+
+    #[test]
+    fn test_parse_nested_type_args() {
+        let s = "0xabc::pool::Pool<vector<u8>, 0x1::option::Option<0x1::string::String>>";
+        let tag: StructTag = s.parse().unwrap();
+        assert_eq!(tag.type_args.len(), 2);
+        assert_eq!(tag.type_args[0], TypeTag::Vector(Box::new(TypeTag::U8)));
+        assert_eq!(
+            tag.to_string(),
+            format!(
+                "{}::pool::Pool<vector<u8>, 0x1::option::Option<0x1::string::String>>",
+                normalize_address("0xabc")
+            )
+        );
+
+        // Whitespace around separators is tolerated.
+        let spaced: StructTag = "0x1::a::B< u64 ,vector< address > >".parse().unwrap();
+        assert_eq!(spaced.to_string(), "0x1::a::B<u64, vector<address>>");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!("u64".parse::<StructTag>().is_err());
+        assert!("0x1::coin".parse::<StructTag>().is_err());
+        assert!("0x1::coin::Coin<u64".parse::<StructTag>().is_err());
+        assert!("0x1::coin::Coin<u64>>".parse::<StructTag>().is_err());
+        assert!("0xzz::coin::Coin".parse::<StructTag>().is_err());
+        assert!("0x1::coin::Coin<>".parse::<StructTag>().is_err());
+    }
+
+    #[test]
+    fn test_matching() {
+        let tag: StructTag = "0x1::coin::CoinStore<0x1::aptos_coin::AptosCoin>"
+            .parse()
+            .unwrap();
+        assert!(tag.is_in_module(LONG_ONE, "coin"));
+        assert!(!tag.is_in_module("0x2", "coin"));
+        assert!(tag.is("0x01", "coin", "CoinStore"));
+        assert!(!tag.is("0x1", "coin", "Coin"));
+        assert!(tag.is_same_struct(&StructTag::new("0x1", "coin", "CoinStore")));
+        assert_eq!(tag.without_type_args().to_string(), "0x1::coin::CoinStore");
+    }
+
+    #[test]
+    fn test_format_address() {
+        assert_eq!(format_address(LONG_ONE), "0x1");
+        assert_eq!(format_address("0xA"), "0xa");
+        assert_eq!(format_address("0x10"), normalize_address("0x10"));
+    }
+}
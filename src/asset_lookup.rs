@@ -0,0 +1,138 @@
+//! Looks up the metadata of the assets markets are denominated in on a fullnode. The
+//! `0x1::coin::CoinInfo` and `0x1::fungible_asset::Metadata` writes that create an asset are
+//! rarely in the transactions the Kizo filter streams, and server-side filters can't match
+//! write-set resources, so the metadata is fetched the first time a market uses the asset.
+//!
+//! Enabled by pointing `ASSET_METADATA_URL` at a fullnode REST API (e.g.
+//! `https://api.testnet.aptoslabs.com/v1`). Assets already in `asset_metadata` aren't looked up
+//! again. The rows are stamped with the ledger version they were read at, so metadata streamed
+//! for older versions doesn't overwrite them.
+
+use crate::{models::AssetMetadata, schema::asset_metadata};
+use anyhow::{bail, Context, Result};
+use aptos_indexer_processor_sdk::{
+    postgres::utils::database::ArcDbPool,
+    utils::{convert::deserialize_u64_from_string_or_number, struct_tag::StructTag},
+};
+use diesel::{ExpressionMethods, QueryDsl};
+use diesel_async::RunQueryDsl;
+use serde::Deserialize;
+use serde_json::Value;
+use std::{collections::HashSet, env, time::Duration};
+use tracing::{info, warn};
+
+const COIN_INFO: &str = "0x1::coin::CoinInfo";
+const FUNGIBLE_ASSET_METADATA: &str = "0x1::fungible_asset::Metadata";
+
+#[derive(Deserialize)]
+struct MoveResource {
+    #[serde(rename = "type")]
+    resource_type: String,
+    data: Value,
+}
+
+#[derive(Deserialize)]
+struct NodeLedgerInfo {
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
+    ledger_version: u64,
+}
+
+/// Fetches the metadata of the assets in `asset_types` that are neither in `known` nor in
+/// `asset_metadata`. Like the indexer's own inserts, failures are logged and don't stop the
+/// processor; the asset is looked up again the next time a market uses it.
+pub async fn fetch_missing<'a>(
+    conn_pool: ArcDbPool,
+    asset_types: impl IntoIterator<Item = &'a str>,
+    known: &[AssetMetadata],
+) -> Vec<AssetMetadata> {
+    let Ok(node_url) = env::var("ASSET_METADATA_URL") else {
+        return vec![];
+    };
+    let mut missing: Vec<&str> = asset_types
+        .into_iter()
+        .filter(|asset_type| !known.iter().any(|asset| asset.asset_type == *asset_type))
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    if missing.is_empty() {
+        return vec![];
+    }
+    match stored_asset_types(conn_pool, &missing).await {
+        Ok(stored) => missing.retain(|asset_type| !stored.iter().any(|s| s == asset_type)),
+        Err(e) => {
+            warn!("Failed to read stored asset metadata: {e:?}");
+            return vec![];
+        },
+    }
+
+    let mut fetched = vec![];
+    for asset_type in missing {
+        match fetch(node_url.trim_end_matches('/'), asset_type).await {
+            Ok(asset) => {
+                info!(asset_type, symbol = %asset.symbol, "Fetched asset metadata");
+                fetched.push(asset);
+            },
+            Err(e) => warn!("Failed to fetch the metadata of {asset_type}: {e:?}"),
+        }
+    }
+    fetched
+}
+
+async fn stored_asset_types(conn_pool: ArcDbPool, asset_types: &[&str]) -> Result<Vec<String>> {
+    let mut conn = conn_pool
+        .get()
+        .await
+        .context("Failed to get a database connection")?;
+    Ok(asset_metadata::table
+        .select(asset_metadata::asset_type)
+        .filter(asset_metadata::asset_type.eq_any(asset_types))
+        .load(&mut conn)
+        .await?)
+}
+
+/// Reads the `CoinInfo` of a coin type from the account that declares it, or the `Metadata` of a
+/// fungible asset from its object.
+async fn fetch(node_url: &str, asset_type: &str) -> Result<AssetMetadata> {
+    let (address, resource_type) = match asset_type.parse::<StructTag>() {
+        Ok(coin_type) => (
+            coin_type.address.clone(),
+            format!("{COIN_INFO}<{coin_type}>"),
+        ),
+        Err(_) => (asset_type.to_string(), FUNGIBLE_ASSET_METADATA.to_string()),
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let ledger: NodeLedgerInfo = client
+        .get(node_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Failed to read ledger info")?;
+    let resource: MoveResource = client
+        .get(format!(
+            "{node_url}/accounts/{address}/resource/{resource_type}"
+        ))
+        .query(&[("ledger_version", ledger.ledger_version)])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("Failed to read {resource_type} at {address}"))?;
+
+    let Some(asset) = AssetMetadata::from_write_resource(
+        &address,
+        &resource.resource_type.parse()?,
+        &resource.data.to_string(),
+        ledger.ledger_version as i64,
+    ) else {
+        bail!(
+            "{} at {address} is not asset metadata",
+            resource.resource_type
+        );
+    };
+    Ok(asset)
+}
@@ -0,0 +1,549 @@
+//! Optional projection of Kizo events into the backend's Prisma tables (`users`,
+//! `markets_extended`, `bets_extended`, `fee_records`, `yield_records` and `sync_status`).
+//!
+//! Enabled with `BACKEND_PROJECTION_ENABLED=true`, in which case the indexer no longer calls the
+//! backend's full sync endpoint. Every statement reads back from the indexer's own tables, so
+//! re-processing a batch is idempotent. Rows are keyed on the same unique columns the backend
+//! uses: `users.address`, `markets_extended."blockchainMarketId"`,
+//! `bets_extended."blockchainBetId"` and `sync_status."eventType"`. Rows created by the indexer
+//! get deterministic `kizo-*` ids since Prisma only generates ids for its own inserts. Yield and
+//! fee records are keyed on their event, like `yield_deposits` and `protocol_fees`, so
+//! re-indexing a range doesn't project them twice. Deposits and fees stored without an event
+//! index keep the generated id they were projected with. Dates come from the transaction, or
+//! from when the row was indexed for rows stored before transaction timestamps were.
+
+use crate::{handle_store_error, models::VersionedEvent, KizoRows};
+use aptos_indexer_processor_sdk::{
+    postgres::utils::database::{execute_with_better_error, ArcDbPool},
+    utils::errors::ProcessorError,
+};
+use diesel::{
+    pg::Pg,
+    query_builder::{QueryFragment, QueryId},
+    sql_types::{Array, BigInt, Text},
+};
+use std::{collections::BTreeSet, env};
+use tracing::info;
+
+const UPSERT_USERS_SQL: &str = r#"
+INSERT INTO users (id, address, "createdAt", "updatedAt")
+SELECT 'kizo-user-' || address, address, NOW(), NOW()
+FROM UNNEST($1::text[]) AS address
+ON CONFLICT (address) DO NOTHING"#;
+
+const UPSERT_MARKETS_SQL: &str = r#"
+INSERT INTO markets_extended (
+    id, "blockchainMarketId", platform, question, status, probability, volume, "openInterest",
+    "endDate", "totalPoolSize", "yesPoolSize", "noPoolSize", "countYes", "countNo",
+    "currentYield", "totalYieldEarned", "createdAt", "updatedAt"
+)
+SELECT
+    'kizo-market-' || m.market_id, m.market_id, 'kizo', m.question,
+    CASE WHEN m.resolved THEN 'resolved' ELSE 'active' END, 50, 0, 0,
+    to_timestamp(m.end_time) AT TIME ZONE 'UTC', 0, 0, 0, 0, 0,
+    0, COALESCE(m.total_yield_earned, 0), COALESCE(m.transaction_timestamp, m.inserted_at), NOW()
+FROM markets m
+WHERE m.market_id = ANY($1)
+ON CONFLICT ("blockchainMarketId") DO UPDATE SET
+    question = EXCLUDED.question,
+    "endDate" = EXCLUDED."endDate",
+    "updatedAt" = NOW()"#;
+
+const UPSERT_BETS_SQL: &str = r#"
+INSERT INTO bets_extended (
+    id, "blockchainBetId", "userId", "marketId", position, amount, odds, status, "createdAt",
+    "updatedAt"
+)
+SELECT
+    'kizo-bet-' || b.bet_id, b.bet_id, u.id, me.id, b.position, b.amount, 1, 'active',
+    COALESCE(b.transaction_timestamp, b.inserted_at), NOW()
+FROM bets b
+JOIN users u ON u.address = b.user_addr
+LEFT JOIN markets_extended me ON me."blockchainMarketId" = b.market_id
+WHERE b.bet_id = ANY($1)
+ON CONFLICT ("blockchainBetId") DO UPDATE SET
+    "marketId" = EXCLUDED."marketId",
+    position = EXCLUDED.position,
+    amount = EXCLUDED.amount,
+    "updatedAt" = NOW()"#;
+
+const UPDATE_CLAIMED_BETS_SQL: &str = r#"
+UPDATE bets_extended be SET
+    status = 'claimed',
+    payout = w.winning_amount,
+    "updatedAt" = NOW()
+FROM winnings_claims w
+WHERE be."blockchainBetId" = w.bet_id AND w.bet_id = ANY($1)"#;
+
+const UPDATE_RESOLVED_MARKETS_SQL: &str = r#"
+UPDATE markets_extended me SET
+    status = 'resolved',
+    result = r.outcome,
+    "resolutionDate" = COALESCE(r.transaction_timestamp, r.inserted_at),
+    "totalYieldEarned" = r.total_yield_earned,
+    "updatedAt" = NOW()
+FROM market_resolutions r
+WHERE me."blockchainMarketId" = r.market_id AND r.market_id = ANY($1)"#;
+
+const REFRESH_MARKET_POOLS_SQL: &str = r#"
+UPDATE markets_extended me SET
+    "totalPoolSize" = COALESCE(p.total, 0),
+    "yesPoolSize" = COALESCE(p.yes, 0),
+    "noPoolSize" = COALESCE(p.no, 0),
+    "countYes" = COALESCE(p.count_yes, 0),
+    "countNo" = COALESCE(p.count_no, 0),
+    volume = COALESCE(p.total, 0),
+    "openInterest" = COALESCE(p.total, 0),
+    probability = CASE WHEN p.total > 0 THEN ROUND(p.yes * 100.0 / p.total)::int ELSE 50 END,
+    "currentYield" = COALESCE(y.deposited, 0),
+    "updatedAt" = NOW()
+FROM UNNEST($1::bigint[]) AS ids(market_id)
+LEFT JOIN (
+    SELECT
+        market_id,
+        SUM(amount) AS total,
+        COALESCE(SUM(amount) FILTER (WHERE position), 0) AS yes,
+        COALESCE(SUM(amount) FILTER (WHERE NOT position), 0) AS no,
+        COUNT(*) FILTER (WHERE position) AS count_yes,
+        COUNT(*) FILTER (WHERE NOT position) AS count_no
+    FROM bets
+    WHERE market_id = ANY($1)
+    GROUP BY market_id
+) p ON p.market_id = ids.market_id
+LEFT JOIN (
+    SELECT market_id, SUM(amount) AS deposited
+    FROM yield_deposits
+    WHERE market_id = ANY($1)
+    GROUP BY market_id
+) y ON y.market_id = ids.market_id
+WHERE me."blockchainMarketId" = ids.market_id"#;
+
+/// Deposits are only projected for protocols the backend has registered. They are matched on the
+/// backend id the yield protocol registry gives, or else by address or registry name, preferring a
+/// protocol whose id is the address.
+const INSERT_YIELD_RECORDS_SQL: &str = r#"
+INSERT INTO yield_records (id, "marketId", "protocolId", amount, apy, "yield", period, "createdAt")
+SELECT
+    'kizo-yield-' || COALESCE(d.transaction_version || '-' || d.event_index, d.deposit_id::text),
+    me.id, p.id, d.amount, p."baseApy", 0, COALESCE(d.transaction_timestamp, d.inserted_at),
+    COALESCE(d.transaction_timestamp, d.inserted_at)
+FROM yield_deposits d
+JOIN markets_extended me ON me."blockchainMarketId" = d.market_id
+LEFT JOIN yield_protocols y ON y.address = d.protocol_addr
+JOIN LATERAL (
+    SELECT p.id, p."baseApy"
+    FROM protocols p
+    WHERE p.id = COALESCE(y.backend_protocol_id, d.protocol_addr)
+        OR (y.backend_protocol_id IS NULL AND p.name = COALESCE(y.name, d.protocol_addr))
+    ORDER BY p.id = COALESCE(y.backend_protocol_id, d.protocol_addr) DESC, p.id
+    LIMIT 1
+) p ON true
+WHERE d.transaction_version = ANY($1)
+ON CONFLICT (id) DO NOTHING"#;
+
+const INSERT_FEE_RECORDS_SQL: &str = r#"
+INSERT INTO fee_records (id, "marketId", "feeType", amount, source, "createdAt")
+SELECT
+    'kizo-fee-' || COALESCE(f.transaction_version || '-' || f.event_index, f.fee_id::text), me.id,
+    'protocol', f.fee_amount, 'indexer', COALESCE(f.transaction_timestamp, f.inserted_at)
+FROM protocol_fees f
+LEFT JOIN markets_extended me ON me."blockchainMarketId" = f.market_id
+WHERE f.transaction_version = ANY($1)
+ON CONFLICT (id) DO NOTHING"#;
+
+const UPSERT_SYNC_STATUS_SQL: &str = r#"
+INSERT INTO sync_status (
+    id, "eventType", "lastSyncBlock", "lastSyncTime", "isActive", "createdAt", "updatedAt"
+)
+SELECT 'kizo-' || event_type, event_type, block, NOW(), true, NOW(), NOW()
+FROM UNNEST($1::text[], $2::bigint[]) AS t(event_type, block)
+ON CONFLICT ("eventType") DO UPDATE SET
+    "lastSyncBlock" = GREATEST(sync_status."lastSyncBlock", EXCLUDED."lastSyncBlock"),
+    "lastSyncTime" = NOW(),
+    "updatedAt" = NOW()"#;
+
+pub fn is_enabled() -> bool {
+    env::var("BACKEND_PROJECTION_ENABLED")
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(false)
+}
+
+/// Projects a processed batch into the backend tables. Like the indexer's own inserts, failures
+/// are logged, and connection failures fail the batch so it's retried.
+pub async fn project(conn_pool: ArcDbPool, rows: &KizoRows) -> Result<(), ProcessorError> {
+    let user_addrs = unique(rows.onchain_users.iter().map(|u| u.address.clone()));
+    let created_market_ids = unique(rows.markets.iter().map(|m| m.market_id));
+    let bet_ids = unique(rows.bets.iter().map(|b| b.bet_id));
+    let claimed_bet_ids = unique(rows.winnings_claims.iter().map(|w| w.bet_id));
+    let resolved_market_ids = unique(rows.market_resolutions.iter().map(|r| r.market_id));
+    let pool_market_ids = unique(
+        rows.bets
+            .iter()
+            .map(|b| b.market_id)
+            .chain(rows.yield_deposits.iter().map(|d| d.market_id)),
+    );
+    let yield_versions = unique(rows.yield_deposits.iter().map(|d| d.transaction_version));
+    let fee_versions = unique(rows.protocol_fees.iter().map(|f| f.transaction_version));
+
+    let bind_ids =
+        |sql: &'static str, ids: Vec<i64>| diesel::sql_query(sql).bind::<Array<BigInt>, _>(ids);
+
+    // Order matters: bets look up users and markets, aggregates read the projected markets.
+    if !user_addrs.is_empty() {
+        let query = diesel::sql_query(UPSERT_USERS_SQL).bind::<Array<Text>, _>(user_addrs);
+        run(conn_pool.clone(), "users", query).await?;
+    }
+    if !created_market_ids.is_empty() {
+        let query = bind_ids(UPSERT_MARKETS_SQL, created_market_ids);
+        run(conn_pool.clone(), "markets_extended", query).await?;
+    }
+    if !bet_ids.is_empty() {
+        run(
+            conn_pool.clone(),
+            "bets_extended",
+            bind_ids(UPSERT_BETS_SQL, bet_ids),
+        )
+        .await?;
+    }
+    if !claimed_bet_ids.is_empty() {
+        let query = bind_ids(UPDATE_CLAIMED_BETS_SQL, claimed_bet_ids);
+        run(conn_pool.clone(), "bets_extended claims", query).await?;
+    }
+    if !resolved_market_ids.is_empty() {
+        let query = bind_ids(UPDATE_RESOLVED_MARKETS_SQL, resolved_market_ids);
+        run(conn_pool.clone(), "markets_extended resolutions", query).await?;
+    }
+    if !pool_market_ids.is_empty() {
+        let query = bind_ids(REFRESH_MARKET_POOLS_SQL, pool_market_ids);
+        run(conn_pool.clone(), "markets_extended pools", query).await?;
+    }
+    if !yield_versions.is_empty() {
+        let query = bind_ids(INSERT_YIELD_RECORDS_SQL, yield_versions);
+        run(conn_pool.clone(), "yield_records", query).await?;
+    }
+    if !fee_versions.is_empty() {
+        let query = bind_ids(INSERT_FEE_RECORDS_SQL, fee_versions);
+        run(conn_pool.clone(), "fee_records", query).await?;
+    }
+
+    let (event_types, blocks): (Vec<String>, Vec<i64>) = last_sync_blocks(rows).into_iter().unzip();
+    if !event_types.is_empty() {
+        let query = diesel::sql_query(UPSERT_SYNC_STATUS_SQL)
+            .bind::<Array<Text>, _>(event_types)
+            .bind::<Array<BigInt>, _>(blocks);
+        run(conn_pool, "sync_status", query).await?;
+    }
+
+    Ok(())
+}
+
+/// Highest block height seen in this batch for each event type.
+fn last_sync_blocks(rows: &KizoRows) -> Vec<(String, i64)> {
+    use crate::models::{
+        BetPlacedEvent, MarketCreatedEvent, MarketResolvedEvent, ProtocolFeeCollectedEvent,
+        WinningsClaimedEvent, YieldDepositedEvent,
+    };
+
+    [
+        (
+            MarketCreatedEvent::EVENT_NAME,
+            rows.markets
+                .iter()
+                .map(|r| r.transaction_block_height)
+                .max(),
+        ),
+        (
+            BetPlacedEvent::EVENT_NAME,
+            rows.bets.iter().map(|r| r.transaction_block_height).max(),
+        ),
+        (
+            MarketResolvedEvent::EVENT_NAME,
+            rows.market_resolutions
+                .iter()
+                .map(|r| r.transaction_block_height)
+                .max(),
+        ),
+        (
+            WinningsClaimedEvent::EVENT_NAME,
+            rows.winnings_claims
+                .iter()
+                .map(|r| r.transaction_block_height)
+                .max(),
+        ),
+        (
+            YieldDepositedEvent::EVENT_NAME,
+            rows.yield_deposits
+                .iter()
+                .map(|r| r.transaction_block_height)
+                .max(),
+        ),
+        (
+            ProtocolFeeCollectedEvent::EVENT_NAME,
+            rows.protocol_fees
+                .iter()
+                .map(|r| r.transaction_block_height)
+                .max(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(event_type, block)| Some((event_type.to_string(), block?)))
+    .collect()
+}
+
+fn unique<T: Ord>(items: impl Iterator<Item = T>) -> Vec<T> {
+    items.collect::<BTreeSet<_>>().into_iter().collect()
+}
+
+async fn run<Q>(conn_pool: ArcDbPool, target: &str, query: Q) -> Result<(), ProcessorError>
+where
+    Q: QueryFragment<Pg> + QueryId + Send,
+{
+    match execute_with_better_error(conn_pool, query).await {
+        Ok(count) => {
+            info!("Projected {} rows into {}", count, target);
+            Ok(())
+        },
+        Err(e) => handle_store_error(&format!("the {target} projection"), e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        models::*,
+        test_db::{batch_execute, lines, migrated_db},
+        MARKET_CREATED_EVENT,
+    };
+    use ahash::AHashMap;
+    use aptos_indexer_processor_sdk::postgres::utils::writer::{write_bundle, RowBundle};
+    use serde_json::{json, Value};
+
+    /// The columns of the backend's Prisma tables the projection writes.
+    const BACKEND_TABLES_SQL: &str = r#"
+CREATE TABLE users (
+    id TEXT PRIMARY KEY, address TEXT NOT NULL UNIQUE, "createdAt" TIMESTAMP NOT NULL,
+    "updatedAt" TIMESTAMP NOT NULL
+);
+CREATE TABLE markets_extended (
+    id TEXT PRIMARY KEY, "blockchainMarketId" BIGINT UNIQUE, platform TEXT, question TEXT,
+    status TEXT, probability INT, volume BIGINT, "openInterest" BIGINT, "endDate" TIMESTAMP,
+    "totalPoolSize" BIGINT, "yesPoolSize" BIGINT, "noPoolSize" BIGINT, "countYes" INT,
+    "countNo" INT, "currentYield" BIGINT, "totalYieldEarned" BIGINT, result BOOLEAN,
+    "resolutionDate" TIMESTAMP, "createdAt" TIMESTAMP, "updatedAt" TIMESTAMP
+);
+CREATE TABLE bets_extended (
+    id TEXT PRIMARY KEY, "blockchainBetId" BIGINT UNIQUE, "userId" TEXT, "marketId" TEXT,
+    position BOOLEAN, amount BIGINT, odds NUMERIC, status TEXT, payout BIGINT,
+    "createdAt" TIMESTAMP, "updatedAt" TIMESTAMP
+);
+CREATE TABLE protocols (id TEXT PRIMARY KEY, name TEXT, "baseApy" DOUBLE PRECISION);
+CREATE TABLE yield_records (
+    id TEXT PRIMARY KEY, "marketId" TEXT, "protocolId" TEXT, amount BIGINT,
+    apy DOUBLE PRECISION, "yield" BIGINT, period TIMESTAMP, "createdAt" TIMESTAMP
+);
+CREATE TABLE fee_records (
+    id TEXT PRIMARY KEY, "marketId" TEXT, "feeType" TEXT, amount BIGINT, source TEXT,
+    "createdAt" TIMESTAMP
+);
+CREATE TABLE sync_status (
+    id TEXT PRIMARY KEY, "eventType" TEXT UNIQUE, "lastSyncBlock" BIGINT,
+    "lastSyncTime" TIMESTAMP, "isActive" BOOLEAN, "createdAt" TIMESTAMP, "updatedAt" TIMESTAMP
+);
+INSERT INTO protocols VALUES ('aries', 'Aries', 5.5), ('aries-legacy', 'Aries', 3.0);
+INSERT INTO yield_protocols (address, name, backend_protocol_id, updated_at)
+VALUES ('0x00000000000000000000000000000000000000000000000000000000000000a1', 'Aries', 'aries', NOW());
+"#;
+
+    const USER: &str = "0x00000000000000000000000000000000000000000000000000000000000000b0";
+    const PROTOCOL: &str = "0x00000000000000000000000000000000000000000000000000000000000000a1";
+
+    fn timestamp(secs: i64) -> chrono::NaiveDateTime {
+        chrono::DateTime::from_timestamp(secs, 0)
+            .unwrap()
+            .naive_utc()
+    }
+
+    fn event<T: VersionedEvent>(data: Value) -> T {
+        serde_json::from_value(data).unwrap()
+    }
+
+    /// A market created at version 100, bet on at 200, deposited into and charged a fee at 300,
+    /// resolved at 400 and claimed at 500. Each version's timestamp is its version in seconds.
+    fn rows() -> KizoRows {
+        KizoRows {
+            markets: vec![Market::from_event(
+                &event(json!({
+                    "market_id": "1",
+                    "question": "Will it rain?",
+                    "end_time": "1000",
+                    "yield_protocol_addr": PROTOCOL,
+                })),
+                &MARKET_CREATED_EVENT.parse().unwrap(),
+                Some(USER),
+                100,
+                10,
+                timestamp(100),
+            )],
+            bets: vec![Bet::from_event(
+                &event(json!({
+                    "bet_id": "7",
+                    "market_id": "1",
+                    "user": USER,
+                    "position": true,
+                    "amount": "400",
+                })),
+                200,
+                20,
+                timestamp(200),
+            )],
+            yield_deposits: vec![NewYieldDeposit::from_event(
+                &event(json!({"market_id": "1", "amount": "40", "protocol_addr": PROTOCOL})),
+                300,
+                0,
+                30,
+                timestamp(300),
+            )],
+            protocol_fees: vec![NewProtocolFee::from_event(
+                &event(json!({"market_id": "1", "fee_amount": "4"})),
+                300,
+                1,
+                30,
+                timestamp(300),
+            )],
+            market_resolutions: vec![MarketResolution::from_event(
+                &event(json!({"market_id": "1", "outcome": true, "total_yield_earned": "12"})),
+                400,
+                40,
+                timestamp(400),
+            )],
+            winnings_claims: vec![NewWinningsClaim::from_event(
+                &event(json!({
+                    "bet_id": "7",
+                    "user": USER,
+                    "winning_amount": "412",
+                    "yield_share": "12",
+                })),
+                500,
+                0,
+                50,
+                timestamp(500),
+            )],
+            onchain_users: vec![OnchainUser::from_activity(
+                USER,
+                UserActivity::Bet,
+                200,
+                timestamp(200),
+            )],
+            ..Default::default()
+        }
+    }
+
+    /// Stores `rows` the way `process_batch` does, then projects them.
+    async fn index(pool: &ArcDbPool, rows: &KizoRows) {
+        let bundle = RowBundle::new()
+            .with(rows.markets.clone())
+            .with(rows.bets.clone())
+            .with(rows.market_resolutions.clone())
+            .with(rows.winnings_claims.clone())
+            .with(rows.yield_deposits.clone())
+            .with(rows.protocol_fees.clone());
+        write_bundle(pool.clone(), &bundle, &AHashMap::new())
+            .await
+            .unwrap();
+        project(pool.clone(), rows).await.unwrap();
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_projection() {
+        let (_database, pool) = migrated_db().await;
+        batch_execute(&pool, BACKEND_TABLES_SQL).await;
+        let rows = rows();
+        index(&pool, &rows).await;
+
+        let markets = r#"
+SELECT concat_ws(' ', id, status, result, "totalPoolSize", "yesPoolSize", "countYes",
+    "currentYield", "totalYieldEarned", "createdAt", "resolutionDate") AS line
+FROM markets_extended"#;
+        assert_eq!(
+            lines(&pool, markets).await,
+            vec![
+                "kizo-market-1 resolved t 400 400 1 40 12 1970-01-01 00:01:40 1970-01-01 00:06:40"
+            ]
+        );
+
+        let bets = r#"
+SELECT concat_ws(' ', id, "userId", "marketId", status, payout, "createdAt") AS line
+FROM bets_extended"#;
+        assert_eq!(
+            lines(&pool, bets).await,
+            vec![format!(
+                "kizo-bet-7 kizo-user-{USER} kizo-market-1 claimed 412 1970-01-01 00:03:20"
+            )]
+        );
+
+        let yields = r#"
+SELECT concat_ws(' ', id, "marketId", "protocolId", amount, apy, period, "createdAt") AS line
+FROM yield_records"#;
+        assert_eq!(
+            lines(&pool, yields).await,
+            vec![
+                "kizo-yield-300-0 kizo-market-1 aries 40 5.5 1970-01-01 00:05:00 \
+                 1970-01-01 00:05:00"
+            ]
+        );
+
+        let fees = r#"
+SELECT concat_ws(' ', id, "marketId", amount, "createdAt") AS line FROM fee_records"#;
+        assert_eq!(
+            lines(&pool, fees).await,
+            vec!["kizo-fee-300-1 kizo-market-1 4 1970-01-01 00:05:00"]
+        );
+
+        let sync_status = r#"
+SELECT concat_ws(' ', "eventType", "lastSyncBlock") AS line
+FROM sync_status ORDER BY "eventType""#;
+        assert_eq!(
+            lines(&pool, sync_status).await,
+            vec![
+                "BetPlacedEvent 20",
+                "MarketCreatedEvent 10",
+                "MarketResolvedEvent 40",
+                "ProtocolFeeCollectedEvent 30",
+                "WinningsClaimedEvent 50",
+                "YieldDepositedEvent 30",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_reprojection_is_idempotent() {
+        let (_database, pool) = migrated_db().await;
+        batch_execute(&pool, BACKEND_TABLES_SQL).await;
+        // A second deposit into the same market in the same transaction is its own record
+        let mut rows = rows();
+        rows.yield_deposits.push(NewYieldDeposit::from_event(
+            &event(json!({"market_id": "1", "amount": "10", "protocol_addr": PROTOCOL})),
+            300,
+            2,
+            30,
+            timestamp(300),
+        ));
+        index(&pool, &rows).await;
+
+        let counts = r#"
+SELECT concat_ws(' ',
+    (SELECT COUNT(*) FROM users), (SELECT COUNT(*) FROM markets_extended),
+    (SELECT COUNT(*) FROM bets_extended), (SELECT COUNT(*) FROM yield_records),
+    (SELECT COUNT(*) FROM fee_records), (SELECT "totalPoolSize" FROM markets_extended),
+    (SELECT "currentYield" FROM markets_extended)) AS line"#;
+        let before = lines(&pool, counts).await;
+        assert_eq!(before, vec!["1 1 1 2 1 400 50"]);
+
+        // Replaying the range re-indexes the same events, with fresh `inserted_at`s
+        index(&pool, &rows).await;
+        assert_eq!(lines(&pool, counts).await, before);
+    }
+}
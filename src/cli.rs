@@ -0,0 +1,432 @@
+//! Command line interface of the `kizo-indexer` binary. Running it without a subcommand is the
+//! same as `run`, so existing deployments keep working.
+
+use crate::{
+    process_batch, rewind, schema, starting_version, transaction_filter,
+    yield_protocols::{self, YieldProtocolRegistry},
+    MIGRATIONS, PROCESSOR_NAME,
+};
+use anyhow::{bail, Context, Result};
+use aptos_indexer_processor_sdk::{
+    aptos_indexer_transaction_stream::TransactionStreamConfig,
+    postgres::{
+        basic_processor::{process_with_config, run_processor, ProcessConfig},
+        models::{ledger_info::LedgerInfo, processor_status::ProcessorStatusQuery},
+        processor_metadata_schema::processor_metadata::processor_status,
+        utils::{
+            database::{
+                execute_with_better_error, new_db_pool, run_migrations, ArcDbPool, DbPoolConnection,
+            },
+            scheduled_rewind::{clear_scheduled_rewind, get_scheduled_rewind},
+        },
+        SDK_MIGRATIONS,
+    },
+    server_framework::{load, setup_logging, setup_panic_handler, GenericConfig},
+    utils::shutdown::ShutdownSignal,
+};
+use clap::{Parser, Subcommand};
+use diesel::{sql_types::BigInt, ExpressionMethods, QueryDsl, QueryableByName};
+use diesel_async::RunQueryDsl;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+use tracing::{error, info, warn};
+
+#[derive(Parser)]
+#[clap(author, version, about)]
+pub struct Cli {
+    #[clap(short, long, value_parser, default_value = "config.yaml")]
+    pub config_path: PathBuf,
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Index transactions from the stream, resuming from the last checkpoint.
+    Run,
+    /// Apply the Kizo and SDK migrations, then exit.
+    Migrate,
+    /// Print the processor checkpoint, chain id and lag.
+    Status,
+    /// Roll the Kizo tables back to a version and reset the checkpoint to it, in one transaction.
+    Rewind {
+        #[clap(long)]
+        to_version: u64,
+    },
+    /// Check that the Kizo tables are consistent with each other and with the checkpoint.
+    Verify,
+    /// Re-index a version range under a separate checkpoint, leaving the main one untouched.
+    /// Rows are keyed on their market, bet, event or resource, so rows already indexed are not
+    /// stored again. `onchain_users` counters skip activity below a user's last seen version, so
+    /// a gap is only counted for users not seen after it; rewind to before the gap to fix those.
+    Replay {
+        #[clap(long)]
+        from_version: u64,
+        #[clap(long)]
+        to_version: u64,
+    },
+}
+
+impl Cli {
+    pub async fn run(self) -> Result<()> {
+        setup_logging();
+        setup_panic_handler();
+        let mut config = load::<GenericConfig<ProcessConfig>>(&self.config_path)?;
+
+        match self.command.unwrap_or(Command::Run) {
+            Command::Run => {
+                starting_version::discover_if_needed(&mut config.server_config).await?;
+                transaction_filter::apply(&mut config.server_config.transaction_stream_config)?;
+                sync_yield_protocols(&self.config_path, &config.server_config).await?;
+                apply_scheduled_rewind(&config.server_config).await?;
+                let per_table_chunk_sizes = Arc::new(
+                    config
+                        .server_config
+                        .postgres_config
+                        .per_table_chunk_sizes
+                        .clone(),
+                );
+                let process_function = move |transactions, conn_pool| {
+                    process_batch(transactions, conn_pool, per_table_chunk_sizes.clone())
+                };
+                process_with_config(
+                    PROCESSOR_NAME.to_string(),
+                    config,
+                    MIGRATIONS,
+                    process_function,
+                )
+                .await
+            },
+            Command::Migrate => migrate(&config.server_config).await,
+            Command::Status => status(&config.server_config).await,
+            Command::Rewind { to_version } => rewind(&config.server_config, to_version).await,
+            Command::Verify => verify(&config.server_config).await,
+            Command::Replay {
+                from_version,
+                to_version,
+            } => {
+                sync_yield_protocols(&self.config_path, &config.server_config).await?;
+                let shutdown_timeout = Duration::from_secs(config.shutdown_timeout_secs);
+                replay(
+                    config.server_config,
+                    from_version,
+                    to_version,
+                    shutdown_timeout,
+                )
+                .await
+            },
+        }
+    }
+}
+
+async fn connect(config: &ProcessConfig) -> Result<ArcDbPool> {
+    new_db_pool(
+        &config.postgres_config.connection_string,
+        Some(config.postgres_config.db_pool_size),
+    )
+    .await
+    .context("Failed to create connection pool")
+}
+
+async fn get_conn(pool: &ArcDbPool) -> Result<DbPoolConnection<'_>> {
+    pool.get()
+        .await
+        .context("Failed to get a database connection")
+}
+
+/// Syncs the yield protocol registry from the config file. Applies the Kizo migrations first,
+/// since the processor only does so once it starts.
+async fn sync_yield_protocols(config_path: &Path, config: &ProcessConfig) -> Result<()> {
+    let registry = load::<YieldProtocolRegistry>(&config_path.to_path_buf())?;
+    let pool = connect(config).await?;
+    let connection_string = config.postgres_config.connection_string.clone();
+    run_migrations(connection_string, pool.clone(), MIGRATIONS).await;
+    yield_protocols::sync(&pool, registry).await
+}
+
+async fn migrate(config: &ProcessConfig) -> Result<()> {
+    let pool = connect(config).await?;
+    let connection_string = config.postgres_config.connection_string.clone();
+    run_migrations(connection_string.clone(), pool.clone(), MIGRATIONS).await;
+    run_migrations(connection_string, pool, SDK_MIGRATIONS).await;
+    info!("Migrations applied");
+    Ok(())
+}
+
+async fn status(config: &ProcessConfig) -> Result<()> {
+    let pool = connect(config).await?;
+    let mut conn = get_conn(&pool).await?;
+
+    let chain_id = LedgerInfo::get(&mut conn).await?.map(|l| l.chain_id);
+    println!(
+        "chain id:         {}",
+        chain_id.map_or("unknown".to_string(), |id| id.to_string())
+    );
+
+    let Some(status) = ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn).await?
+    else {
+        println!("processor:        {PROCESSOR_NAME} (no checkpoint yet)");
+        return Ok(());
+    };
+    println!("processor:        {}", status.processor);
+    println!("last version:     {}", status.last_success_version);
+    println!("last updated:     {}", status.last_updated);
+    match status.last_transaction_timestamp {
+        Some(timestamp) => {
+            let lag = chrono::Utc::now().naive_utc() - timestamp;
+            println!("last txn time:    {timestamp}");
+            println!("lag:              {}s", lag.num_seconds());
+        },
+        None => println!("lag:              unknown"),
+    }
+    Ok(())
+}
+
+async fn rewind(config: &ProcessConfig, to_version: u64) -> Result<()> {
+    let pool = connect(config).await?;
+    let version = to_version as i64;
+    let mut conn = get_conn(&pool).await?;
+    if let Some(status) = ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn).await? {
+        if status.last_success_version < version {
+            bail!(
+                "Checkpoint {} is already below {version}, nothing to rewind",
+                status.last_success_version
+            );
+        }
+    }
+    drop(conn);
+
+    for (step, count) in rewind::rewind_to(&pool, version).await? {
+        info!("{step}: {count}");
+    }
+    info!("Rewound to version {version}");
+    Ok(())
+}
+
+/// Applies a rewind scheduled through the admin API with `rewind::rewind_to`, so the Kizo tables
+/// are rolled back too rather than only the checkpoint.
+async fn apply_scheduled_rewind(config: &ProcessConfig) -> Result<()> {
+    let pool = connect(config).await?;
+    // The table scheduled rewinds are kept in comes with the SDK migrations
+    let connection_string = config.postgres_config.connection_string.clone();
+    run_migrations(connection_string, pool.clone(), SDK_MIGRATIONS).await;
+    rewind_if_scheduled(&pool).await
+}
+
+/// Rewinds to the scheduled version, unless the checkpoint is already below it, and clears the
+/// schedule.
+async fn rewind_if_scheduled(pool: &ArcDbPool) -> Result<()> {
+    let Some(to_version) = get_scheduled_rewind(PROCESSOR_NAME, pool).await? else {
+        return Ok(());
+    };
+    let version = to_version as i64;
+
+    let mut conn = get_conn(pool).await?;
+    let status = ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn).await?;
+    drop(conn);
+    match status {
+        Some(status) if status.last_success_version < version => warn!(
+            "Dropped the scheduled rewind to version {version}, checkpoint {} is already below it",
+            status.last_success_version
+        ),
+        _ => {
+            for (step, count) in rewind::rewind_to(pool, version).await? {
+                info!("{step}: {count}");
+            }
+            info!("Applied the scheduled rewind to version {version}");
+        },
+    }
+    clear_scheduled_rewind(PROCESSOR_NAME, pool).await
+}
+
+#[derive(QueryableByName)]
+struct Count {
+    #[diesel(sql_type = BigInt)]
+    count: i64,
+}
+
+/// `current_market_state` rows whose version differs from the latest history entry.
+const STALE_MARKET_STATE_SQL: &str = r#"
+SELECT COUNT(*) AS count
+FROM current_market_state c
+JOIN (
+    SELECT resource_address, resource_type, MAX(transaction_version) AS transaction_version
+    FROM market_state_history
+    GROUP BY resource_address, resource_type
+) h USING (resource_address, resource_type)
+WHERE c.last_transaction_version <> h.transaction_version"#;
+
+/// Bettors and claimers that are missing from `onchain_users`.
+const MISSING_USERS_SQL: &str = r#"
+SELECT COUNT(DISTINCT addr) AS count
+FROM (
+    SELECT user_addr AS addr FROM bets
+    UNION
+    SELECT user_addr AS addr FROM winnings_claims
+) a
+LEFT JOIN onchain_users u ON u.address = a.addr
+WHERE u.address IS NULL"#;
+
+async fn verify(config: &ProcessConfig) -> Result<()> {
+    let pool = connect(config).await?;
+    let mut conn = get_conn(&pool).await?;
+    let checkpoint = ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn)
+        .await?
+        .map_or(-1, |s| s.last_success_version);
+
+    let mut checks = vec![];
+    macro_rules! check_above_checkpoint {
+        ($table:ident) => {{
+            let count: i64 = schema::$table::table
+                .filter(schema::$table::transaction_version.gt(checkpoint))
+                .count()
+                .get_result(&mut conn)
+                .await?;
+            checks.push((
+                format!("{} rows above checkpoint {checkpoint}", stringify!($table)),
+                count,
+            ));
+        }};
+    }
+    check_above_checkpoint!(markets);
+    check_above_checkpoint!(bets);
+    check_above_checkpoint!(market_resolutions);
+    check_above_checkpoint!(winnings_claims);
+    check_above_checkpoint!(yield_deposits);
+    check_above_checkpoint!(protocol_fees);
+    check_above_checkpoint!(market_state_history);
+
+    for (description, sql) in [
+        (
+            "current_market_state rows out of date with market_state_history",
+            STALE_MARKET_STATE_SQL,
+        ),
+        (
+            "bettors and claimers missing from onchain_users",
+            MISSING_USERS_SQL,
+        ),
+    ] {
+        let Count { count } = diesel::sql_query(sql).get_result(&mut conn).await?;
+        checks.push((description.to_string(), count));
+    }
+
+    for (description, count) in &checks {
+        let result = if *count == 0 { "ok" } else { "FAILED" };
+        println!("{result:<8} {description}: {count}");
+    }
+    let failed = checks.iter().filter(|(_, count)| *count != 0).count();
+    if failed > 0 {
+        bail!("{failed} verification checks failed");
+    }
+    Ok(())
+}
+
+async fn replay(
+    mut config: ProcessConfig,
+    from_version: u64,
+    to_version: u64,
+    shutdown_timeout: Duration,
+) -> Result<()> {
+    if from_version > to_version {
+        bail!("--from-version {from_version} is above --to-version {to_version}");
+    }
+    transaction_filter::apply(&mut config.transaction_stream_config)?;
+    let processor_name = format!("{PROCESSOR_NAME}_replay");
+
+    // Drop the checkpoint of any previous replay so the range below is used as is.
+    let pool = connect(&config).await?;
+    execute_with_better_error(
+        pool,
+        diesel::delete(
+            processor_status::table.filter(processor_status::processor.eq(&processor_name)),
+        ),
+    )
+    .await?;
+
+    info!("Replaying versions [{from_version}, {to_version}] as {processor_name}");
+    let shutdown_signal = ShutdownSignal::new();
+    shutdown_signal.trigger_on_termination();
+    let per_table_chunk_sizes = Arc::new(config.postgres_config.per_table_chunk_sizes.clone());
+    let process_function = move |transactions, conn_pool| {
+        process_batch(transactions, conn_pool, per_table_chunk_sizes.clone())
+    };
+    let replay = run_processor(
+        processor_name,
+        ProcessConfig {
+            transaction_stream_config: TransactionStreamConfig {
+                starting_version: Some(from_version),
+                request_ending_version: Some(to_version),
+                ..config.transaction_stream_config
+            },
+            ..config
+        },
+        MIGRATIONS,
+        process_function,
+        shutdown_signal.clone(),
+    );
+    tokio::select! {
+        res = replay => res,
+        _ = shutdown_signal.deadline(shutdown_timeout) => {
+            error!("Replay did not drain within {}s. Exiting anyway.", shutdown_timeout.as_secs());
+            Ok(())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db::{batch_execute, lines, migrated_db};
+    use aptos_indexer_processor_sdk::{
+        admin_api::RewindScheduler, postgres::utils::scheduled_rewind::PostgresRewindScheduler,
+    };
+
+    /// A market created at version 100 with a bet at 300, indexed up to version 500.
+    const INDEXED_SQL: &str = r#"
+INSERT INTO markets (
+    market_id, question, end_time, yield_protocol_addr, transaction_version,
+    transaction_block_height, inserted_at, asset_type
+)
+VALUES (1, 'Will it rain?', 1000, '0xa1', 100, 10, NOW(), '0x1::aptos_coin::AptosCoin');
+INSERT INTO bets (
+    bet_id, market_id, user_addr, position, amount, transaction_version,
+    transaction_block_height, inserted_at
+)
+VALUES (7, 1, '0xb0', true, 400, 300, 30, NOW());
+INSERT INTO processor_metadata.processor_status (processor, last_success_version)
+VALUES ('kizo_prediction_market_indexer', 500);
+"#;
+
+    const STATE_SQL: &str = r#"
+SELECT concat_ws(' ',
+    (SELECT last_success_version FROM processor_metadata.processor_status),
+    (SELECT COUNT(*) FROM markets), (SELECT COUNT(*) FROM bets)) AS line"#;
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_rewind_if_scheduled() {
+        let (_database, pool) = migrated_db().await;
+        batch_execute(&pool, INDEXED_SQL).await;
+        let scheduler = PostgresRewindScheduler::new(PROCESSOR_NAME, pool.clone());
+
+        rewind_if_scheduled(&pool).await.unwrap();
+        assert_eq!(lines(&pool, STATE_SQL).await, vec!["500 1 1"]);
+
+        // Rolls the Kizo tables back along with the checkpoint
+        scheduler.schedule_rewind(200).await.unwrap();
+        rewind_if_scheduled(&pool).await.unwrap();
+        assert_eq!(lines(&pool, STATE_SQL).await, vec!["200 1 0"]);
+        let scheduled = get_scheduled_rewind(PROCESSOR_NAME, &pool).await.unwrap();
+        assert_eq!(scheduled, None);
+
+        // Dropped once the checkpoint is below the version
+        scheduler.schedule_rewind(300).await.unwrap();
+        rewind_if_scheduled(&pool).await.unwrap();
+        assert_eq!(lines(&pool, STATE_SQL).await, vec!["200 1 0"]);
+        let scheduled = get_scheduled_rewind(PROCESSOR_NAME, &pool).await.unwrap();
+        assert_eq!(scheduled, None);
+    }
+}
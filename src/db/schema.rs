@@ -14,6 +14,11 @@ diesel::table! {
         outcome -> Nullable<Bool>,
         total_yield_earned -> Nullable<Int8>,
         resolution_transaction_version -> Nullable<Int8>,
+        extra -> Nullable<Jsonb>,
+        #[max_length = 66]
+        creator_addr -> Nullable<Varchar>,
+        asset_type -> Text,
+        transaction_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -32,6 +37,8 @@ diesel::table! {
         winning_amount -> Nullable<Int8>,
         yield_share -> Nullable<Int8>,
         claim_transaction_version -> Nullable<Int8>,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -43,6 +50,8 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -57,6 +66,9 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
     }
 }
 
@@ -70,6 +82,9 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
     }
 }
 
@@ -81,6 +96,108 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    current_market_state (resource_address, resource_type) {
+        #[max_length = 66]
+        resource_address -> Varchar,
+        resource_type -> Text,
+        market_id -> Nullable<Int8>,
+        total_pool -> Nullable<Int8>,
+        yes_pool -> Nullable<Int8>,
+        no_pool -> Nullable<Int8>,
+        yield_deposited -> Nullable<Int8>,
+        resolved -> Nullable<Bool>,
+        data -> Nullable<Jsonb>,
+        is_deleted -> Bool,
+        last_transaction_version -> Int8,
+        last_transaction_block_height -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    market_state_history (transaction_version, write_set_change_index) {
+        transaction_version -> Int8,
+        write_set_change_index -> Int8,
+        #[max_length = 66]
+        resource_address -> Varchar,
+        resource_type -> Text,
+        market_id -> Nullable<Int8>,
+        total_pool -> Nullable<Int8>,
+        yes_pool -> Nullable<Int8>,
+        no_pool -> Nullable<Int8>,
+        yield_deposited -> Nullable<Int8>,
+        resolved -> Nullable<Bool>,
+        data -> Nullable<Jsonb>,
+        is_deleted -> Bool,
+        transaction_block_height -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    onchain_users (address) {
+        #[max_length = 66]
+        address -> Varchar,
+        first_seen_version -> Int8,
+        first_seen_timestamp -> Timestamp,
+        last_seen_version -> Int8,
+        last_seen_timestamp -> Timestamp,
+        activity_count -> Int8,
+        bet_count -> Int8,
+        claim_count -> Int8,
+        markets_created_count -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    asset_metadata (asset_type) {
+        asset_type -> Text,
+        #[max_length = 10]
+        standard -> Varchar,
+        name -> Text,
+        symbol -> Text,
+        decimals -> Int4,
+        last_transaction_version -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    protocol_snapshots (period, bucket_start, yield_protocol_addr, asset_type) {
+        #[max_length = 4]
+        period -> Varchar,
+        bucket_start -> Timestamp,
+        #[max_length = 66]
+        yield_protocol_addr -> Varchar,
+        asset_type -> Text,
+        tvl -> Numeric,
+        volume -> Numeric,
+        bet_count -> Int8,
+        active_market_count -> Int8,
+        unique_bettor_count -> Int8,
+        yield_deposited -> Numeric,
+        fees_collected -> Numeric,
+        updated_at -> Timestamp,
+    }
+}
+
+diesel::table! {
+    yield_protocols (address) {
+        #[max_length = 66]
+        address -> Varchar,
+        name -> Text,
+        backend_protocol_id -> Nullable<Text>,
+        updated_at -> Timestamp,
     }
 }
 
@@ -97,4 +214,10 @@ diesel::allow_tables_to_appear_in_same_query!(
     winnings_claims,
     yield_deposits,
     protocol_fees,
+    current_market_state,
+    market_state_history,
+    onchain_users,
+    asset_metadata,
+    protocol_snapshots,
+    yield_protocols,
 );
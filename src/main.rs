@@ -1,28 +1,52 @@
-use anyhow::Result;
+use ahash::AHashMap;
+use anyhow::{Context, Result};
 use aptos_indexer_processor_sdk::{
-    aptos_protos::transaction::v1::transaction::TxnData,
-    postgres::{
-        basic_processor::process,
-        utils::database::{execute_in_chunks, MAX_DIESEL_PARAM_SIZE},
+    aptos_indexer_transaction_stream::utils::time::parse_timestamp,
+    aptos_protos::transaction::v1::{write_set_change::Change, Event, Transaction},
+    common_steps::EventRegistry,
+    postgres::utils::{
+        database::{execute_in_chunks, get_config_table_chunk_size, ArcDbPool},
+        writer::{write_bundle, RowBundle},
     },
+    utils::{errors::ProcessorError, struct_tag::StructTag},
+};
+use clap::Parser;
+use diesel::{
+    pg::Pg, query_builder::QueryFragment, query_dsl::methods::FilterDsl, upsert::excluded,
+    ExpressionMethods,
 };
-use diesel::{pg::Pg, query_builder::QueryFragment, upsert::excluded, ExpressionMethods};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations};
-use field_count::FieldCount;
 use rayon::prelude::*;
-use std::env;
+use std::{
+    collections::HashMap,
+    env,
+    sync::{Arc, LazyLock},
+};
 use tracing::{error, info, warn};
 
+mod asset_lookup;
+mod backend_projection;
+mod cli;
 pub mod models;
+mod rewind;
 #[path = "db/schema.rs"]
 pub mod schema;
+mod snapshots;
+mod starting_version;
+#[cfg(test)]
+mod test_db;
+mod transaction_filter;
+mod yield_protocols;
 
 use models::*;
 
 const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
 
+const PROCESSOR_NAME: &str = "kizo_prediction_market_indexer";
+
 // Event type strings from your Move contract
-// Contract address: 0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c
+const KIZO_ADDRESS: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c";
+const KIZO_MODULE: &str = "kizo_prediction_market";
 const MARKET_CREATED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c::kizo_prediction_market::MarketCreatedEvent";
 const BET_PLACED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c::kizo_prediction_market::BetPlacedEvent";
 const MARKET_RESOLVED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c::kizo_prediction_market::MarketResolvedEvent";
@@ -30,344 +54,423 @@ const WINNINGS_CLAIMED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04b
 const YIELD_DEPOSITED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c::kizo_prediction_market::YieldDepositedEvent";
 const PROTOCOL_FEE_COLLECTED_EVENT: &str = "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c::kizo_prediction_market::ProtocolFeeCollectedEvent";
 
-// Insert query builders
-fn insert_markets_query(
-    items_to_insert: Vec<Market>,
+/// Counters are only added when the batch is newer than what the row already saw, so
+/// re-processing a batch after a restart doesn't double count. Adding to the existing row is not
+/// one of the `WriteStrategy`s, so unlike the other tables this query is written by hand.
+fn insert_onchain_users_query(
+    items_to_insert: Vec<OnchainUser>,
 ) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    use schema::markets::dsl::*;
-    diesel::insert_into(schema::markets::table)
+    use schema::onchain_users::dsl::*;
+    diesel::insert_into(schema::onchain_users::table)
         .values(items_to_insert)
-        .on_conflict(market_id)
-        .do_nothing()
+        .on_conflict(address)
+        .do_update()
+        .set((
+            last_seen_version.eq(excluded(last_seen_version)),
+            last_seen_timestamp.eq(excluded(last_seen_timestamp)),
+            activity_count.eq(activity_count + excluded(activity_count)),
+            bet_count.eq(bet_count + excluded(bet_count)),
+            claim_count.eq(claim_count + excluded(claim_count)),
+            markets_created_count.eq(markets_created_count + excluded(markets_created_count)),
+        ))
+        .filter(last_seen_version.lt(excluded(first_seen_version)))
 }
 
-fn insert_bets_query(
-    items_to_insert: Vec<Bet>,
-) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    use schema::bets::dsl::*;
-    diesel::insert_into(schema::bets::table)
-        .values(items_to_insert)
-        .on_conflict(bet_id)
-        .do_nothing()
+#[tokio::main]
+async fn main() -> Result<()> {
+    cli::Cli::parse().run().await
 }
 
-fn insert_market_resolutions_query(
-    items_to_insert: Vec<MarketResolution>,
-) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    use schema::market_resolutions::dsl::*;
-    diesel::insert_into(schema::market_resolutions::table)
-        .values(items_to_insert)
-        .on_conflict(market_id)
-        .do_update()
-        .set((
-            outcome.eq(excluded(outcome)),
-            total_yield_earned.eq(excluded(total_yield_earned)),
-            transaction_version.eq(excluded(transaction_version)),
-            transaction_block_height.eq(excluded(transaction_block_height)),
-        ))
+/// Parses a batch of transactions and stores the resulting rows. `per_table_chunk_sizes`
+/// overrides how many rows of a table go in one insert.
+async fn process_batch(
+    transactions: Vec<Transaction>,
+    conn_pool: ArcDbPool,
+    per_table_chunk_sizes: Arc<AHashMap<String, usize>>,
+) -> Result<(), ProcessorError> {
+    // Process transactions in parallel and merge the results
+    let rows = transactions
+        .par_iter()
+        .map(parse_transaction)
+        .reduce(KizoRows::default, KizoRows::merge);
+    let KizoRows {
+        markets,
+        bets,
+        market_resolutions,
+        winnings_claims,
+        yield_deposits,
+        protocol_fees,
+        market_state_history,
+        onchain_users,
+        asset_metadata,
+    } = &rows;
+    let current_market_state = latest_market_states(market_state_history);
+    let onchain_users = merge_onchain_users(onchain_users);
+    let mut asset_metadata = latest_asset_metadata(asset_metadata);
+    let fetched = asset_lookup::fetch_missing(
+        conn_pool.clone(),
+        markets.iter().map(|market| market.asset_type.as_str()),
+        &asset_metadata,
+    )
+    .await;
+    asset_metadata.extend(fetched);
+
+    // Tables are written after the ones they reference, see `PostgresWritable::DEPENDS_ON`
+    let bundle = RowBundle::new()
+        .with(asset_metadata)
+        .with(markets.clone())
+        .with(bets.clone())
+        .with(market_resolutions.clone())
+        .with(winnings_claims.clone())
+        .with(yield_deposits.clone())
+        .with(protocol_fees.clone())
+        .with(market_state_history.clone())
+        .with(current_market_state);
+    if let Err(e) = write_bundle(conn_pool.clone(), &bundle, &per_table_chunk_sizes).await {
+        handle_store_error("rows", e)?;
+    }
+
+    if !onchain_users.is_empty() {
+        match execute_in_chunks(
+            conn_pool.clone(),
+            insert_onchain_users_query,
+            &onchain_users,
+            get_config_table_chunk_size::<OnchainUser>("onchain_users", &per_table_chunk_sizes),
+        )
+        .await
+        {
+            Ok(_) => info!("Stored {} on-chain users", onchain_users.len()),
+            Err(e) => handle_store_error("on-chain users", e)?,
+        }
+    }
+
+    info!(
+        "Processed transactions version [{}, {}]",
+        transactions.first().map(|t| t.version).unwrap_or(0),
+        transactions.last().map(|t| t.version).unwrap_or(0)
+    );
+
+    // Refresh the stats snapshots, then either project straight into the backend tables or
+    // trigger a backend sync if any new data was stored
+    let total_new_items = markets.len()
+        + bets.len()
+        + market_resolutions.len()
+        + winnings_claims.len()
+        + yield_deposits.len()
+        + protocol_fees.len();
+
+    if total_new_items > 0 {
+        snapshots::refresh(conn_pool.clone(), &rows).await;
+        if backend_projection::is_enabled() {
+            backend_projection::project(conn_pool.clone(), &rows).await?;
+        } else {
+            trigger_backend_sync(total_new_items).await;
+        }
+    }
+
+    Ok(())
 }
 
-fn insert_winnings_claims_query(
-    items_to_insert: Vec<NewWinningsClaim>,
-) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    diesel::insert_into(schema::winnings_claims::table)
-        .values(items_to_insert)
-        .on_conflict_do_nothing()
+/// Logs a failed insert and moves on, unless the DB could not be reached. That error is passed
+/// on, so the processor pauses until the DB is back and then retries the whole batch. Tables
+/// written before the failure are written again, which is safe since every write is keyed.
+fn handle_store_error(table: &str, error: ProcessorError) -> Result<(), ProcessorError> {
+    error!("Failed to store {table}: {error:?}");
+    if error.is_connection_failure() {
+        return Err(error);
+    }
+    Ok(())
 }
 
-fn insert_yield_deposits_query(
-    items_to_insert: Vec<NewYieldDeposit>,
-) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    diesel::insert_into(schema::yield_deposits::table)
-        .values(items_to_insert)
-        .on_conflict_do_nothing()
+/// Rows extracted from a batch of transactions, one vector per destination table.
+#[derive(Default)]
+struct KizoRows {
+    markets: Vec<Market>,
+    bets: Vec<Bet>,
+    market_resolutions: Vec<MarketResolution>,
+    winnings_claims: Vec<NewWinningsClaim>,
+    yield_deposits: Vec<NewYieldDeposit>,
+    protocol_fees: Vec<NewProtocolFee>,
+    market_state_history: Vec<MarketStateHistory>,
+    onchain_users: Vec<OnchainUser>,
+    asset_metadata: Vec<AssetMetadata>,
 }
 
-fn insert_protocol_fees_query(
-    items_to_insert: Vec<NewProtocolFee>,
-) -> impl QueryFragment<Pg> + diesel::query_builder::QueryId + Send {
-    diesel::insert_into(schema::protocol_fees::table)
-        .values(items_to_insert)
-        .on_conflict_do_nothing()
+impl KizoRows {
+    fn merge(mut self, other: KizoRows) -> KizoRows {
+        self.markets.extend(other.markets);
+        self.bets.extend(other.bets);
+        self.market_resolutions.extend(other.market_resolutions);
+        self.winnings_claims.extend(other.winnings_claims);
+        self.yield_deposits.extend(other.yield_deposits);
+        self.protocol_fees.extend(other.protocol_fees);
+        self.market_state_history.extend(other.market_state_history);
+        self.onchain_users.extend(other.onchain_users);
+        self.asset_metadata.extend(other.asset_metadata);
+        self
+    }
 }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    process(
-        "kizo_prediction_market_indexer".to_string(),
-        MIGRATIONS,
-        async |transactions, conn_pool| {
-            // Process transactions in parallel and collect results
-            let processed_data = transactions
-                .par_iter()
-                .map(|txn| {
-                    let txn_version = txn.version as i64;
-                    let block_height = txn.block_height as i64;
-
-                    let txn_data = match txn.txn_data.as_ref() {
-                        Some(data) => data,
-                        None => {
-                            warn!(
-                                transaction_version = txn_version,
-                                "Transaction data doesn't exist"
-                            );
-                            return (
-                                Vec::new(),
-                                Vec::new(),
-                                Vec::new(),
-                                Vec::new(),
-                                Vec::new(),
-                                Vec::new(),
-                            );
-                        },
-                    };
-
-                    let default = vec![];
-                    let raw_events = match txn_data {
-                        TxnData::BlockMetadata(tx_inner) => &tx_inner.events,
-                        TxnData::Genesis(tx_inner) => &tx_inner.events,
-                        TxnData::User(tx_inner) => &tx_inner.events,
-                        _ => &default,
-                    };
-
-                    let mut markets = Vec::new();
-                    let mut bets = Vec::new();
-                    let mut market_resolutions = Vec::new();
-                    let mut winnings_claims = Vec::new();
-                    let mut yield_deposits = Vec::new();
-                    let mut protocol_fees = Vec::new();
-
-                    // Process each event
-                    for event in raw_events {
-                        let event_type = event.type_str.as_str();
-
-                        // Debug log all kizo events
-                        if event_type.contains(
-                            "0x66c4ec614f237de2470e107a17329e17d2e9d04bd6f609bdb7f7b52ae24c957c",
-                        ) {
-                            info!(
-                                "Found Kizo event at version {}: type={}, data={}",
-                                txn_version, event_type, event.data
-                            );
-                        }
-
-                        match event_type {
-                            MARKET_CREATED_EVENT => {
-                                match parse_event_data::<MarketCreatedEvent>(event) {
-                                    Some(market_event) => {
-                                        markets.push(Market::from_event(
-                                            &market_event,
-                                            txn_version,
-                                            block_height,
-                                        ));
-                                        info!(
-                                            "Successfully parsed market at version {}",
-                                            txn_version
-                                        );
-                                    },
-                                    None => {
-                                        error!(
-                                            "Failed to parse MarketCreatedEvent at version {}: {}",
-                                            txn_version, event.data
-                                        );
-                                    },
-                                }
-                            },
-                            BET_PLACED_EVENT => {
-                                if let Some(bet_event) = parse_event_data::<BetPlacedEvent>(event) {
-                                    bets.push(Bet::from_event(
-                                        &bet_event,
-                                        txn_version,
-                                        block_height,
-                                    ));
-                                }
-                            },
-                            MARKET_RESOLVED_EVENT => {
-                                if let Some(resolution_event) =
-                                    parse_event_data::<MarketResolvedEvent>(event)
-                                {
-                                    market_resolutions.push(MarketResolution::from_event(
-                                        &resolution_event,
-                                        txn_version,
-                                        block_height,
-                                    ));
-                                }
-                            },
-                            WINNINGS_CLAIMED_EVENT => {
-                                if let Some(claim_event) =
-                                    parse_event_data::<WinningsClaimedEvent>(event)
-                                {
-                                    winnings_claims.push(NewWinningsClaim::from_event(
-                                        &claim_event,
-                                        txn_version,
-                                        block_height,
-                                    ));
-                                }
-                            },
-                            YIELD_DEPOSITED_EVENT => {
-                                if let Some(deposit_event) =
-                                    parse_event_data::<YieldDepositedEvent>(event)
-                                {
-                                    yield_deposits.push(NewYieldDeposit::from_event(
-                                        &deposit_event,
-                                        txn_version,
-                                        block_height,
-                                    ));
-                                }
-                            },
-                            PROTOCOL_FEE_COLLECTED_EVENT => {
-                                if let Some(fee_event) =
-                                    parse_event_data::<ProtocolFeeCollectedEvent>(event)
-                                {
-                                    protocol_fees.push(NewProtocolFee::from_event(
-                                        &fee_event,
-                                        txn_version,
-                                        block_height,
-                                    ));
-                                }
-                            },
-                            _ => {
-                                // Skip non-Kizo events
-                            },
-                        }
-                    }
-
-                    (
-                        markets,
-                        bets,
-                        market_resolutions,
-                        winnings_claims,
-                        yield_deposits,
-                        protocol_fees,
-                    )
-                })
-                .collect::<Vec<_>>();
-
-            // Flatten all collected data
-            let mut markets = Vec::new();
-            let mut bets = Vec::new();
-            let mut market_resolutions = Vec::new();
-            let mut winnings_claims = Vec::new();
-            let mut yield_deposits = Vec::new();
-            let mut protocol_fees = Vec::new();
-
-            for (m, b, mr, wc, yd, pf) in processed_data {
-                markets.extend(m);
-                bets.extend(b);
-                market_resolutions.extend(mr);
-                winnings_claims.extend(wc);
-                yield_deposits.extend(yd);
-                protocol_fees.extend(pf);
-            }
+/// Kizo events the processor turns into rows.
+enum KizoEvent {
+    MarketCreated(MarketCreatedEvent),
+    BetPlaced(BetPlacedEvent),
+    MarketResolved(MarketResolvedEvent),
+    WinningsClaimed(WinningsClaimedEvent),
+    YieldDeposited(YieldDepositedEvent),
+    ProtocolFeeCollected(ProtocolFeeCollectedEvent),
+}
 
-            // Store all data in database
-            if !markets.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_markets_query,
-                    &markets,
-                    MAX_DIESEL_PARAM_SIZE / Market::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} markets", markets.len()),
-                    Err(e) => error!("Failed to store markets: {:?}", e),
-                }
-            }
+static KIZO_EVENTS: LazyLock<EventRegistry<KizoEvent>> = LazyLock::new(|| {
+    EventRegistry::new()
+        .register_with(MARKET_CREATED_EVENT, versioned(KizoEvent::MarketCreated))
+        .register_with(BET_PLACED_EVENT, versioned(KizoEvent::BetPlaced))
+        .register_with(MARKET_RESOLVED_EVENT, versioned(KizoEvent::MarketResolved))
+        .register_with(
+            WINNINGS_CLAIMED_EVENT,
+            versioned(KizoEvent::WinningsClaimed),
+        )
+        .register_with(YIELD_DEPOSITED_EVENT, versioned(KizoEvent::YieldDeposited))
+        .register_with(
+            PROTOCOL_FEE_COLLECTED_EVENT,
+            versioned(KizoEvent::ProtocolFeeCollected),
+        )
+});
+
+/// Decodes through the versioned parser so parse outcomes keep showing up in metrics.
+fn versioned<T: VersionedEvent + 'static>(
+    wrap: fn(T) -> KizoEvent,
+) -> impl Fn(&Event) -> Result<KizoEvent> + Send + Sync {
+    move |event: &Event| {
+        parse_event_data::<T>(event)
+            .map(wrap)
+            .with_context(|| format!("Failed to parse {}", T::EVENT_NAME))
+    }
+}
 
-            if !bets.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_bets_query,
-                    &bets,
-                    MAX_DIESEL_PARAM_SIZE / Bet::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} bets", bets.len()),
-                    Err(e) => error!("Failed to store bets: {:?}", e),
+fn parse_transaction(txn: &Transaction) -> KizoRows {
+    let mut rows = KizoRows::default();
+
+    let (events, failures) = KIZO_EVENTS.extract(txn);
+    for failure in failures {
+        error!(
+            "Failed to parse {} at version {}: {}",
+            failure.type_str, failure.transaction_version, failure.data
+        );
+    }
+
+    for decoded in events {
+        let txn_version = decoded.transaction_version as i64;
+        let block_height = decoded.transaction_block_height as i64;
+        let txn_timestamp = decoded.transaction_timestamp;
+        let event_index = decoded.event_index as i64;
+        // Market creation events don't name the creator, so attribute them to the sender
+        let sender = decoded.sender.as_deref();
+
+        match decoded.event {
+            KizoEvent::MarketCreated(market_event) => {
+                rows.markets.push(Market::from_event(
+                    &market_event,
+                    &decoded.type_tag,
+                    sender,
+                    txn_version,
+                    block_height,
+                    txn_timestamp,
+                ));
+                if let Some(sender) = sender {
+                    rows.onchain_users.push(OnchainUser::from_activity(
+                        sender,
+                        UserActivity::MarketCreation,
+                        txn_version,
+                        txn_timestamp,
+                    ));
                 }
-            }
+                info!("Successfully parsed market at version {}", txn_version);
+            },
+            KizoEvent::BetPlaced(bet_event) => {
+                rows.bets.push(Bet::from_event(
+                    &bet_event,
+                    txn_version,
+                    block_height,
+                    txn_timestamp,
+                ));
+                rows.onchain_users.push(OnchainUser::from_activity(
+                    &bet_event.user,
+                    UserActivity::Bet,
+                    txn_version,
+                    txn_timestamp,
+                ));
+            },
+            KizoEvent::MarketResolved(resolution_event) => {
+                rows.market_resolutions.push(MarketResolution::from_event(
+                    &resolution_event,
+                    txn_version,
+                    block_height,
+                    txn_timestamp,
+                ));
+            },
+            KizoEvent::WinningsClaimed(claim_event) => {
+                rows.winnings_claims.push(NewWinningsClaim::from_event(
+                    &claim_event,
+                    txn_version,
+                    event_index,
+                    block_height,
+                    txn_timestamp,
+                ));
+                rows.onchain_users.push(OnchainUser::from_activity(
+                    &claim_event.user,
+                    UserActivity::Claim,
+                    txn_version,
+                    txn_timestamp,
+                ));
+            },
+            KizoEvent::YieldDeposited(deposit_event) => {
+                rows.yield_deposits.push(NewYieldDeposit::from_event(
+                    &deposit_event,
+                    txn_version,
+                    event_index,
+                    block_height,
+                    txn_timestamp,
+                ));
+            },
+            KizoEvent::ProtocolFeeCollected(fee_event) => {
+                rows.protocol_fees.push(NewProtocolFee::from_event(
+                    &fee_event,
+                    txn_version,
+                    event_index,
+                    block_height,
+                    txn_timestamp,
+                ));
+            },
+        }
+    }
 
-            if !market_resolutions.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_market_resolutions_query,
-                    &market_resolutions,
-                    MAX_DIESEL_PARAM_SIZE / MarketResolution::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} market resolutions", market_resolutions.len()),
-                    Err(e) => error!("Failed to store market resolutions: {:?}", e),
-                }
-            }
+    rows.market_state_history = parse_market_state_changes(txn);
+    rows.asset_metadata = parse_asset_metadata(txn);
+    rows
+}
 
-            if !winnings_claims.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_winnings_claims_query,
-                    &winnings_claims,
-                    MAX_DIESEL_PARAM_SIZE / NewWinningsClaim::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} winnings claims", winnings_claims.len()),
-                    Err(e) => error!("Failed to store winnings claims: {:?}", e),
-                }
-            }
+/// Collects writes and deletes of resources declared by the Kizo module. Events only carry
+/// deltas, so this is what lets us cross-check pool balances and yield positions on chain.
+fn parse_market_state_changes(txn: &Transaction) -> Vec<MarketStateHistory> {
+    let txn_version = txn.version as i64;
+    let block_height = txn.block_height as i64;
+    let txn_timestamp = transaction_timestamp(txn);
+    let changes = match txn.info.as_ref() {
+        Some(info) => &info.changes,
+        None => return Vec::new(),
+    };
 
-            if !yield_deposits.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_yield_deposits_query,
-                    &yield_deposits,
-                    MAX_DIESEL_PARAM_SIZE / NewYieldDeposit::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} yield deposits", yield_deposits.len()),
-                    Err(e) => error!("Failed to store yield deposits: {:?}", e),
-                }
+    changes
+        .iter()
+        .enumerate()
+        .filter_map(|(index, wsc)| {
+            let (address, type_str, data) = match wsc.change.as_ref()? {
+                Change::WriteResource(resource) => (
+                    &resource.address,
+                    &resource.type_str,
+                    Some(resource.data.as_str()),
+                ),
+                Change::DeleteResource(resource) => (&resource.address, &resource.type_str, None),
+                _ => return None,
+            };
+            let is_kizo_resource = type_str
+                .parse::<StructTag>()
+                .is_ok_and(|tag| tag.is_in_module(KIZO_ADDRESS, KIZO_MODULE));
+            if !is_kizo_resource {
+                return None;
             }
+            let data = match data.map(serde_json::from_str::<serde_json::Value>) {
+                Some(Ok(value)) => Some(value),
+                Some(Err(e)) => {
+                    error!(
+                        transaction_version = txn_version,
+                        resource_type = type_str,
+                        error = ?e,
+                        "Failed to parse Kizo resource data"
+                    );
+                    return None;
+                },
+                None => None,
+            };
+            Some(MarketStateHistory::from_resource_change(
+                address,
+                type_str,
+                data,
+                txn_version,
+                index as i64,
+                block_height,
+                txn_timestamp,
+            ))
+        })
+        .collect()
+}
 
-            if !protocol_fees.is_empty() {
-                match execute_in_chunks(
-                    conn_pool.clone(),
-                    insert_protocol_fees_query,
-                    &protocol_fees,
-                    MAX_DIESEL_PARAM_SIZE / NewProtocolFee::field_count(),
-                )
-                .await
-                {
-                    Ok(_) => info!("Stored {} protocol fees", protocol_fees.len()),
-                    Err(e) => error!("Failed to store protocol fees: {:?}", e),
-                }
-            }
+/// Collects coin and fungible asset metadata written by the transaction, so amounts in any asset
+/// can be shown with the right symbol and decimals.
+fn parse_asset_metadata(txn: &Transaction) -> Vec<AssetMetadata> {
+    let Some(info) = txn.info.as_ref() else {
+        return Vec::new();
+    };
+    info.changes
+        .iter()
+        .filter_map(|wsc| match wsc.change.as_ref()? {
+            Change::WriteResource(resource) => Some(resource),
+            _ => None,
+        })
+        .filter_map(|resource| {
+            let resource_type = resource.type_str.parse::<StructTag>().ok()?;
+            AssetMetadata::from_write_resource(
+                &resource.address,
+                &resource_type,
+                &resource.data,
+                txn.version as i64,
+            )
+        })
+        .collect()
+}
 
-            info!(
-                "Processed transactions version [{}, {}]",
-                transactions.first().map(|t| t.version).unwrap_or(0),
-                transactions.last().map(|t| t.version).unwrap_or(0)
-            );
-
-            // Trigger backend sync if any new data was stored
-            let total_new_items = markets.len()
-                + bets.len()
-                + market_resolutions.len()
-                + winnings_claims.len()
-                + yield_deposits.len()
-                + protocol_fees.len();
-
-            if total_new_items > 0 {
-                trigger_backend_sync(total_new_items).await;
-            }
+fn transaction_timestamp(txn: &Transaction) -> chrono::NaiveDateTime {
+    txn.timestamp
+        .as_ref()
+        .map(|ts| parse_timestamp(ts, txn.version as i64).naive_utc())
+        .unwrap_or_default()
+}
 
-            Ok(())
-        },
-    )
-    .await?;
-    Ok(())
+/// Collapses every sighting of an address in the batch into one row for the upsert.
+fn merge_onchain_users(sightings: &[OnchainUser]) -> Vec<OnchainUser> {
+    let mut users: HashMap<&str, OnchainUser> = HashMap::new();
+    for sighting in sightings {
+        users
+            .entry(sighting.address.as_str())
+            .and_modify(|user| user.merge(sighting))
+            .or_insert_with(|| sighting.clone());
+    }
+    users.into_values().collect()
+}
+
+/// Keeps only the latest metadata per asset so the upsert never touches a row twice.
+fn latest_asset_metadata(metadata: &[AssetMetadata]) -> Vec<AssetMetadata> {
+    let mut latest: HashMap<&str, &AssetMetadata> = HashMap::new();
+    for asset in metadata {
+        latest.insert(asset.asset_type.as_str(), asset);
+    }
+    latest.into_values().cloned().collect()
+}
+
+/// Keeps only the last change per resource so the upsert never touches a row twice.
+fn latest_market_states(history: &[MarketStateHistory]) -> Vec<CurrentMarketState> {
+    let mut latest: HashMap<(&str, &str), &MarketStateHistory> = HashMap::new();
+    for change in history {
+        latest.insert(
+            (
+                change.resource_address.as_str(),
+                change.resource_type.as_str(),
+            ),
+            change,
+        );
+    }
+    latest.into_values().map(CurrentMarketState::from).collect()
 }
 
 /// Trigger the backend sync endpoint after new data is indexed
@@ -427,9 +530,3 @@ async fn trigger_backend_sync(total_items: usize) {
         }
     });
 }
-
-fn parse_event_data<T: serde::de::DeserializeOwned>(
-    event: &aptos_indexer_processor_sdk::aptos_protos::transaction::v1::Event,
-) -> Option<T> {
-    serde_json::from_str(&event.data).ok()
-}
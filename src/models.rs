@@ -1,14 +1,22 @@
 #![allow(clippy::extra_unused_lifetimes)]
 
 use crate::schema::{
-    bets, market_resolutions, markets, protocol_fees, winnings_claims, yield_deposits,
+    asset_metadata, bets, current_market_state, market_resolutions, market_state_history, markets,
+    onchain_users, protocol_fees, winnings_claims, yield_deposits, yield_protocols,
 };
 use aptos_indexer_processor_sdk::{
-    aptos_protos::transaction::v1::Event as EventPB, utils::convert::standardize_address,
+    aptos_protos::transaction::v1::Event as EventPB,
+    postgres::utils::writer::{BoxedInsert, PostgresWritable, WriteStrategy},
+    utils::{
+        convert::{deserialize_u64_from_string_or_number, standardize_address},
+        step_metrics::{EventParseMetricLabels, EVENT_PARSE_COUNT},
+        struct_tag::{normalize_address, StructTag, TypeTag},
+    },
 };
 use diesel::{Identifiable, Insertable, Queryable};
 use field_count::FieldCount;
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 
 // ===== Markets =====
 
@@ -27,31 +35,43 @@ pub struct Market {
     pub outcome: Option<bool>,
     pub total_yield_earned: Option<i64>,
     pub resolution_transaction_version: Option<i64>,
+    pub extra: Option<Value>,
+    pub creator_addr: Option<String>,
+    pub asset_type: String,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MarketCreatedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub market_id: u64,
     pub question: String,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub end_time: u64,
     pub yield_protocol_addr: String,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
-fn deserialize_string_to_u64<'de, D>(deserializer: D) -> Result<u64, D::Error>
-where
-    D: serde::Deserializer<'de>,
-{
-    let s = String::deserialize(deserializer)?;
-    s.parse().map_err(serde::de::Error::custom)
+impl VersionedEvent for MarketCreatedEvent {
+    const EVENT_NAME: &'static str = "MarketCreatedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] = &[(
+        1,
+        &["market_id", "question", "end_time", "yield_protocol_addr"],
+    )];
 }
 
 impl Market {
+    /// `event_type` is the exact type the event was emitted with; generic events carry the
+    /// market's asset as their type argument.
     pub fn from_event(
         event: &MarketCreatedEvent,
+        event_type: &StructTag,
+        creator_addr: Option<&str>,
         transaction_version: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         Market {
             market_id: event.market_id as i64,
@@ -65,10 +85,42 @@ impl Market {
             outcome: None,
             total_yield_earned: Some(0),
             resolution_transaction_version: None,
+            extra: extra_to_json(&event.extra),
+            creator_addr: creator_addr.map(standardize_address),
+            asset_type: market_asset_type(event_type, &event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
         }
     }
 }
 
+/// Markets created before multi-asset support are all in APT.
+pub const APT_COIN_TYPE: &str = "0x1::aptos_coin::AptosCoin";
+
+/// Works out the asset a market is denominated in: the coin type argument of a generic
+/// `MarketCreatedEvent<CoinType>`, else an `asset_type`/`coin_type` field, else the `metadata`
+/// object of a fungible asset. Defaults to APT.
+fn market_asset_type(event_type: &StructTag, extra: &Map<String, Value>) -> String {
+    if let Some(TypeTag::Struct(coin_type)) = event_type.type_args.first() {
+        return coin_type.to_string();
+    }
+    let field = ["asset_type", "coin_type"]
+        .iter()
+        .find_map(|key| extra.get(*key)?.as_str())
+        .or_else(|| extra.get("metadata")?.get("inner")?.as_str());
+    match field {
+        Some(asset) => normalize_asset_type(asset),
+        None => APT_COIN_TYPE.to_string(),
+    }
+}
+
+/// Coin types are formatted like the node API does, fungible asset addresses in the long form.
+fn normalize_asset_type(asset: &str) -> String {
+    match asset.parse::<StructTag>() {
+        Ok(coin_type) => coin_type.to_string(),
+        Err(_) => normalize_address(asset),
+    }
+}
+
 // ===== Bets =====
 
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
@@ -87,18 +139,29 @@ pub struct Bet {
     pub winning_amount: Option<i64>,
     pub yield_share: Option<i64>,
     pub claim_transaction_version: Option<i64>,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct BetPlacedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub bet_id: u64,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub market_id: u64,
     pub user: String,
     pub position: bool,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub amount: u64,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl VersionedEvent for BetPlacedEvent {
+    const EVENT_NAME: &'static str = "BetPlacedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+        &[(1, &["bet_id", "market_id", "user", "position", "amount"])];
 }
 
 impl Bet {
@@ -106,6 +169,7 @@ impl Bet {
         event: &BetPlacedEvent,
         transaction_version: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         Bet {
             bet_id: event.bet_id as i64,
@@ -120,6 +184,8 @@ impl Bet {
             winning_amount: Some(0),
             yield_share: Some(0),
             claim_transaction_version: None,
+            extra: extra_to_json(&event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
         }
     }
 }
@@ -136,15 +202,26 @@ pub struct MarketResolution {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct MarketResolvedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub market_id: u64,
     pub outcome: bool,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub total_yield_earned: u64,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl VersionedEvent for MarketResolvedEvent {
+    const EVENT_NAME: &'static str = "MarketResolvedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+        &[(1, &["market_id", "outcome", "total_yield_earned"])];
 }
 
 impl MarketResolution {
@@ -152,6 +229,7 @@ impl MarketResolution {
         event: &MarketResolvedEvent,
         transaction_version: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         MarketResolution {
             market_id: event.market_id as i64,
@@ -160,6 +238,8 @@ impl MarketResolution {
             transaction_version,
             transaction_block_height,
             inserted_at: chrono::Utc::now().naive_utc(),
+            extra: extra_to_json(&event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
         }
     }
 }
@@ -178,17 +258,29 @@ pub struct WinningsClaim {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct WinningsClaimedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub bet_id: u64,
     pub user: String,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub winning_amount: u64,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub yield_share: u64,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl VersionedEvent for WinningsClaimedEvent {
+    const EVENT_NAME: &'static str = "WinningsClaimedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+        &[(1, &["bet_id", "user", "winning_amount", "yield_share"])];
 }
 
 // Note: claim_id is auto-generated, so we use a NewWinningsClaim for insertion
@@ -202,13 +294,18 @@ pub struct NewWinningsClaim {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 impl NewWinningsClaim {
     pub fn from_event(
         event: &WinningsClaimedEvent,
         transaction_version: i64,
+        event_index: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         NewWinningsClaim {
             bet_id: event.bet_id as i64,
@@ -218,6 +315,9 @@ impl NewWinningsClaim {
             transaction_version,
             transaction_block_height,
             inserted_at: chrono::Utc::now().naive_utc(),
+            extra: extra_to_json(&event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
+            event_index: Some(event_index),
         }
     }
 }
@@ -235,15 +335,27 @@ pub struct YieldDeposit {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct YieldDepositedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub market_id: u64,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub amount: u64,
     pub protocol_addr: String,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl VersionedEvent for YieldDepositedEvent {
+    const EVENT_NAME: &'static str = "YieldDepositedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+        &[(1, &["market_id", "amount", "protocol_addr"])];
 }
 
 // Note: deposit_id is auto-generated, so we use a NewYieldDeposit for insertion
@@ -256,13 +368,18 @@ pub struct NewYieldDeposit {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 impl NewYieldDeposit {
     pub fn from_event(
         event: &YieldDepositedEvent,
         transaction_version: i64,
+        event_index: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         NewYieldDeposit {
             market_id: event.market_id as i64,
@@ -271,10 +388,27 @@ impl NewYieldDeposit {
             transaction_version,
             transaction_block_height,
             inserted_at: chrono::Utc::now().naive_utc(),
+            extra: extra_to_json(&event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
+            event_index: Some(event_index),
         }
     }
 }
 
+// ===== Yield Protocols =====
+
+/// A yield protocol markets can deposit into, from the `yield_protocols` section of the config.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
+#[diesel(primary_key(address))]
+#[diesel(table_name = yield_protocols)]
+pub struct YieldProtocol {
+    pub address: String,
+    pub name: String,
+    /// `protocols.id` of the same protocol in the backend's tables
+    pub backend_protocol_id: Option<String>,
+    pub updated_at: chrono::NaiveDateTime,
+}
+
 // ===== Protocol Fees =====
 
 #[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
@@ -287,14 +421,26 @@ pub struct ProtocolFee {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct ProtocolFeeCollectedEvent {
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub market_id: u64,
-    #[serde(deserialize_with = "deserialize_string_to_u64")]
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
     pub fee_amount: u64,
+    /// Fields not known to any schema version, kept so nothing is lost on upgrades.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+impl VersionedEvent for ProtocolFeeCollectedEvent {
+    const EVENT_NAME: &'static str = "ProtocolFeeCollectedEvent";
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+        &[(1, &["market_id", "fee_amount"])];
 }
 
 // Note: fee_id is auto-generated, so we use a NewProtocolFee for insertion
@@ -306,13 +452,18 @@ pub struct NewProtocolFee {
     pub transaction_version: i64,
     pub transaction_block_height: i64,
     pub inserted_at: chrono::NaiveDateTime,
+    pub extra: Option<Value>,
+    pub transaction_timestamp: Option<chrono::NaiveDateTime>,
+    pub event_index: Option<i64>,
 }
 
 impl NewProtocolFee {
     pub fn from_event(
         event: &ProtocolFeeCollectedEvent,
         transaction_version: i64,
+        event_index: i64,
         transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
     ) -> Self {
         NewProtocolFee {
             market_id: event.market_id as i64,
@@ -320,15 +471,659 @@ impl NewProtocolFee {
             transaction_version,
             transaction_block_height,
             inserted_at: chrono::Utc::now().naive_utc(),
+            extra: extra_to_json(&event.extra),
+            transaction_timestamp: Some(transaction_timestamp),
+            event_index: Some(event_index),
+        }
+    }
+}
+
+// ===== On-chain Users =====
+
+/// Every address that bet, claimed winnings or created a market, whether or not it ever signed up
+/// through the web app.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
+#[diesel(primary_key(address))]
+#[diesel(table_name = onchain_users)]
+pub struct OnchainUser {
+    pub address: String,
+    pub first_seen_version: i64,
+    pub first_seen_timestamp: chrono::NaiveDateTime,
+    pub last_seen_version: i64,
+    pub last_seen_timestamp: chrono::NaiveDateTime,
+    pub activity_count: i64,
+    pub bet_count: i64,
+    pub claim_count: i64,
+    pub markets_created_count: i64,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UserActivity {
+    Bet,
+    Claim,
+    MarketCreation,
+}
+
+impl OnchainUser {
+    pub fn from_activity(
+        address: &str,
+        activity: UserActivity,
+        transaction_version: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
+    ) -> Self {
+        OnchainUser {
+            address: standardize_address(address),
+            first_seen_version: transaction_version,
+            first_seen_timestamp: transaction_timestamp,
+            last_seen_version: transaction_version,
+            last_seen_timestamp: transaction_timestamp,
+            activity_count: 1,
+            bet_count: (activity == UserActivity::Bet) as i64,
+            claim_count: (activity == UserActivity::Claim) as i64,
+            markets_created_count: (activity == UserActivity::MarketCreation) as i64,
+            inserted_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+
+    /// Folds another sighting of the same address into this one.
+    pub fn merge(&mut self, other: &OnchainUser) {
+        if other.first_seen_version < self.first_seen_version {
+            self.first_seen_version = other.first_seen_version;
+            self.first_seen_timestamp = other.first_seen_timestamp;
+        }
+        if other.last_seen_version > self.last_seen_version {
+            self.last_seen_version = other.last_seen_version;
+            self.last_seen_timestamp = other.last_seen_timestamp;
+        }
+        self.activity_count += other.activity_count;
+        self.bet_count += other.bet_count;
+        self.claim_count += other.claim_count;
+        self.markets_created_count += other.markets_created_count;
+    }
+}
+
+// ===== Asset Metadata (write-set resources) =====
+
+/// Name, symbol and decimals of a coin type or fungible asset that markets can be denominated in.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
+#[diesel(primary_key(asset_type))]
+#[diesel(table_name = asset_metadata)]
+pub struct AssetMetadata {
+    pub asset_type: String,
+    /// `coin` or `fa`
+    pub standard: String,
+    pub name: String,
+    pub symbol: String,
+    pub decimals: i32,
+    pub last_transaction_version: i64,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+impl AssetMetadata {
+    /// Decodes a `0x1::coin::CoinInfo<CoinType>` resource, keyed by its coin type, or a
+    /// `0x1::fungible_asset::Metadata` resource, keyed by the object address holding it. Any other
+    /// resource yields `None`.
+    pub fn from_write_resource(
+        address: &str,
+        resource_type: &StructTag,
+        data: &str,
+        transaction_version: i64,
+    ) -> Option<Self> {
+        let (asset_type, standard) = if resource_type.is("0x1", "coin", "CoinInfo") {
+            match resource_type.type_args.first()? {
+                TypeTag::Struct(coin_type) => (coin_type.to_string(), "coin"),
+                _ => return None,
+            }
+        } else if resource_type.is("0x1", "fungible_asset", "Metadata") {
+            (normalize_address(address), "fa")
+        } else {
+            return None;
+        };
+        let data = serde_json::from_str::<Value>(data).ok()?;
+        Some(AssetMetadata {
+            asset_type,
+            standard: standard.to_string(),
+            name: data.get("name")?.as_str()?.to_string(),
+            symbol: data.get("symbol")?.as_str()?.to_string(),
+            decimals: json_u64_field(&data, &["decimals"])? as i32,
+            last_transaction_version: transaction_version,
+            inserted_at: chrono::Utc::now().naive_utc(),
+        })
+    }
+}
+
+// ===== Market State (write-set resources) =====
+
+/// Latest on-chain value of a Kizo resource, keyed by the account or object holding it.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
+#[diesel(primary_key(resource_address, resource_type))]
+#[diesel(table_name = current_market_state)]
+pub struct CurrentMarketState {
+    pub resource_address: String,
+    pub resource_type: String,
+    pub market_id: Option<i64>,
+    pub total_pool: Option<i64>,
+    pub yes_pool: Option<i64>,
+    pub no_pool: Option<i64>,
+    pub yield_deposited: Option<i64>,
+    pub resolved: Option<bool>,
+    pub data: Option<Value>,
+    pub is_deleted: bool,
+    pub last_transaction_version: i64,
+    pub last_transaction_block_height: i64,
+    pub last_transaction_timestamp: chrono::NaiveDateTime,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+/// Every write or delete of a Kizo resource, one row per write set change.
+#[derive(Clone, Debug, Deserialize, FieldCount, Identifiable, Insertable, Serialize, Queryable)]
+#[diesel(primary_key(transaction_version, write_set_change_index))]
+#[diesel(table_name = market_state_history)]
+pub struct MarketStateHistory {
+    pub transaction_version: i64,
+    pub write_set_change_index: i64,
+    pub resource_address: String,
+    pub resource_type: String,
+    pub market_id: Option<i64>,
+    pub total_pool: Option<i64>,
+    pub yes_pool: Option<i64>,
+    pub no_pool: Option<i64>,
+    pub yield_deposited: Option<i64>,
+    pub resolved: Option<bool>,
+    pub data: Option<Value>,
+    pub is_deleted: bool,
+    pub transaction_block_height: i64,
+    pub transaction_timestamp: chrono::NaiveDateTime,
+    pub inserted_at: chrono::NaiveDateTime,
+}
+
+/// Fields decoded from a Kizo resource. Resources that don't carry a field leave it as `None`;
+/// the raw resource JSON is always stored next to these.
+#[derive(Clone, Debug, Default)]
+pub struct DecodedMarketResource {
+    pub market_id: Option<i64>,
+    pub total_pool: Option<i64>,
+    pub yes_pool: Option<i64>,
+    pub no_pool: Option<i64>,
+    pub yield_deposited: Option<i64>,
+    pub resolved: Option<bool>,
+}
+
+impl DecodedMarketResource {
+    pub fn from_json(data: &Value) -> Self {
+        DecodedMarketResource {
+            market_id: json_u64_field(data, &["market_id", "id"]),
+            total_pool: json_u64_field(data, &["total_pool", "total_pool_size", "total_amount"]),
+            yes_pool: json_u64_field(data, &["yes_pool", "yes_pool_size", "total_yes_amount"]),
+            no_pool: json_u64_field(data, &["no_pool", "no_pool_size", "total_no_amount"]),
+            yield_deposited: json_u64_field(data, &["yield_deposited", "total_yield_deposited"]),
+            resolved: ["resolved", "is_resolved"]
+                .iter()
+                .find_map(|key| data.get(key).and_then(Value::as_bool)),
+        }
+    }
+}
+
+/// Move serializes u64 as a JSON string, but accept plain numbers as well.
+fn json_u64_field(data: &Value, keys: &[&str]) -> Option<i64> {
+    keys.iter().find_map(|key| match data.get(key)? {
+        Value::String(s) => s.parse::<u64>().ok().map(|v| v as i64),
+        Value::Number(n) => n.as_u64().map(|v| v as i64),
+        _ => None,
+    })
+}
+
+impl MarketStateHistory {
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_resource_change(
+        resource_address: &str,
+        resource_type: &str,
+        data: Option<Value>,
+        transaction_version: i64,
+        write_set_change_index: i64,
+        transaction_block_height: i64,
+        transaction_timestamp: chrono::NaiveDateTime,
+    ) -> Self {
+        let decoded = data
+            .as_ref()
+            .map(DecodedMarketResource::from_json)
+            .unwrap_or_default();
+        MarketStateHistory {
+            transaction_version,
+            write_set_change_index,
+            resource_address: standardize_address(resource_address),
+            resource_type: resource_type.to_string(),
+            market_id: decoded.market_id,
+            total_pool: decoded.total_pool,
+            yes_pool: decoded.yes_pool,
+            no_pool: decoded.no_pool,
+            yield_deposited: decoded.yield_deposited,
+            resolved: decoded.resolved,
+            is_deleted: data.is_none(),
+            data,
+            transaction_block_height,
+            transaction_timestamp,
+            inserted_at: chrono::Utc::now().naive_utc(),
+        }
+    }
+}
+
+impl From<&MarketStateHistory> for CurrentMarketState {
+    fn from(history: &MarketStateHistory) -> Self {
+        CurrentMarketState {
+            resource_address: history.resource_address.clone(),
+            resource_type: history.resource_type.clone(),
+            market_id: history.market_id,
+            total_pool: history.total_pool,
+            yes_pool: history.yes_pool,
+            no_pool: history.no_pool,
+            yield_deposited: history.yield_deposited,
+            resolved: history.resolved,
+            data: history.data.clone(),
+            is_deleted: history.is_deleted,
+            last_transaction_version: history.transaction_version,
+            last_transaction_block_height: history.transaction_block_height,
+            last_transaction_timestamp: history.transaction_timestamp,
+            inserted_at: history.inserted_at,
         }
     }
 }
 
 // ===== Helper function to parse events =====
 
+/// A Kizo event whose layout may change across contract upgrades.
+///
+/// `SCHEMA_VERSIONS` lists every known layout as `(version, fields)`, oldest first. Fields added
+/// by later versions must be optional on the struct so older payloads still deserialize, and
+/// anything no version knows about ends up in the struct's flattened `extra` map.
+pub trait VersionedEvent: for<'de> Deserialize<'de> {
+    const EVENT_NAME: &'static str;
+    const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])];
+
+    /// Returns the newest schema version whose fields are all present in `data`.
+    fn detect_schema_version(data: &Value) -> Option<u32> {
+        let object = data.as_object()?;
+        Self::SCHEMA_VERSIONS
+            .iter()
+            .rev()
+            .find(|(_, fields)| fields.iter().all(|field| object.contains_key(*field)))
+            .map(|(version, _)| *version)
+    }
+}
+
+/// Parses an event payload, recording the outcome per event type and schema version so a
+/// contract upgrade that stops matching shows up in metrics instead of silently dropping rows.
 pub fn parse_event_data<T>(event: &EventPB) -> Option<T>
 where
-    T: for<'de> Deserialize<'de>,
+    T: VersionedEvent,
 {
-    serde_json::from_str(event.data.as_str()).ok()
+    let data = serde_json::from_str::<Value>(event.data.as_str());
+    let schema_version = data.as_ref().ok().and_then(T::detect_schema_version);
+    let result = data.and_then(serde_json::from_value::<T>);
+
+    let status = if result.is_ok() { "success" } else { "failure" };
+    EVENT_PARSE_COUNT
+        .get_or_create(&EventParseMetricLabels {
+            event_type: T::EVENT_NAME.to_string(),
+            schema_version: schema_version
+                .map(|version| version.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+            status: status.to_string(),
+        })
+        .inc();
+
+    match result {
+        Ok(parsed_event) => Some(parsed_event),
+        Err(e) => {
+            tracing::warn!(
+                event_type = T::EVENT_NAME,
+                schema_version = ?schema_version,
+                error = ?e,
+                "Failed to parse event data"
+            );
+            None
+        },
+    }
+}
+
+/// Converts the unknown fields captured on an event into the JSONB `extra` column.
+fn extra_to_json(extra: &Map<String, Value>) -> Option<Value> {
+    if extra.is_empty() {
+        None
+    } else {
+        Some(Value::Object(extra.clone()))
+    }
+}
+
+// ===== Postgres writes =====
+
+impl PostgresWritable for AssetMetadata {
+    const TABLE_NAME: &'static str = "asset_metadata";
+    const COLUMNS: &'static [&'static str] = &[
+        "asset_type",
+        "standard",
+        "name",
+        "symbol",
+        "decimals",
+        "last_transaction_version",
+        "inserted_at",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["asset_type"];
+    const STRATEGY: WriteStrategy = WriteStrategy::UpsertIfNewerVersion {
+        version_column: "last_transaction_version",
+    };
+    const PRESERVED_COLUMNS: &'static [&'static str] = &["inserted_at"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(asset_metadata::table).values(rows))
+    }
+}
+
+impl PostgresWritable for Market {
+    const TABLE_NAME: &'static str = "markets";
+    const COLUMNS: &'static [&'static str] = &[
+        "market_id",
+        "question",
+        "end_time",
+        "yield_protocol_addr",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "resolved",
+        "outcome",
+        "total_yield_earned",
+        "resolution_transaction_version",
+        "extra",
+        "creator_addr",
+        "asset_type",
+        "transaction_timestamp",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["market_id"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+    // So markets in a new asset never show up without their decimals
+    const DEPENDS_ON: &'static [&'static str] = &["asset_metadata"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(markets::table).values(rows))
+    }
+}
+
+impl PostgresWritable for Bet {
+    const TABLE_NAME: &'static str = "bets";
+    const COLUMNS: &'static [&'static str] = &[
+        "bet_id",
+        "market_id",
+        "user_addr",
+        "position",
+        "amount",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "claimed",
+        "winning_amount",
+        "yield_share",
+        "claim_transaction_version",
+        "extra",
+        "transaction_timestamp",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["transaction_version", "event_index"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+    const DEPENDS_ON: &'static [&'static str] = &["markets"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(bets::table).values(rows))
+    }
+}
+
+impl PostgresWritable for MarketResolution {
+    const TABLE_NAME: &'static str = "market_resolutions";
+    const COLUMNS: &'static [&'static str] = &[
+        "market_id",
+        "outcome",
+        "total_yield_earned",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "extra",
+        "transaction_timestamp",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["market_id"];
+    const STRATEGY: WriteStrategy = WriteStrategy::UpsertAllColumns;
+    const PRESERVED_COLUMNS: &'static [&'static str] = &["inserted_at"];
+    const DEPENDS_ON: &'static [&'static str] = &["markets"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(market_resolutions::table).values(rows))
+    }
+}
+
+// Claims, deposits and fees have generated ids, so they conflict on their event's position in
+// the transaction.
+
+impl PostgresWritable for NewWinningsClaim {
+    const TABLE_NAME: &'static str = "winnings_claims";
+    const COLUMNS: &'static [&'static str] = &[
+        "bet_id",
+        "user_addr",
+        "winning_amount",
+        "yield_share",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "extra",
+        "transaction_timestamp",
+        "event_index",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["transaction_version", "event_index"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+    const DEPENDS_ON: &'static [&'static str] = &["bets"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(winnings_claims::table).values(rows))
+    }
+}
+
+impl PostgresWritable for NewYieldDeposit {
+    const TABLE_NAME: &'static str = "yield_deposits";
+    const COLUMNS: &'static [&'static str] = &[
+        "market_id",
+        "amount",
+        "protocol_addr",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "extra",
+        "transaction_timestamp",
+        "event_index",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["transaction_version", "event_index"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+    const DEPENDS_ON: &'static [&'static str] = &["markets"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(yield_deposits::table).values(rows))
+    }
+}
+
+impl PostgresWritable for NewProtocolFee {
+    const TABLE_NAME: &'static str = "protocol_fees";
+    const COLUMNS: &'static [&'static str] = &[
+        "market_id",
+        "fee_amount",
+        "transaction_version",
+        "transaction_block_height",
+        "inserted_at",
+        "extra",
+        "transaction_timestamp",
+        "event_index",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["transaction_version", "event_index"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+    const DEPENDS_ON: &'static [&'static str] = &["markets"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(protocol_fees::table).values(rows))
+    }
+}
+
+impl PostgresWritable for MarketStateHistory {
+    const TABLE_NAME: &'static str = "market_state_history";
+    const COLUMNS: &'static [&'static str] = &[
+        "transaction_version",
+        "write_set_change_index",
+        "resource_address",
+        "resource_type",
+        "market_id",
+        "total_pool",
+        "yes_pool",
+        "no_pool",
+        "yield_deposited",
+        "resolved",
+        "data",
+        "is_deleted",
+        "transaction_block_height",
+        "transaction_timestamp",
+        "inserted_at",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] =
+        &["transaction_version", "write_set_change_index"];
+    const STRATEGY: WriteStrategy = WriteStrategy::InsertIgnore;
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(market_state_history::table).values(rows))
+    }
+}
+
+impl PostgresWritable for CurrentMarketState {
+    const TABLE_NAME: &'static str = "current_market_state";
+    const COLUMNS: &'static [&'static str] = &[
+        "resource_address",
+        "resource_type",
+        "market_id",
+        "total_pool",
+        "yes_pool",
+        "no_pool",
+        "yield_deposited",
+        "resolved",
+        "data",
+        "is_deleted",
+        "last_transaction_version",
+        "last_transaction_block_height",
+        "last_transaction_timestamp",
+        "inserted_at",
+    ];
+    const CONFLICT_TARGET: &'static [&'static str] = &["resource_address", "resource_type"];
+    const STRATEGY: WriteStrategy = WriteStrategy::UpsertIfNewerVersion {
+        version_column: "last_transaction_version",
+    };
+    const PRESERVED_COLUMNS: &'static [&'static str] = &["inserted_at"];
+
+    fn insert(rows: Vec<Self>) -> BoxedInsert {
+        Box::new(diesel::insert_into(current_market_state::table).values(rows))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Added `fee_bps` in its second layout.
+    #[derive(Deserialize)]
+    struct UpgradedEvent {}
+
+    impl VersionedEvent for UpgradedEvent {
+        const EVENT_NAME: &'static str = "UpgradedEvent";
+        const SCHEMA_VERSIONS: &'static [(u32, &'static [&'static str])] =
+            &[(1, &["market_id"]), (2, &["market_id", "fee_bps"])];
+    }
+
+    fn bet_placed() -> Value {
+        json!({
+            "bet_id": "7",
+            "market_id": "3",
+            "user": "0xa11ce",
+            "position": true,
+            "amount": "250000000",
+        })
+    }
+
+    fn event(data: &Value) -> EventPB {
+        EventPB {
+            data: data.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_detect_schema_version() {
+        let mut data = bet_placed();
+        assert_eq!(BetPlacedEvent::detect_schema_version(&data), Some(1));
+
+        // Fields no version knows about don't change the version
+        data["referrer"] = json!("0xb0b");
+        assert_eq!(BetPlacedEvent::detect_schema_version(&data), Some(1));
+
+        data.as_object_mut().unwrap().remove("amount");
+        assert_eq!(BetPlacedEvent::detect_schema_version(&data), None);
+        assert_eq!(BetPlacedEvent::detect_schema_version(&json!("7")), None);
+
+        // The newest layout whose fields are all present wins
+        let data = json!({"market_id": "3"});
+        assert_eq!(UpgradedEvent::detect_schema_version(&data), Some(1));
+        let data = json!({"market_id": "3", "fee_bps": 50});
+        assert_eq!(UpgradedEvent::detect_schema_version(&data), Some(2));
+        let data = json!({"fee_bps": 50});
+        assert_eq!(UpgradedEvent::detect_schema_version(&data), None);
+    }
+
+    #[test]
+    fn test_parse_event_data_keeps_extra_fields() {
+        let mut data = bet_placed();
+        data["referrer"] = json!("0xb0b");
+        data["boost"] = json!({"multiplier": "2"});
+
+        let bet = parse_event_data::<BetPlacedEvent>(&event(&data)).unwrap();
+        assert_eq!(bet.bet_id, 7);
+        assert_eq!(
+            Value::Object(bet.extra),
+            json!({"referrer": "0xb0b", "boost": {"multiplier": "2"}})
+        );
+    }
+
+    #[test]
+    fn test_parse_event_data_missing_field() {
+        let mut data = bet_placed();
+        data.as_object_mut().unwrap().remove("user");
+        assert!(parse_event_data::<BetPlacedEvent>(&event(&data)).is_none());
+
+        assert!(parse_event_data::<BetPlacedEvent>(&event(&json!({}))).is_none());
+        let invalid_json = EventPB {
+            data: "{\"bet_id\":".to_string(),
+            ..Default::default()
+        };
+        assert!(parse_event_data::<BetPlacedEvent>(&invalid_json).is_none());
+    }
+
+    #[test]
+    fn test_parse_event_data_numbers() {
+        // Move serializes u64 as a string, but plain numbers parse the same
+        let as_strings = parse_event_data::<BetPlacedEvent>(&event(&bet_placed())).unwrap();
+        let mut data = bet_placed();
+        data["bet_id"] = json!(7);
+        data["market_id"] = json!(3);
+        data["amount"] = json!(250_000_000);
+        let as_numbers = parse_event_data::<BetPlacedEvent>(&event(&data)).unwrap();
+        for bet in [as_strings, as_numbers] {
+            assert_eq!((bet.bet_id, bet.market_id, bet.amount), (7, 3, 250_000_000));
+            assert!(bet.extra.is_empty());
+        }
+
+        for amount in [json!("-5"), json!("2.5"), json!(-5), json!(true)] {
+            let mut data = bet_placed();
+            data["amount"] = amount;
+            assert!(parse_event_data::<BetPlacedEvent>(&event(&data)).is_none());
+        }
+    }
 }
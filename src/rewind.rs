@@ -0,0 +1,273 @@
+//! Rolls the Kizo tables back to a transaction version, e.g. after shipping a projection bug.
+//!
+//! Rows created above the version are deleted, updates applied to older rows by later
+//! transactions are reverted, and the checkpoint is reset so the next run re-indexes from there.
+//! Everything happens in one database transaction. `asset_metadata` is kept, since it may have
+//! been fetched from a node rather than indexed and re-indexing wouldn't bring it back. The
+//! backend's Prisma tables are left alone; run a full backend sync or re-enable the projection
+//! once the range has been re-indexed.
+
+use crate::{schema, PROCESSOR_NAME};
+use anyhow::{Context, Result};
+use aptos_indexer_processor_sdk::postgres::{
+    processor_metadata_schema::processor_metadata::processor_status, utils::database::ArcDbPool,
+};
+use diesel::{sql_types::BigInt, ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
+
+/// Resolutions above the version are deleted, so their markets are open again.
+const REVERT_RESOLVED_MARKETS_SQL: &str = r#"
+UPDATE markets SET
+    resolved = false,
+    outcome = NULL,
+    total_yield_earned = 0,
+    resolution_transaction_version = NULL
+WHERE resolution_transaction_version > $1"#;
+
+/// Claims above the version are deleted, so their bets are unclaimed again, as they were indexed.
+const REVERT_CLAIMED_BETS_SQL: &str = r#"
+UPDATE bets SET
+    claimed = false,
+    winning_amount = 0,
+    yield_share = 0,
+    claim_transaction_version = NULL
+WHERE claim_transaction_version > $1"#;
+
+/// Takes the bets, claims and market creations above the version back out of the user counters.
+/// Must run before those rows are deleted. `last_seen_version` is clamped to the version so the
+/// re-indexed activity passes the replay guard of the upsert again, and `last_seen_timestamp` goes
+/// back to the user's latest remaining activity. Users first seen above the version are deleted
+/// afterwards, so everyone updated here has some.
+const REVERT_USER_ACTIVITY_SQL: &str = r#"
+WITH undone AS (
+    SELECT
+        address,
+        SUM(bets) AS bets,
+        SUM(claims) AS claims,
+        SUM(markets) AS markets
+    FROM (
+        SELECT user_addr AS address, 1 AS bets, 0 AS claims, 0 AS markets
+        FROM bets WHERE transaction_version > $1
+        UNION ALL
+        SELECT user_addr, 0, 1, 0 FROM winnings_claims WHERE transaction_version > $1
+        UNION ALL
+        SELECT creator_addr, 0, 0, 1
+        FROM markets WHERE transaction_version > $1 AND creator_addr IS NOT NULL
+    ) activity
+    GROUP BY address
+),
+remaining AS (
+    SELECT address, MAX(transaction_timestamp) AS last_seen_timestamp
+    FROM (
+        SELECT user_addr AS address, transaction_timestamp
+        FROM bets WHERE transaction_version <= $1
+        UNION ALL
+        SELECT user_addr, transaction_timestamp
+        FROM winnings_claims WHERE transaction_version <= $1
+        UNION ALL
+        SELECT creator_addr, transaction_timestamp
+        FROM markets WHERE transaction_version <= $1 AND creator_addr IS NOT NULL
+    ) activity
+    WHERE address IN (SELECT address FROM undone)
+    GROUP BY address
+)
+UPDATE onchain_users u SET
+    bet_count = GREATEST(u.bet_count - undone.bets, 0),
+    claim_count = GREATEST(u.claim_count - undone.claims, 0),
+    markets_created_count = GREATEST(u.markets_created_count - undone.markets, 0),
+    activity_count = GREATEST(u.activity_count - undone.bets - undone.claims - undone.markets, 0),
+    last_seen_version = LEAST(u.last_seen_version, $1),
+    -- Rows indexed before transaction timestamps were stored have none
+    last_seen_timestamp = GREATEST(u.first_seen_timestamp, remaining.last_seen_timestamp)
+FROM undone
+LEFT JOIN remaining ON remaining.address = undone.address
+WHERE u.address = undone.address"#;
+
+/// Drops the snapshots from the first bucket the rewound range touches; re-indexing recomputes
+/// them. Must run before the rows are deleted.
+const DELETE_SNAPSHOTS_SQL: &str = r#"
+DELETE FROM protocol_snapshots s
+USING (
+    SELECT MIN(transaction_timestamp) AS first_rewound
+    FROM (
+        SELECT transaction_timestamp FROM markets WHERE transaction_version > $1
+        UNION ALL
+        SELECT transaction_timestamp FROM bets WHERE transaction_version > $1
+        UNION ALL
+        SELECT transaction_timestamp FROM market_resolutions WHERE transaction_version > $1
+        UNION ALL
+        SELECT transaction_timestamp FROM yield_deposits WHERE transaction_version > $1
+        UNION ALL
+        SELECT transaction_timestamp FROM protocol_fees WHERE transaction_version > $1
+    ) rewound
+) r
+WHERE s.bucket_start >= date_trunc(s.period, r.first_rewound)"#;
+
+/// Restores `current_market_state` rows deleted by the rewind from the latest remaining history.
+const RESTORE_MARKET_STATE_SQL: &str = r#"
+INSERT INTO current_market_state (
+    resource_address, resource_type, market_id, total_pool, yes_pool, no_pool, yield_deposited,
+    resolved, data, is_deleted, last_transaction_version, last_transaction_block_height,
+    last_transaction_timestamp
+)
+SELECT DISTINCT ON (h.resource_address, h.resource_type)
+    h.resource_address, h.resource_type, h.market_id, h.total_pool, h.yes_pool, h.no_pool,
+    h.yield_deposited, h.resolved, h.data, h.is_deleted, h.transaction_version,
+    h.transaction_block_height, h.transaction_timestamp
+FROM market_state_history h
+WHERE NOT EXISTS (
+    SELECT 1 FROM current_market_state c
+    WHERE c.resource_address = h.resource_address AND c.resource_type = h.resource_type
+)
+ORDER BY h.resource_address, h.resource_type, h.transaction_version DESC,
+    h.write_set_change_index DESC"#;
+
+/// Rewinds every Kizo table and the checkpoint to `version`. Returns the number of rows touched
+/// per step.
+pub async fn rewind_to(pool: &ArcDbPool, version: i64) -> Result<Vec<(&'static str, usize)>> {
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get a database connection")?;
+    let affected = conn
+        .transaction::<_, diesel::result::Error, _>(|conn| {
+            async move {
+                let mut affected = vec![];
+                for (step, sql) in [
+                    ("protocol_snapshots deleted", DELETE_SNAPSHOTS_SQL),
+                    ("markets reopened", REVERT_RESOLVED_MARKETS_SQL),
+                    ("bets unclaimed", REVERT_CLAIMED_BETS_SQL),
+                    ("onchain_users reverted", REVERT_USER_ACTIVITY_SQL),
+                ] {
+                    let count = diesel::sql_query(sql)
+                        .bind::<BigInt, _>(version)
+                        .execute(conn)
+                        .await?;
+                    affected.push((step, count));
+                }
+
+                let count = diesel::delete(
+                    schema::onchain_users::table
+                        .filter(schema::onchain_users::first_seen_version.gt(version)),
+                )
+                .execute(conn)
+                .await?;
+                affected.push(("onchain_users deleted", count));
+
+                // Children first so the foreign keys to markets and bets hold.
+                macro_rules! delete_above {
+                    ($table:ident) => {{
+                        let count = diesel::delete(
+                            schema::$table::table
+                                .filter(schema::$table::transaction_version.gt(version)),
+                        )
+                        .execute(conn)
+                        .await?;
+                        affected.push((concat!(stringify!($table), " deleted"), count));
+                    }};
+                }
+                delete_above!(winnings_claims);
+                delete_above!(protocol_fees);
+                delete_above!(yield_deposits);
+                delete_above!(market_resolutions);
+                delete_above!(bets);
+                delete_above!(markets);
+                delete_above!(market_state_history);
+
+                let count = diesel::delete(
+                    schema::current_market_state::table
+                        .filter(schema::current_market_state::last_transaction_version.gt(version)),
+                )
+                .execute(conn)
+                .await?;
+                affected.push(("current_market_state deleted", count));
+                let count = diesel::sql_query(RESTORE_MARKET_STATE_SQL)
+                    .execute(conn)
+                    .await?;
+                affected.push(("current_market_state restored", count));
+
+                diesel::update(
+                    processor_status::table.filter(processor_status::processor.eq(PROCESSOR_NAME)),
+                )
+                .set(processor_status::last_success_version.eq(version))
+                .execute(conn)
+                .await?;
+                Ok(affected)
+            }
+            .scope_boxed()
+        })
+        .await
+        .with_context(|| format!("Failed to rewind to version {version}, nothing was changed"))?;
+    Ok(affected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_db::{batch_execute, lines, migrated_db};
+
+    /// A market created by 0xc0 at version 100, bet on by 0xb0 at 200 and 300, and the first bet
+    /// claimed at 400. Each version's timestamp is its version in seconds.
+    const INDEXED_SQL: &str = r#"
+INSERT INTO markets (
+    market_id, question, end_time, yield_protocol_addr, transaction_version,
+    transaction_block_height, inserted_at, asset_type, creator_addr, transaction_timestamp
+)
+VALUES (1, 'Will it rain?', 1000, '0xa1', 100, 10, NOW(), '0x1::aptos_coin::AptosCoin', '0xc0',
+    to_timestamp(100) AT TIME ZONE 'UTC');
+INSERT INTO bets (
+    bet_id, market_id, user_addr, position, amount, transaction_version,
+    transaction_block_height, inserted_at, claimed, winning_amount, yield_share,
+    claim_transaction_version, transaction_timestamp
+)
+VALUES
+    (7, 1, '0xb0', true, 400, 200, 20, NOW(), true, 412, 12, 400,
+        to_timestamp(200) AT TIME ZONE 'UTC'),
+    (8, 1, '0xb0', true, 100, 300, 30, NOW(), false, 0, 0, NULL,
+        to_timestamp(300) AT TIME ZONE 'UTC');
+INSERT INTO winnings_claims (
+    bet_id, user_addr, winning_amount, yield_share, transaction_version,
+    transaction_block_height, inserted_at, transaction_timestamp
+)
+VALUES (7, '0xb0', 412, 12, 400, 40, NOW(), to_timestamp(400) AT TIME ZONE 'UTC');
+INSERT INTO onchain_users (
+    address, first_seen_version, first_seen_timestamp, last_seen_version, last_seen_timestamp,
+    activity_count, bet_count, claim_count, markets_created_count, inserted_at
+)
+VALUES
+    ('0xb0', 200, to_timestamp(200) AT TIME ZONE 'UTC', 400, to_timestamp(400) AT TIME ZONE 'UTC',
+        3, 2, 1, 0, NOW()),
+    ('0xc0', 100, to_timestamp(100) AT TIME ZONE 'UTC', 100, to_timestamp(100) AT TIME ZONE 'UTC',
+        1, 0, 0, 1, NOW());
+"#;
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_rewind_to() {
+        let (_database, pool) = migrated_db().await;
+        batch_execute(&pool, INDEXED_SQL).await;
+
+        rewind_to(&pool, 250).await.unwrap();
+
+        // Unclaimed again, as the bet was indexed
+        let bets = r#"
+SELECT concat_ws(' ', bet_id, claimed, winning_amount, yield_share, claim_transaction_version)
+    AS line
+FROM bets ORDER BY bet_id"#;
+        assert_eq!(lines(&pool, bets).await, vec!["7 f 0 0"]);
+
+        // 0xb0 was last seen placing the bet at 200; 0xc0 wasn't active above the version
+        let users = r#"
+SELECT concat_ws(' ', address, last_seen_version, last_seen_timestamp, activity_count, bet_count,
+    claim_count, markets_created_count) AS line
+FROM onchain_users ORDER BY address"#;
+        assert_eq!(
+            lines(&pool, users).await,
+            vec![
+                "0xb0 250 1970-01-01 00:03:20 1 1 0 0",
+                "0xc0 100 1970-01-01 00:01:40 1 0 0 1",
+            ]
+        );
+    }
+}
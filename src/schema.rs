@@ -1,5 +1,18 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    asset_metadata (asset_type) {
+        asset_type -> Text,
+        #[max_length = 10]
+        standard -> Varchar,
+        name -> Text,
+        symbol -> Text,
+        decimals -> Int4,
+        last_transaction_version -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     bets (bet_id) {
         bet_id -> Int8,
@@ -15,6 +28,8 @@ diesel::table! {
         winning_amount -> Nullable<Int8>,
         yield_share -> Nullable<Int8>,
         claim_transaction_version -> Nullable<Int8>,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -49,6 +64,26 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    current_market_state (resource_address, resource_type) {
+        #[max_length = 66]
+        resource_address -> Varchar,
+        resource_type -> Text,
+        market_id -> Nullable<Int8>,
+        total_pool -> Nullable<Int8>,
+        yes_pool -> Nullable<Int8>,
+        no_pool -> Nullable<Int8>,
+        yield_deposited -> Nullable<Int8>,
+        resolved -> Nullable<Bool>,
+        data -> Nullable<Jsonb>,
+        is_deleted -> Bool,
+        last_transaction_version -> Int8,
+        last_transaction_block_height -> Int8,
+        last_transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     event_processing_log (id) {
         id -> Int4,
@@ -92,6 +127,29 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+    }
+}
+
+diesel::table! {
+    market_state_history (transaction_version, write_set_change_index) {
+        transaction_version -> Int8,
+        write_set_change_index -> Int8,
+        #[max_length = 66]
+        resource_address -> Varchar,
+        resource_type -> Text,
+        market_id -> Nullable<Int8>,
+        total_pool -> Nullable<Int8>,
+        yes_pool -> Nullable<Int8>,
+        no_pool -> Nullable<Int8>,
+        yield_deposited -> Nullable<Int8>,
+        resolved -> Nullable<Bool>,
+        data -> Nullable<Jsonb>,
+        is_deleted -> Bool,
+        transaction_block_height -> Int8,
+        transaction_timestamp -> Timestamp,
+        inserted_at -> Timestamp,
     }
 }
 
@@ -109,6 +167,11 @@ diesel::table! {
         outcome -> Nullable<Bool>,
         total_yield_earned -> Nullable<Int8>,
         resolution_transaction_version -> Nullable<Int8>,
+        extra -> Nullable<Jsonb>,
+        #[max_length = 66]
+        creator_addr -> Nullable<Varchar>,
+        asset_type -> Text,
+        transaction_timestamp -> Nullable<Timestamp>,
     }
 }
 
@@ -144,6 +207,22 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    onchain_users (address) {
+        #[max_length = 66]
+        address -> Varchar,
+        first_seen_version -> Int8,
+        first_seen_timestamp -> Timestamp,
+        last_seen_version -> Int8,
+        last_seen_timestamp -> Timestamp,
+        activity_count -> Int8,
+        bet_count -> Int8,
+        claim_count -> Int8,
+        markets_created_count -> Int8,
+        inserted_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     protocol_fees (fee_id) {
         fee_id -> Int8,
@@ -152,6 +231,28 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    protocol_snapshots (period, bucket_start, yield_protocol_addr, asset_type) {
+        #[max_length = 4]
+        period -> Varchar,
+        bucket_start -> Timestamp,
+        #[max_length = 66]
+        yield_protocol_addr -> Varchar,
+        asset_type -> Text,
+        tvl -> Numeric,
+        volume -> Numeric,
+        bet_count -> Int8,
+        active_market_count -> Int8,
+        unique_bettor_count -> Int8,
+        yield_deposited -> Numeric,
+        fees_collected -> Numeric,
+        updated_at -> Timestamp,
     }
 }
 
@@ -204,6 +305,9 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
     }
 }
 
@@ -217,6 +321,19 @@ diesel::table! {
         transaction_version -> Int8,
         transaction_block_height -> Int8,
         inserted_at -> Timestamp,
+        extra -> Nullable<Jsonb>,
+        transaction_timestamp -> Nullable<Timestamp>,
+        event_index -> Nullable<Int8>,
+    }
+}
+
+diesel::table! {
+    yield_protocols (address) {
+        #[max_length = 66]
+        address -> Varchar,
+        name -> Text,
+        backend_protocol_id -> Nullable<Text>,
+        updated_at -> Timestamp,
     }
 }
 
@@ -243,20 +360,26 @@ diesel::joinable!(yield_deposits -> markets (market_id));
 diesel::joinable!(yield_records -> protocols (protocolId));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    asset_metadata,
     bets,
     bets_extended,
     blockchain_events,
+    current_market_state,
     event_processing_log,
     fee_records,
     indexer_state,
     market_resolutions,
+    market_state_history,
     markets,
     markets_extended,
+    onchain_users,
     protocol_fees,
+    protocol_snapshots,
     protocols,
     sync_status,
     users,
     winnings_claims,
     yield_deposits,
+    yield_protocols,
     yield_records,
 );
@@ -0,0 +1,144 @@
+//! Hourly and daily `protocol_snapshots` for the stats page's historical charts.
+//!
+//! Buckets are in block time and recomputed from the indexer's own tables after each batch, so
+//! re-processing is idempotent and a backfill produces the same history as live indexing. The
+//! recomputed range starts at the earlier of the batch's first bucket and the latest existing
+//! snapshot, which fills in the buckets of quiet periods without Kizo transactions.
+
+use crate::KizoRows;
+use aptos_indexer_processor_sdk::postgres::utils::database::{
+    execute_with_better_error, ArcDbPool,
+};
+use diesel::sql_types::{Text, Timestamp};
+use tracing::{error, info};
+
+const PERIODS: [&str; 2] = ["hour", "day"];
+
+/// `$1` is the period, `$2` and `$3` the first and last block time of the batch.
+const REFRESH_SNAPSHOTS_SQL: &str = r#"
+WITH buckets AS (
+    SELECT bucket_start, bucket_start + ('1 ' || $1)::INTERVAL AS bucket_end
+    FROM generate_series(
+        LEAST(
+            date_trunc($1, $2),
+            COALESCE(
+                (SELECT MAX(bucket_start) FROM protocol_snapshots WHERE period = $1),
+                date_trunc($1, $2)
+            )
+        ),
+        date_trunc($1, $3),
+        ('1 ' || $1)::INTERVAL
+    ) AS bucket_start
+),
+market_lifetimes AS (
+    SELECT
+        m.market_id,
+        m.asset_type,
+        m.yield_protocol_addr,
+        m.transaction_timestamp AS created_at,
+        r.transaction_timestamp AS resolved_at
+    FROM markets m
+    LEFT JOIN market_resolutions r ON r.market_id = m.market_id
+    WHERE m.transaction_timestamp IS NOT NULL
+),
+facts AS (
+    -- Pools of markets still open at the end of the bucket
+    SELECT
+        b.bucket_start, m.asset_type, m.yield_protocol_addr, m.market_id AS open_market_id,
+        (
+            SELECT COALESCE(SUM(bet.amount), 0) FROM bets bet
+            WHERE bet.market_id = m.market_id AND bet.transaction_timestamp < b.bucket_end
+        ) AS tvl,
+        0 AS volume, 0 AS bets, NULL AS bettor, 0 AS yield_deposited, 0 AS fees_collected
+    FROM buckets b
+    JOIN market_lifetimes m ON m.created_at < b.bucket_end
+        AND (m.resolved_at IS NULL OR m.resolved_at >= b.bucket_end)
+    UNION ALL
+    SELECT
+        b.bucket_start, m.asset_type, m.yield_protocol_addr, NULL, 0,
+        bet.amount, 1, bet.user_addr, 0, 0
+    FROM buckets b
+    JOIN bets bet ON bet.transaction_timestamp >= b.bucket_start
+        AND bet.transaction_timestamp < b.bucket_end
+    JOIN markets m ON m.market_id = bet.market_id
+    UNION ALL
+    -- Deposits are attributed to the protocol they went to
+    SELECT b.bucket_start, m.asset_type, d.protocol_addr, NULL, 0, 0, 0, NULL, d.amount, 0
+    FROM buckets b
+    JOIN yield_deposits d ON d.transaction_timestamp >= b.bucket_start
+        AND d.transaction_timestamp < b.bucket_end
+    JOIN markets m ON m.market_id = d.market_id
+    UNION ALL
+    SELECT b.bucket_start, m.asset_type, m.yield_protocol_addr, NULL, 0, 0, 0, NULL, 0, f.fee_amount
+    FROM buckets b
+    JOIN protocol_fees f ON f.transaction_timestamp >= b.bucket_start
+        AND f.transaction_timestamp < b.bucket_end
+    JOIN markets m ON m.market_id = f.market_id
+)
+INSERT INTO protocol_snapshots (
+    period, bucket_start, yield_protocol_addr, asset_type, tvl, volume, bet_count,
+    active_market_count, unique_bettor_count, yield_deposited, fees_collected, updated_at
+)
+SELECT
+    $1,
+    bucket_start,
+    -- '' is the protocol-wide row
+    COALESCE(yield_protocol_addr, ''),
+    asset_type,
+    SUM(tvl),
+    SUM(volume),
+    SUM(bets),
+    COUNT(DISTINCT open_market_id),
+    COUNT(DISTINCT bettor),
+    SUM(yield_deposited),
+    SUM(fees_collected),
+    NOW()
+FROM facts
+GROUP BY GROUPING SETS (
+    (bucket_start, asset_type),
+    (bucket_start, asset_type, yield_protocol_addr)
+)
+ON CONFLICT (period, bucket_start, yield_protocol_addr, asset_type) DO UPDATE SET
+    tvl = EXCLUDED.tvl,
+    volume = EXCLUDED.volume,
+    bet_count = EXCLUDED.bet_count,
+    active_market_count = EXCLUDED.active_market_count,
+    unique_bettor_count = EXCLUDED.unique_bettor_count,
+    yield_deposited = EXCLUDED.yield_deposited,
+    fees_collected = EXCLUDED.fees_collected,
+    updated_at = NOW()"#;
+
+/// Refreshes the snapshots covering a processed batch. Like the indexer's own inserts, failures
+/// are logged and don't stop the processor; the next batch recomputes the same buckets.
+pub async fn refresh(conn_pool: ArcDbPool, rows: &KizoRows) {
+    let timestamps = rows
+        .markets
+        .iter()
+        .map(|r| r.transaction_timestamp)
+        .chain(rows.bets.iter().map(|r| r.transaction_timestamp))
+        .chain(
+            rows.market_resolutions
+                .iter()
+                .map(|r| r.transaction_timestamp),
+        )
+        .chain(rows.yield_deposits.iter().map(|r| r.transaction_timestamp))
+        .chain(rows.protocol_fees.iter().map(|r| r.transaction_timestamp))
+        .flatten();
+    let Some((first, last)) = timestamps.fold(None, |range, ts| match range {
+        None => Some((ts, ts)),
+        Some((first, last)) => Some((ts.min(first), ts.max(last))),
+    }) else {
+        return;
+    };
+
+    for period in PERIODS {
+        let query = diesel::sql_query(REFRESH_SNAPSHOTS_SQL)
+            .bind::<Text, _>(period)
+            .bind::<Timestamp, _>(first)
+            .bind::<Timestamp, _>(last);
+        match execute_with_better_error(conn_pool.clone(), query).await {
+            Ok(count) => info!("Refreshed {} {} snapshots", count, period),
+            Err(e) => error!("Failed to refresh {} snapshots: {:?}", period, e),
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Finds the version the Kizo package was published at, so a fresh deployment doesn't have to
+//! stream from genesis or have `starting_version` looked up by hand.
+//!
+//! Enabled by pointing `STARTING_VERSION_DISCOVERY_URL` at a fullnode REST API (e.g.
+//! `https://api.testnet.aptoslabs.com/v1`). Only used when there is no checkpoint yet and the
+//! config doesn't set `starting_version`. The search bisects the node's ledger history on whether
+//! `0x1::code::PackageRegistry` exists under the Kizo address, which takes a few dozen requests.
+//! `STARTING_VERSION_DISCOVERY_ADDRESS` overrides the address, e.g. for a deployment of the
+//! package on another network.
+
+use crate::{KIZO_ADDRESS, PROCESSOR_NAME};
+use anyhow::{bail, Context, Result};
+use aptos_indexer_processor_sdk::{
+    postgres::{
+        basic_processor::ProcessConfig, models::processor_status::ProcessorStatusQuery,
+        utils::database::new_db_pool,
+    },
+    utils::convert::{deserialize_u64_from_string_or_number, standardize_address},
+};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::{env, future::Future, time::Duration};
+use tracing::{info, warn};
+
+const PACKAGE_REGISTRY: &str = "0x1::code::PackageRegistry";
+
+#[derive(Deserialize)]
+struct NodeLedgerInfo {
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
+    ledger_version: u64,
+    #[serde(deserialize_with = "deserialize_u64_from_string_or_number")]
+    oldest_ledger_version: u64,
+}
+
+/// Sets `starting_version` in `config` to the deployment version of the Kizo package when
+/// discovery is enabled and the processor has neither a checkpoint nor a configured start.
+pub async fn discover_if_needed(config: &mut ProcessConfig) -> Result<()> {
+    let Ok(node_url) = env::var("STARTING_VERSION_DISCOVERY_URL") else {
+        return Ok(());
+    };
+    if config.transaction_stream_config.starting_version.is_some() {
+        return Ok(());
+    }
+    let pool = new_db_pool(&config.postgres_config.connection_string, Some(1))
+        .await
+        .context("Failed to create connection pool")?;
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get a database connection")?;
+    if ProcessorStatusQuery::get_by_processor(PROCESSOR_NAME, &mut conn)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    let address = env::var("STARTING_VERSION_DISCOVERY_ADDRESS")
+        .map(|address| standardize_address(&address))
+        .unwrap_or_else(|_| KIZO_ADDRESS.to_string());
+    let version = discover(node_url.trim_end_matches('/'), &address).await?;
+    info!("Starting from version {version}, where {address} was first published");
+    config.transaction_stream_config.starting_version = Some(version);
+    Ok(())
+}
+
+/// Returns the first version at which `address` holds a published package.
+async fn discover(node_url: &str, address: &str) -> Result<u64> {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(30))
+        .build()?;
+    let ledger: NodeLedgerInfo = client
+        .get(node_url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Failed to read ledger info")?;
+
+    let client = &client;
+    first_version_where(
+        ledger.oldest_ledger_version,
+        ledger.ledger_version,
+        |version| has_package(client, node_url, address, version),
+    )
+    .await
+    .with_context(|| format!("Failed to find when {address} was published on {node_url}"))
+}
+
+/// Bisects `[oldest, latest]` for the first version at which `holds` is true, given that it stays
+/// true from there on. Returns `oldest` if it already holds there, since the node keeps nothing
+/// older.
+async fn first_version_where<F, Fut>(oldest: u64, latest: u64, holds: F) -> Result<u64>
+where
+    F: Fn(u64) -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let (mut low, mut high) = (oldest, latest);
+    if !holds(high).await? {
+        bail!("Not published as of version {high}");
+    }
+    if holds(low).await? {
+        warn!("Published before version {low}, the oldest one the node keeps; starting there");
+        return Ok(low);
+    }
+    // Invariant: false at `low`, true at `high`.
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if holds(mid).await? {
+            high = mid;
+        } else {
+            low = mid;
+        }
+    }
+    Ok(high)
+}
+
+async fn has_package(
+    client: &reqwest::Client,
+    node_url: &str,
+    address: &str,
+    version: u64,
+) -> Result<bool> {
+    let response = client
+        .get(format!(
+            "{node_url}/accounts/{address}/resource/{PACKAGE_REGISTRY}"
+        ))
+        .query(&[("ledger_version", version)])
+        .send()
+        .await?;
+    match response.status() {
+        StatusCode::OK => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        status => bail!("Unexpected status {status} looking up {address} at version {version}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn published_from(oldest: u64, latest: u64, published: u64) -> Result<u64> {
+        first_version_where(
+            oldest,
+            latest,
+            |version| async move { Ok(version >= published) },
+        )
+        .await
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_first_version_where() {
+        assert_eq!(published_from(10, 1000, 437).await.unwrap(), 437);
+        assert_eq!(published_from(10, 1000, 11).await.unwrap(), 11);
+        assert_eq!(published_from(10, 1000, 1000).await.unwrap(), 1000);
+        // Published before the oldest version the node keeps
+        assert_eq!(published_from(10, 1000, 3).await.unwrap(), 10);
+        assert!(published_from(10, 1000, 1001).await.is_err());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::needless_return)]
+    async fn test_first_version_where_propagates_errors() {
+        let result = first_version_where(10, 1000, |version| async move {
+            if version == 505 {
+                bail!("Node unavailable");
+            }
+            Ok(version >= 437)
+        })
+        .await;
+        assert_eq!(result.unwrap_err().to_string(), "Node unavailable");
+    }
+}
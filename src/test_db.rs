@@ -0,0 +1,44 @@
+//! A migrated Kizo database in a Postgres container, for tests that run SQL.
+
+use crate::MIGRATIONS;
+use aptos_indexer_processor_sdk::{
+    postgres::{
+        utils::database::{new_db_pool, run_migrations, ArcDbPool},
+        SDK_MIGRATIONS,
+    },
+    testing_framework::database::{PostgresTestDatabase, TestDatabase},
+};
+use diesel::{sql_types::Text, QueryableByName};
+use diesel_async::{RunQueryDsl, SimpleAsyncConnection};
+
+/// Starts Postgres and applies the Kizo and SDK migrations. The container stops when the
+/// returned database is dropped.
+pub async fn migrated_db() -> (PostgresTestDatabase, ArcDbPool) {
+    let mut database = PostgresTestDatabase::new();
+    database.setup().await.unwrap();
+    let pool = new_db_pool(&database.get_db_url(), Some(4)).await.unwrap();
+    run_migrations(database.get_db_url(), pool.clone(), MIGRATIONS).await;
+    run_migrations(database.get_db_url(), pool.clone(), SDK_MIGRATIONS).await;
+    (database, pool)
+}
+
+pub async fn batch_execute(pool: &ArcDbPool, sql: &str) {
+    pool.get().await.unwrap().batch_execute(sql).await.unwrap();
+}
+
+#[derive(QueryableByName)]
+struct Line {
+    #[diesel(sql_type = Text)]
+    line: String,
+}
+
+/// Runs `sql`, which selects a single text column named `line`.
+pub async fn lines(pool: &ArcDbPool, sql: &str) -> Vec<String> {
+    diesel::sql_query(sql)
+        .load::<Line>(&mut pool.get().await.unwrap())
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| row.line)
+        .collect()
+}
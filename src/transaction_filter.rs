@@ -0,0 +1,56 @@
+//! Server-side transaction filter for the Kizo module, so the data service only streams
+//! transactions the indexer can use instead of the whole chain.
+//!
+//! A transaction matches when it emits an event declared in the Kizo module or calls one of its
+//! entry functions. The latter keeps resource-only changes (`market_state_history`) and the
+//! sender (`onchain_users`) of transactions that don't emit events. A `transaction_filter` set in
+//! the config is combined with it, so it can only narrow the stream further. Filters can't match
+//! write-set resources, so asset metadata is looked up with `asset_lookup` instead.
+
+use crate::{KIZO_ADDRESS, KIZO_MODULE};
+use anyhow::{Context, Result};
+use aptos_indexer_processor_sdk::aptos_indexer_transaction_stream::{
+    BooleanTransactionFilter, EntryFunctionFilterBuilder, EventFilterBuilder,
+    MoveStructTagFilterBuilder, TransactionStreamConfig, UserTransactionFilterBuilder,
+    UserTransactionPayloadFilterBuilder,
+};
+use tracing::info;
+
+/// Transactions that emit a Kizo event or call a Kizo entry function.
+pub fn kizo_filter() -> Result<BooleanTransactionFilter> {
+    let events = EventFilterBuilder::default()
+        .struct_type(
+            MoveStructTagFilterBuilder::default()
+                .address(KIZO_ADDRESS.to_string())
+                .module(KIZO_MODULE.to_string())
+                .build()?,
+        )
+        .build()?;
+    let entry_functions = UserTransactionFilterBuilder::default()
+        .payload(
+            UserTransactionPayloadFilterBuilder::default()
+                .function(
+                    EntryFunctionFilterBuilder::default()
+                        .address(KIZO_ADDRESS.to_string())
+                        .module(KIZO_MODULE.to_string())
+                        .build()?,
+                )
+                .build()?,
+        )
+        .build()?;
+    Ok(BooleanTransactionFilter::from(events).or(entry_functions))
+}
+
+/// Sets the stream's filter to the Kizo filter, AND-ed with any filter from the config.
+pub fn apply(config: &mut TransactionStreamConfig) -> Result<()> {
+    let filter = kizo_filter().context("Failed to build the Kizo transaction filter")?;
+    let filter = match config.transaction_filter.take() {
+        Some(configured) => {
+            info!("Combining the configured transaction filter with the Kizo filter");
+            filter.and(configured)
+        },
+        None => filter,
+    };
+    config.transaction_filter = Some(filter);
+    Ok(())
+}
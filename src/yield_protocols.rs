@@ -0,0 +1,91 @@
+//! Registry of the yield protocols markets deposit into, so stats can show names instead of raw
+//! addresses and the backend projection can link deposits to the backend's `protocols`.
+//!
+//! The registry lives in a top-level `yield_protocols` section of the config file:
+//!
+//! ```yaml
+//! yield_protocols:
+//!   - address: "0x<protocol address>"
+//!     name: amnis
+//!     backend_protocol_id: amnis
+//! ```
+//!
+//! It is synced into `yield_protocols` on startup. The config is the source of truth: protocols
+//! removed from it are removed from the table. Leaving the section out keeps the table as is.
+
+use crate::{models::YieldProtocol, schema};
+use anyhow::{Context, Result};
+use aptos_indexer_processor_sdk::{
+    postgres::utils::database::ArcDbPool, utils::convert::standardize_address,
+};
+use diesel::{upsert::excluded, ExpressionMethods, QueryDsl};
+use diesel_async::{AsyncConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
+use serde::Deserialize;
+use tracing::info;
+
+/// The parts of the config file this module reads. Everything else is ignored.
+#[derive(Deserialize)]
+pub struct YieldProtocolRegistry {
+    #[serde(default)]
+    pub yield_protocols: Option<Vec<YieldProtocolConfig>>,
+}
+
+#[derive(Deserialize)]
+pub struct YieldProtocolConfig {
+    pub address: String,
+    pub name: String,
+    #[serde(default)]
+    pub backend_protocol_id: Option<String>,
+}
+
+/// Replaces the contents of `yield_protocols` with the configured registry.
+pub async fn sync(pool: &ArcDbPool, registry: YieldProtocolRegistry) -> Result<()> {
+    let Some(configured) = registry.yield_protocols else {
+        return Ok(());
+    };
+    let now = chrono::Utc::now().naive_utc();
+    let protocols: Vec<YieldProtocol> = configured
+        .into_iter()
+        .map(|protocol| YieldProtocol {
+            address: standardize_address(&protocol.address),
+            name: protocol.name,
+            backend_protocol_id: protocol.backend_protocol_id,
+            updated_at: now,
+        })
+        .collect();
+    let addresses: Vec<String> = protocols.iter().map(|p| p.address.clone()).collect();
+    let count = protocols.len();
+
+    let mut conn = pool
+        .get()
+        .await
+        .context("Failed to get a database connection")?;
+    conn.transaction::<_, diesel::result::Error, _>(|conn| {
+        async move {
+            use schema::yield_protocols::dsl::*;
+            diesel::delete(yield_protocols.filter(address.ne_all(addresses)))
+                .execute(conn)
+                .await?;
+            if !protocols.is_empty() {
+                diesel::insert_into(yield_protocols)
+                    .values(&protocols)
+                    .on_conflict(address)
+                    .do_update()
+                    .set((
+                        name.eq(excluded(name)),
+                        backend_protocol_id.eq(excluded(backend_protocol_id)),
+                        updated_at.eq(excluded(updated_at)),
+                    ))
+                    .execute(conn)
+                    .await?;
+            }
+            Ok(())
+        }
+        .scope_boxed()
+    })
+    .await
+    .context("Failed to sync the yield protocol registry")?;
+    info!("Synced {} yield protocols", count);
+    Ok(())
+}